@@ -0,0 +1,265 @@
+use crate::models::{Command, SkippedLine};
+
+/// Group given to commands created from shell aliases.
+pub const ALIAS_GROUP: &str = "Aliases";
+
+/// A shell alias definition, e.g. `alias build='cargo build'`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alias {
+    pub name: String,
+    pub script: String,
+    /// One-based line number in the aliases file
+    pub line: usize,
+}
+
+impl Alias {
+    /// The command for this alias, with an ID derived from its name so importing the
+    /// same file again finds the commands it already created.
+    pub fn to_command(&self) -> Command {
+        Command {
+            id: format!("alias-{}", self.name),
+            name: self.name.clone(),
+            script: self.script.clone(),
+            group: Some(ALIAS_GROUP.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Turns the aliases in `text` into new commands, skipping malformed lines and aliases
+/// that already have a command in `existing`.
+///
+/// As in the shell, a later definition of the same alias replaces an earlier one; the
+/// earlier line is reported as skipped.
+pub fn import(text: &str, existing: &[Command]) -> (Vec<Command>, Vec<SkippedLine>) {
+    let (aliases, mut skipped) = parse_aliases(text);
+    let line_text = |line: usize| text.lines().nth(line - 1).unwrap_or_default().trim();
+
+    let mut commands: Vec<Command> = Vec::new();
+    for alias in &aliases {
+        let later = aliases
+            .iter()
+            .rev()
+            .find(|other| other.name == alias.name && other.line > alias.line);
+        let command = alias.to_command();
+        let reason = if let Some(later) = later {
+            format!("Redefined on line {}", later.line)
+        } else if existing.iter().any(|c| c.id == command.id) {
+            format!("Already imported as '{}'", command.name)
+        } else {
+            commands.push(command);
+            continue;
+        };
+        skipped.push(SkippedLine {
+            line: alias.line,
+            text: line_text(alias.line).to_string(),
+            reason,
+        });
+    }
+    skipped.sort_by_key(|s| s.line);
+    (commands, skipped)
+}
+
+/// Parses the `alias name='value'` lines of a file like `~/.bash_aliases`.
+///
+/// Values can be single-quoted (including the `'\''` idiom for an embedded quote),
+/// double-quoted or a bare word, and may be followed by a `#` comment. Blank lines and
+/// comments are ignored; any other line that isn't a single well-formed alias is
+/// returned as skipped, with the reason.
+pub fn parse_aliases(text: &str) -> (Vec<Alias>, Vec<SkippedLine>) {
+    let mut aliases = Vec::new();
+    let mut skipped = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line) {
+            Ok((name, script)) => aliases.push(Alias {
+                name,
+                script,
+                line: index + 1,
+            }),
+            Err(reason) => skipped.push(SkippedLine {
+                line: index + 1,
+                text: line.to_string(),
+                reason,
+            }),
+        }
+    }
+    (aliases, skipped)
+}
+
+fn parse_line(line: &str) -> Result<(String, String), String> {
+    let rest = line
+        .strip_prefix("alias")
+        .filter(|rest| rest.starts_with([' ', '\t']))
+        .ok_or_else(|| "Not an alias definition".to_string())?
+        .trim_start();
+    let (name, value) = rest
+        .split_once('=')
+        .ok_or_else(|| "Missing '=' after the alias name".to_string())?;
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || "'\"$`\\".contains(c)) {
+        return Err(format!("Invalid alias name '{}'", name));
+    }
+
+    let (script, trailing) = parse_word(value)?;
+    let trailing = trailing.trim_start();
+    if !trailing.is_empty() && !trailing.starts_with('#') {
+        return Err("Unexpected text after the alias value".to_string());
+    }
+    if script.trim().is_empty() {
+        return Err(format!("Alias '{}' is empty", name));
+    }
+    Ok((name.to_string(), script))
+}
+
+/// Reads one shell word from the start of `text`, returning it unquoted along with
+/// whatever follows it.
+fn parse_word(text: &str) -> Result<(String, &str), String> {
+    let mut word = String::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(index, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => return Ok((word, &text[index..])),
+            '\'' => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => word.push(c),
+                        None => return Err("Unclosed single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c @ ('"' | '\\' | '$' | '`'))) => word.push(c),
+                            Some((_, c)) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("Unclosed double quote".to_string()),
+                        },
+                        Some((_, c)) => word.push(c),
+                        None => return Err("Unclosed double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                chars.next();
+                if let Some((_, c)) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c => {
+                chars.next();
+                word.push(c);
+            }
+        }
+    }
+    Ok((word, ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_aliases() {
+        let text = r#"
+# Shortcuts
+alias build='cargo build'
+alias gs="git status --short"   # quick status
+alias ll=ls
+alias say='echo it'\''s done'
+alias esc="echo \"hi\" \$HOME"
+
+export EDITOR=vim
+alias broken='unterminated
+alias noequals
+alias empty=''
+alias two=a b
+"#;
+        let (aliases, skipped) = parse_aliases(text);
+
+        let parsed: Vec<(&str, &str, usize)> = aliases
+            .iter()
+            .map(|a| (a.name.as_str(), a.script.as_str(), a.line))
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                ("build", "cargo build", 3),
+                ("gs", "git status --short", 4),
+                ("ll", "ls", 5),
+                ("say", "echo it's done", 6),
+                ("esc", "echo \"hi\" $HOME", 7),
+            ]
+        );
+
+        let reasons: Vec<(usize, &str)> = skipped
+            .iter()
+            .map(|s| (s.line, s.reason.as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (9, "Not an alias definition"),
+                (10, "Unclosed single quote"),
+                (11, "Missing '=' after the alias name"),
+                (12, "Alias 'empty' is empty"),
+                (13, "Unexpected text after the alias value"),
+            ]
+        );
+        assert_eq!(skipped[0].text, "export EDITOR=vim");
+    }
+
+    #[test]
+    fn test_import_skips_redefined_and_existing_aliases() {
+        let text = "alias ll='ls -l'\nalias gs='git status'\nbogus\nalias ll='ls -la'\n";
+        let existing = vec![Command {
+            id: "alias-gs".to_string(),
+            name: "gs".to_string(),
+            ..Default::default()
+        }];
+
+        let (commands, skipped) = import(text, &existing);
+        let scripts: Vec<(&str, &str)> = commands
+            .iter()
+            .map(|c| (c.id.as_str(), c.script.as_str()))
+            .collect();
+        assert_eq!(scripts, vec![("alias-ll", "ls -la")]);
+
+        let reasons: Vec<(usize, &str, &str)> = skipped
+            .iter()
+            .map(|s| (s.line, s.text.as_str(), s.reason.as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (1, "alias ll='ls -l'", "Redefined on line 4"),
+                (2, "alias gs='git status'", "Already imported as 'gs'"),
+                (3, "bogus", "Not an alias definition"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alias_to_command() {
+        let alias = Alias {
+            name: "build".to_string(),
+            script: "cargo build".to_string(),
+            line: 1,
+        };
+        let command = alias.to_command();
+        assert_eq!(command.id, "alias-build");
+        assert_eq!(command.name, "build");
+        assert_eq!(command.script, "cargo build");
+        assert_eq!(command.group.as_deref(), Some(ALIAS_GROUP));
+    }
+}
@@ -0,0 +1,2060 @@
+use crate::error::AppError;
+use crate::models::{
+    BatchOp, BatchOpResult, Command, Config, StoreIssue, StoreIssueKind, TrashedCommand,
+};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Fails with `ReadOnly` when the config's `read_only` presentation mode is on.
+///
+/// Reads and execution stay allowed; only changes to the commands are refused.
+pub fn ensure_writable(config: &Config) -> Result<(), AppError> {
+    if config.read_only.unwrap_or(false) {
+        return Err(AppError::ReadOnly);
+    }
+    Ok(())
+}
+
+/// Checks a command's `min_interval_secs` throttle against when it last started.
+///
+/// Unlike refusing a second concurrent run, this applies even if the previous run has
+/// already finished. Fails with `RateLimited`, holding the whole seconds left to wait
+/// (rounded up), when `now` is too soon after `last_run`.
+pub fn check_rate_limit(
+    command: &Command,
+    last_run: Option<Instant>,
+    now: Instant,
+) -> Result<(), AppError> {
+    let (Some(secs), Some(last_run)) = (command.min_interval_secs, last_run) else {
+        return Ok(());
+    };
+    let elapsed = now.saturating_duration_since(last_run);
+    match Duration::from_secs(secs).checked_sub(elapsed) {
+        Some(left) if !left.is_zero() => {
+            let wait = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+            Err(AppError::RateLimited(wait))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Validates user-editable fields before a command is saved.
+///
+/// Scripts (and each of `steps`) must be at most `max_script_length` bytes and free of
+/// null bytes, so a pasted binary blob can't corrupt the store or confuse the shell.
+///
+/// An `output_filter` must be a valid regex, so a typo is reported when saving rather
+/// than on every run.
+///
+/// # Returns
+///
+/// * `Ok(())` - The command can be stored
+/// * `Err(AppError)` - `Invalid`, describing the first invalid field
+pub fn validate(command: &Command, max_script_length: usize) -> Result<(), AppError> {
+    if command.script.len() > max_script_length {
+        return Err(AppError::Invalid(format!(
+            "Script is too long: {} bytes (limit is {} bytes)",
+            command.script.len(),
+            max_script_length
+        )));
+    }
+    if command.script.contains('\0') {
+        return Err(AppError::Invalid("Script contains null bytes".to_string()));
+    }
+    for (i, step) in command.steps.iter().flatten().enumerate() {
+        if step.len() > max_script_length {
+            return Err(AppError::Invalid(format!(
+                "Step {} is too long: {} bytes (limit is {} bytes)",
+                i + 1,
+                step.len(),
+                max_script_length
+            )));
+        }
+        if step.contains('\0') {
+            return Err(AppError::Invalid(format!(
+                "Step {} contains null bytes",
+                i + 1
+            )));
+        }
+    }
+    if command
+        .macro_steps
+        .iter()
+        .flatten()
+        .any(|id| *id == command.id)
+    {
+        return Err(AppError::Invalid("A macro can't run itself".to_string()));
+    }
+    if let Some(schedule) = command.schedule.as_deref().filter(|s| !s.trim().is_empty()) {
+        crate::schedule::Schedule::parse(schedule).map_err(AppError::Invalid)?;
+    }
+    if let Some(format) = command.output_format.as_deref() {
+        if !["json", "text"]
+            .iter()
+            .any(|f| format.eq_ignore_ascii_case(f))
+        {
+            return Err(AppError::Invalid(format!(
+                "Invalid output format '{}': expected json or text",
+                format
+            )));
+        }
+    }
+    if let Some(filter) = command.output_filter.as_deref() {
+        crate::executor::output_filter(filter).map_err(AppError::Invalid)?;
+    }
+    if let Some(pattern) = command.failure_pattern.as_deref() {
+        crate::executor::failure_pattern(pattern).map_err(AppError::Invalid)?;
+    }
+    if let Some(pattern) = command.progress_regex.as_deref() {
+        crate::stream::progress_pattern(pattern).map_err(AppError::Invalid)?;
+    }
+    if let Some(user) = command.run_as() {
+        if !is_user_name(user) {
+            return Err(AppError::Invalid(format!(
+                "Invalid run_as user '{}': expected a user name like postgres or DOMAIN\\name",
+                user
+            )));
+        }
+    }
+    if let Some(variables) = &command.variables {
+        let mut names = std::collections::HashSet::new();
+        for variable in variables {
+            if !is_variable_name(&variable.name) {
+                return Err(AppError::Invalid(format!(
+                    "Invalid variable name '{}': use letters, digits and underscores",
+                    variable.name
+                )));
+            }
+            if !names.insert(variable.name.as_str()) {
+                return Err(AppError::Invalid(format!(
+                    "Variable '{}' is declared twice",
+                    variable.name
+                )));
+            }
+        }
+    }
+    if let Some(name) = command
+        .env
+        .iter()
+        .flat_map(|env| env.keys())
+        .find(|name| !is_variable_name(name))
+    {
+        return Err(AppError::Invalid(format!(
+            "Invalid environment variable name '{}': use letters, digits and underscores",
+            name
+        )));
+    }
+    if let Some(nice) = command.nice {
+        crate::executor::check_nice(nice).map_err(AppError::Invalid)?;
+    }
+    if command
+        .success_exit_codes
+        .as_ref()
+        .is_some_and(Vec::is_empty)
+    {
+        return Err(AppError::Invalid(
+            "success_exit_codes must list at least one exit code".to_string(),
+        ));
+    }
+    if let Some(color) = command.color.as_deref() {
+        if !is_hex_color(color) {
+            return Err(AppError::Invalid(format!(
+                "Invalid color '{}': expected a hex string like #1E90FF",
+                color
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Process names too common to target by name alone; a kill script matching one of
+/// these would take down unrelated processes (other shells, editors, servers).
+const BROAD_PROCESS_NAMES: &[&str] = &[
+    "sh", "bash", "zsh", "fish", "node", "python", "python3", "ruby", "perl", "java",
+];
+
+/// Flags kill scripts that could stop processes other than the command's own.
+///
+/// [`validate_kill_script`] on `command`'s kill script, if it has one.
+pub fn kill_script_warnings(command: &Command) -> Vec<String> {
+    command
+        .kill_script
+        .as_deref()
+        .map(validate_kill_script)
+        .unwrap_or_default()
+}
+
+/// Looks at each `pkill`, `killall` and `kill` in the script (also behind `sudo`),
+/// warning about name patterns that are very short or a common program like `sh`,
+/// and about `kill -1` / `kill 0`, which signal every process or the whole group.
+/// Advisory only: saving never fails because of these.
+///
+/// # Returns
+///
+/// * `Vec<String>` - One warning per risky invocation; empty if none were found
+pub fn validate_kill_script(script: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let segments = script.split(['\n', ';', '&', '|']);
+    for segment in segments {
+        let words = shell_words(segment);
+        let tokens: Vec<&str> = words
+            .iter()
+            .map(String::as_str)
+            .skip_while(|t| *t == "sudo")
+            .collect();
+        let Some((program, args)) = tokens.split_first() else {
+            continue;
+        };
+        let invocation = tokens.join(" ");
+        match *program {
+            "pkill" | "killall" => {
+                for pattern in args.iter().filter(|a| !a.starts_with('-')) {
+                    if BROAD_PROCESS_NAMES.contains(pattern) || pattern.len() < 3 {
+                        warnings.push(format!(
+                            "`{}` matches every process named like '{}', not just this command's",
+                            invocation, pattern
+                        ));
+                    }
+                }
+            }
+            "kill" if matches!(args.last(), Some(&"-1") | Some(&"0")) => {
+                warnings.push(format!(
+                    "`{}` signals every process it can reach, not just this command's",
+                    invocation
+                ));
+            }
+            _ => {}
+        }
+    }
+    warnings
+}
+
+/// Splits a shell command into words, keeping quoted text together without its quotes.
+fn shell_words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Whether `value` can be passed to `sudo -u` / `runas /user:` as a single user name.
+///
+/// A leading `-` would be read as an option, and whitespace or quotes would split or
+/// break the argument.
+fn is_user_name(value: &str) -> bool {
+    !value.starts_with('-')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._-\\@$".contains(c))
+}
+
+/// Whether `value` can name a template or environment variable: an identifier like
+/// `image_tag`, which the shell can also `export`.
+pub fn is_variable_name(value: &str) -> bool {
+    value.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether `value` is a `#RRGGBB` hex color.
+fn is_hex_color(value: &str) -> bool {
+    value.len() == 7 && value.starts_with('#') && value[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Appends a batch of commands with all-or-nothing semantics.
+///
+/// Every entry is validated before anything is added: IDs must be non-empty and unique
+/// both within the batch and against the existing commands, each command must pass
+/// [`validate`] against `max_script_length`, and its shortcut must be neither reserved
+/// (unless in `reserved_allowed`) nor bound to an existing command or an earlier entry.
+///
+/// # Returns
+///
+/// * `Ok(usize)` - Number of commands added
+/// * `Err(AppError)` - Error naming the index and ID of the first offending entry;
+///   `commands` is left untouched
+pub fn add_many(
+    commands: &mut Vec<Command>,
+    mut batch: Vec<Command>,
+    max_script_length: usize,
+    reserved_allowed: &[String],
+) -> Result<usize, AppError> {
+    let mut seen: std::collections::HashSet<&str> =
+        commands.iter().map(|c| c.id.as_str()).collect();
+
+    for (index, command) in batch.iter().enumerate() {
+        if command.id.trim().is_empty() {
+            return Err(AppError::Invalid(format!(
+                "Command at index {} has an empty ID",
+                index
+            )));
+        }
+        validate(command, max_script_length)
+            .map_err(|e| AppError::Invalid(format!("Command at index {}: {}", index, e)))?;
+        if !seen.insert(command.id.as_str()) {
+            return Err(AppError::Invalid(format!(
+                "Command at index {} ('{}') has duplicate ID {}",
+                index, command.name, command.id
+            )));
+        }
+        crate::shortcuts::check_not_reserved(command, reserved_allowed)
+            .and_then(|()| crate::shortcuts::check_available(commands, command))
+            .and_then(|()| crate::shortcuts::check_available(&batch[..index], command))
+            .map_err(|e| match e {
+                AppError::ShortcutInvalid(message) => {
+                    AppError::ShortcutInvalid(format!("Command at index {}: {}", index, message))
+                }
+                e => e,
+            })?;
+    }
+
+    let added = batch.len();
+    for command in &mut batch {
+        crate::shortcuts::canonicalize(command);
+    }
+    commands.extend(batch);
+    Ok(added)
+}
+
+/// Looks up a command by ID.
+///
+/// # Returns
+///
+/// * `Ok(&Command)` - The matching command
+/// * `Err(AppError)` - `NotFound`
+pub fn find<'a>(commands: &'a [Command], id: &str) -> Result<&'a Command, AppError> {
+    commands
+        .iter()
+        .find(|c| c.id == id)
+        .ok_or_else(|| AppError::NotFound("Command not found".to_string()))
+}
+
+/// The commands to run for `id`, prerequisites first.
+///
+/// Follows `depends_on` transitively and returns every prerequisite once, each after
+/// its own prerequisites, with the command itself last. A command without
+/// dependencies comes back on its own.
+///
+/// # Returns
+///
+/// * `Ok(Vec<&Command>)` - The run order, ending with the command for `id`
+/// * `Err(AppError)` - `NotFound` for `id`, or `Invalid` naming a missing dependency or
+///   the commands forming a cycle
+pub fn run_order<'a>(commands: &'a [Command], id: &str) -> Result<Vec<&'a Command>, AppError> {
+    dependency_order(commands, find(commands, id)?)
+        .map_err(|issue| AppError::Invalid(issue.message))
+}
+
+/// [`run_order`] for a command already looked up, failing with the first unknown
+/// dependency or cycle as a `verify_store` issue against the command that names the
+/// unknown dependency or closes the cycle.
+fn dependency_order<'a>(
+    commands: &'a [Command],
+    command: &'a Command,
+) -> Result<Vec<&'a Command>, StoreIssue> {
+    fn visit<'a>(
+        commands: &'a [Command],
+        command: &'a Command,
+        path: &mut Vec<&'a str>,
+        order: &mut Vec<&'a Command>,
+    ) -> Result<(), StoreIssue> {
+        if order.iter().any(|c| c.id == command.id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|id| *id == command.id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(&command.id);
+            return Err(StoreIssue {
+                kind: StoreIssueKind::ReferenceCycle,
+                command_id: command.id.clone(),
+                message: format!("Dependency cycle: {}", cycle.join(" -> ")),
+            });
+        }
+
+        path.push(&command.id);
+        for dependency in command.depends_on.iter().flatten() {
+            let prerequisite = commands
+                .iter()
+                .find(|c| &c.id == dependency)
+                .ok_or_else(|| unknown_dependency(command, dependency))?;
+            visit(commands, prerequisite, path, order)?;
+        }
+        path.pop();
+        order.push(command);
+        Ok(())
+    }
+
+    let mut order = Vec::new();
+    visit(commands, command, &mut Vec::new(), &mut order)?;
+    Ok(order)
+}
+
+fn unknown_dependency(command: &Command, dependency: &str) -> StoreIssue {
+    StoreIssue {
+        kind: StoreIssueKind::DanglingReference,
+        command_id: command.id.clone(),
+        message: format!(
+            "'{}' depends on unknown command '{}'",
+            command.name, dependency
+        ),
+    }
+}
+
+/// The commands a macro runs, in order.
+///
+/// Macros can include other macros; the whole tree is checked up front so a run never
+/// loops.
+///
+/// # Returns
+///
+/// * `Ok(Vec<&Command>)` - The commands named in `command.macro_steps`
+/// * `Err(AppError)` - `Invalid` naming an unknown step or the macros forming a cycle
+pub fn macro_steps<'a>(
+    commands: &'a [Command],
+    command: &Command,
+) -> Result<Vec<&'a Command>, AppError> {
+    if let Some(issue) = macro_tree_issue(commands, command) {
+        return Err(AppError::Invalid(issue.message));
+    }
+    command
+        .macro_steps
+        .iter()
+        .flatten()
+        .map(|id| {
+            commands
+                .iter()
+                .find(|c| c.id == *id)
+                .ok_or_else(|| AppError::Invalid(unknown_step(command, id).message))
+        })
+        .collect()
+}
+
+/// The first unknown step or cycle in the macro tree under `command`, reported against
+/// the macro that holds the unknown step or closes the cycle.
+fn macro_tree_issue(commands: &[Command], command: &Command) -> Option<StoreIssue> {
+    fn visit(
+        commands: &[Command],
+        command: &Command,
+        path: &mut Vec<String>,
+    ) -> Result<(), StoreIssue> {
+        if let Some(start) = path.iter().position(|id| *id == command.id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(command.id.clone());
+            return Err(StoreIssue {
+                kind: StoreIssueKind::ReferenceCycle,
+                command_id: command.id.clone(),
+                message: format!("Macro cycle: {}", cycle.join(" -> ")),
+            });
+        }
+        path.push(command.id.clone());
+        for id in command.macro_steps.iter().flatten() {
+            let step = commands
+                .iter()
+                .find(|c| c.id == *id)
+                .ok_or_else(|| unknown_step(command, id))?;
+            visit(commands, step, path)?;
+        }
+        path.pop();
+        Ok(())
+    }
+
+    visit(commands, command, &mut Vec::new()).err()
+}
+
+fn unknown_step(command: &Command, id: &str) -> StoreIssue {
+    StoreIssue {
+        kind: StoreIssueKind::DanglingReference,
+        command_id: command.id.clone(),
+        message: format!("Macro '{}' runs unknown command '{}'", command.name, id),
+    }
+}
+
+/// Warnings for a command that was just removed from `commands` while macros still
+/// name it as a step; those macros fail until it is restored or they are edited.
+pub fn removal_warnings(commands: &[Command], id: &str) -> Vec<String> {
+    commands
+        .iter()
+        .filter(|c| c.macro_steps.iter().flatten().any(|step| step == id))
+        .map(|c| format!("Macro '{}' still runs this command", c.name))
+        .collect()
+}
+
+/// Sets `created_at` and `updated_at` on a command that is about to be added.
+pub fn stamp_created(command: &mut Command, now: &str) {
+    command.created_at = Some(now.to_string());
+    command.updated_at = Some(now.to_string());
+}
+
+/// Replaces the stored command with the same ID, merging server-owned fields.
+///
+/// Client-owned fields come from `command`. Server-owned fields (`created_at`,
+/// `run_count`, `last_run_at`, `last_exit_code`) are kept from the stored entry,
+/// falling back to the incoming value only when nothing is stored, and `updated_at`
+/// is set to `now`.
+///
+/// # Returns
+///
+/// * `Ok(())` - The command was replaced
+/// * `Err(AppError)` - `NotFound` or `Locked`
+pub fn update(commands: &mut [Command], mut command: Command, now: &str) -> Result<(), AppError> {
+    let existing = commands
+        .iter_mut()
+        .find(|c| c.id == command.id)
+        .ok_or_else(|| AppError::NotFound("Command not found".to_string()))?;
+
+    if existing.is_locked() {
+        return Err(AppError::Locked("Command is locked".to_string()));
+    }
+
+    command.created_at = existing.created_at.take().or(command.created_at);
+    command.run_count = existing.run_count.or(command.run_count);
+    if existing.last_run_at.is_some() {
+        command.last_run_at = existing.last_run_at.take();
+        command.last_exit_code = existing.last_exit_code;
+    }
+    command.updated_at = Some(now.to_string());
+    *existing = command;
+    Ok(())
+}
+
+/// Applies a list of add, update and delete operations in order.
+///
+/// Each op goes through the same checks as its single-command counterpart (an add
+/// also needs a non-empty, unused ID, and shortcuts must be neither reserved, unless in
+/// `reserved_allowed`, nor already bound); a failing op is skipped and the rest still
+/// apply, each seeing the effect of the ones before it.
+///
+/// # Returns
+///
+/// * `Vec<BatchOpResult>` - One result per op, in order, with the
+///   [`kill_script_warnings`] of each command saved
+/// * `Vec<Command>` - The commands removed by delete ops, for the trash
+pub fn apply_batch(
+    commands: &mut Vec<Command>,
+    ops: Vec<BatchOp>,
+    max_script_length: usize,
+    reserved_allowed: &[String],
+    now: &str,
+) -> (Vec<BatchOpResult>, Vec<Command>) {
+    let mut removed = Vec::new();
+    let results = ops
+        .into_iter()
+        .map(|op| {
+            let applied = match op {
+                BatchOp::Add { mut command } => {
+                    let warnings = kill_script_warnings(&command);
+                    add_checked(commands, &command, max_script_length, reserved_allowed).map(|()| {
+                        stamp_created(&mut command, now);
+                        crate::shortcuts::canonicalize(&mut command);
+                        commands.push(command);
+                        warnings
+                    })
+                }
+                BatchOp::Update { mut command } => {
+                    let warnings = kill_script_warnings(&command);
+                    validate(&command, max_script_length)
+                        .and_then(|()| crate::shortcuts::check_available(commands, &command))
+                        .and_then(|()| {
+                            crate::shortcuts::check_not_reserved(&command, reserved_allowed)
+                        })
+                        .and_then(|()| {
+                            crate::shortcuts::canonicalize(&mut command);
+                            update(commands, command, now)
+                        })
+                        .map(|()| warnings)
+                }
+                BatchOp::Delete { id } => remove(commands, &id).map(|command| {
+                    let warnings = match command {
+                        Some(_) => removal_warnings(commands, &id),
+                        None => Vec::new(),
+                    };
+                    removed.extend(command);
+                    warnings
+                }),
+            };
+            match applied {
+                Ok(warnings) => BatchOpResult {
+                    ok: true,
+                    error: None,
+                    warnings,
+                },
+                Err(e) => BatchOpResult {
+                    ok: false,
+                    error: Some(e),
+                    warnings: Vec::new(),
+                },
+            }
+        })
+        .collect();
+    (results, removed)
+}
+
+/// The checks a batch add op has to pass before `command` is appended.
+fn add_checked(
+    commands: &[Command],
+    command: &Command,
+    max_script_length: usize,
+    reserved_allowed: &[String],
+) -> Result<(), AppError> {
+    if command.id.trim().is_empty() {
+        return Err(AppError::Invalid("Command has an empty ID".to_string()));
+    }
+    if commands.iter().any(|c| c.id == command.id) {
+        return Err(AppError::Invalid(format!(
+            "A command with ID {} already exists",
+            command.id
+        )));
+    }
+    validate(command, max_script_length)?;
+    crate::shortcuts::check_available(commands, command)?;
+    crate::shortcuts::check_not_reserved(command, reserved_allowed)
+}
+
+/// Records a completed execution on the stored command.
+///
+/// Bumps `run_count` and sets `last_run_at` / `last_exit_code`.
+///
+/// # Arguments
+///
+/// * `started_at` - RFC 3339 timestamp of when the script was spawned
+/// * `exit_code` - The process exit code, `None` if it was killed by a signal
+///
+/// # Returns
+///
+/// * `Ok(())` - The command was updated
+/// * `Err(AppError)` - `NotFound` (e.g. it was deleted while running)
+pub fn record_run(
+    commands: &mut [Command],
+    id: &str,
+    started_at: &str,
+    exit_code: Option<i32>,
+) -> Result<(), AppError> {
+    let command = commands
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| AppError::NotFound("Command not found".to_string()))?;
+
+    command.run_count = Some(command.run_count.unwrap_or(0) + 1);
+    command.last_run_at = Some(started_at.to_string());
+    command.last_exit_code = exit_code;
+    Ok(())
+}
+
+/// Records a finished run of `id` in the store file at `path`, under the file's lock.
+///
+/// In `read_only` mode the file is left untouched and `Ok(false)` is returned; the run
+/// still goes to the history.
+pub fn record_run_in_store(
+    path: &Path,
+    config: &Config,
+    id: &str,
+    started_at: &str,
+    exit_code: Option<i32>,
+) -> Result<bool, AppError> {
+    if ensure_writable(config).is_err() {
+        return Ok(false);
+    }
+    crate::store::update_commands(path, config.save_options(), |commands| {
+        record_run(commands, id, started_at, exit_code)
+    })?;
+    Ok(true)
+}
+
+/// Removes the command with the given ID.
+///
+/// Removing an unknown ID succeeds (idempotent), but a locked command is never removed.
+/// Macros that still run the removed command are left as they are; see
+/// [`removal_warnings`].
+///
+/// # Returns
+///
+/// * `Ok(Some(Command))` - The removed command
+/// * `Ok(None)` - No command had that ID
+/// * `Err(AppError)` - `Locked`
+pub fn remove(commands: &mut Vec<Command>, id: &str) -> Result<Option<Command>, AppError> {
+    let Some(index) = commands.iter().position(|c| c.id == id) else {
+        return Ok(None);
+    };
+
+    if commands[index].is_locked() {
+        return Err(AppError::Locked("Command is locked".to_string()));
+    }
+
+    Ok(Some(commands.remove(index)))
+}
+
+/// Removes every command whose ID is in `ids`.
+///
+/// Unknown IDs are ignored. If any matching command is locked, nothing is removed.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Command>)` - The removed commands, in stored order
+/// * `Err(AppError)` - `Locked` naming the first locked match
+pub fn remove_many(commands: &mut Vec<Command>, ids: &[String]) -> Result<Vec<Command>, AppError> {
+    if let Some(locked) = commands
+        .iter()
+        .find(|c| ids.contains(&c.id) && c.is_locked())
+    {
+        return Err(AppError::Locked(format!(
+            "Command is locked: {}",
+            locked.name
+        )));
+    }
+
+    let (removed, kept) = commands.drain(..).partition(|c| ids.contains(&c.id));
+    *commands = kept;
+    Ok(removed)
+}
+
+/// Moves a command to `to_index` in the stored order, shifting the others.
+///
+/// `to_index` is clamped to the last position, so moving "down" past the end is a no-op.
+///
+/// # Returns
+///
+/// * `Ok(usize)` - The index the command ended up at
+/// * `Err(AppError)` - `NotFound`
+pub fn move_to(commands: &mut Vec<Command>, id: &str, to_index: usize) -> Result<usize, AppError> {
+    let from = commands
+        .iter()
+        .position(|c| c.id == id)
+        .ok_or_else(|| AppError::NotFound("Command not found".to_string()))?;
+
+    let to = to_index.min(commands.len() - 1);
+    let command = commands.remove(from);
+    commands.insert(to, command);
+    Ok(to)
+}
+
+/// Flips the `favorite` flag of a command.
+///
+/// Allowed on locked commands too, since pinning doesn't change what the command does.
+///
+/// # Returns
+///
+/// * `Ok(bool)` - The new favorite state
+/// * `Err(AppError)` - `NotFound`
+pub fn toggle_favorite(commands: &mut [Command], id: &str) -> Result<bool, AppError> {
+    let command = commands
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| AppError::NotFound("Command not found".to_string()))?;
+
+    let favorite = !command.favorite.unwrap_or(false);
+    command.favorite = Some(favorite);
+    Ok(favorite)
+}
+
+/// Pauses or resumes a command's schedule.
+///
+/// Like favorites, this is allowed on locked commands.
+///
+/// # Returns
+///
+/// * `Ok(())` - The flag was set
+/// * `Err(AppError)` - `NotFound`, or `Invalid` if the command has no schedule
+pub fn set_schedule_enabled(
+    commands: &mut [Command],
+    id: &str,
+    enabled: bool,
+) -> Result<(), AppError> {
+    let command = commands
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| AppError::NotFound("Command not found".to_string()))?;
+
+    if !command
+        .schedule
+        .as_deref()
+        .is_some_and(|s| !s.trim().is_empty())
+    {
+        return Err(AppError::Invalid(format!(
+            "Command {} has no schedule",
+            command.name
+        )));
+    }
+    command.schedule_enabled = Some(enabled);
+    Ok(())
+}
+
+/// Returns the favorited commands in stored order.
+pub fn favorites(commands: &[Command]) -> Vec<Command> {
+    commands
+        .iter()
+        .filter(|c| c.favorite.unwrap_or(false))
+        .cloned()
+        .collect()
+}
+
+/// Fields [`missing_field`] can check.
+pub const HYGIENE_FIELDS: &[&str] = &["description", "notes", "group", "shortcut", "icon"];
+
+/// IDs of the commands that lack `field`, in stored order, for tidying up the store.
+///
+/// A blank value counts as missing. `field` is one of [`HYGIENE_FIELDS`].
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` - The IDs; empty if every command has the field
+/// * `Err(AppError)` - `Invalid` for any other field name
+pub fn missing_field(commands: &[Command], field: &str) -> Result<Vec<String>, AppError> {
+    let value: fn(&Command) -> Option<&String> = match field {
+        "description" => |c| c.description.as_ref(),
+        "notes" => |c| c.notes.as_ref(),
+        "group" => |c| c.group.as_ref(),
+        "shortcut" => |c| c.shortcut.as_ref(),
+        "icon" => |c| c.icon.as_ref(),
+        _ => {
+            return Err(AppError::Invalid(format!(
+                "Unknown field '{}': expected one of {}",
+                field,
+                HYGIENE_FIELDS.join(", ")
+            )))
+        }
+    };
+    Ok(commands
+        .iter()
+        .filter(|c| value(c).map_or(true, |v| v.trim().is_empty()))
+        .map(|c| c.id.clone())
+        .collect())
+}
+
+/// Scans the commands for problems a hand edit or import can introduce.
+///
+/// Reports duplicate IDs, commands with neither a script nor steps, shortcuts that
+/// don't parse, and shortcuts bound to more than one command (compared by
+/// [`crate::shortcuts::normalize_shortcut`], so `cmd+k` and `Ctrl+K` clash off
+/// macOS). Macro steps and `depends_on` entries naming an unknown command are reported
+/// as dangling references, and every command in a macro or dependency cycle as a
+/// reference cycle, so a hand-edited store doesn't only fail when it runs.
+///
+/// # Returns
+///
+/// * `Vec<StoreIssue>` - Every issue found, in stored order; empty for a healthy store
+pub fn verify(commands: &[Command]) -> Vec<StoreIssue> {
+    let mut issues = Vec::new();
+    let mut ids = std::collections::HashSet::new();
+    let mut shortcuts: std::collections::HashMap<String, &Command> =
+        std::collections::HashMap::new();
+
+    for command in commands {
+        let mut issue = |kind, message| {
+            issues.push(StoreIssue {
+                kind,
+                command_id: command.id.clone(),
+                message,
+            })
+        };
+
+        if !ids.insert(command.id.as_str()) {
+            issue(
+                StoreIssueKind::DuplicateId,
+                format!("'{}' reuses the ID {}", command.name, command.id),
+            );
+        }
+
+        let has_steps = command
+            .steps
+            .iter()
+            .flatten()
+            .any(|step| !step.trim().is_empty());
+        if command.script.trim().is_empty() && !has_steps && !command.is_macro() {
+            issue(
+                StoreIssueKind::EmptyScript,
+                format!("'{}' has an empty script", command.name),
+            );
+        }
+
+        for id in command.macro_steps.iter().flatten() {
+            if !commands.iter().any(|c| c.id == *id) {
+                let dangling = unknown_step(command, id);
+                issue(dangling.kind, dangling.message);
+            }
+        }
+        for dependency in command.depends_on.iter().flatten() {
+            if !commands.iter().any(|c| c.id == *dependency) {
+                let dangling = unknown_dependency(command, dependency);
+                issue(dangling.kind, dangling.message);
+            }
+        }
+        let cycles = [
+            macro_tree_issue(commands, command),
+            dependency_order(commands, command).err(),
+        ];
+        for cycle in cycles.into_iter().flatten().filter(|issue| {
+            issue.kind == StoreIssueKind::ReferenceCycle && issue.command_id == command.id
+        }) {
+            issue(cycle.kind, cycle.message);
+        }
+
+        let Some(shortcut) = command.shortcut.as_deref().filter(|s| !s.trim().is_empty()) else {
+            continue;
+        };
+        if let Err(e) = crate::shortcuts::parse_shortcut(shortcut) {
+            issue(
+                StoreIssueKind::InvalidShortcut,
+                format!("'{}': {}", command.name, e),
+            );
+            continue;
+        }
+        let key = crate::shortcuts::normalize_shortcut(shortcut);
+        match shortcuts.get(&key) {
+            Some(first) => issue(
+                StoreIssueKind::DuplicateShortcut,
+                format!(
+                    "'{}' uses shortcut {}, already bound to '{}'",
+                    command.name, shortcut, first.name
+                ),
+            ),
+            None => {
+                shortcuts.insert(key, command);
+            }
+        }
+    }
+    issues
+}
+
+/// Moves a trashed command back into the command list.
+///
+/// Its shortcut gets the same checks as an add: it may have been bound to another
+/// command, or become reserved (unless in `reserved_allowed`), while it sat in the trash.
+///
+/// # Returns
+///
+/// * `Ok(Command)` - The restored command
+/// * `Err(AppError)` - Not in the trash, a command with the same ID exists again, or
+///   `ShortcutInvalid` for a shortcut that is now taken or reserved
+pub fn restore(
+    commands: &mut Vec<Command>,
+    trash: &mut Vec<TrashedCommand>,
+    id: &str,
+    reserved_allowed: &[String],
+) -> Result<Command, AppError> {
+    let index = trash
+        .iter()
+        .position(|t| t.command.id == id)
+        .ok_or_else(|| AppError::NotFound("Command not found in trash".to_string()))?;
+
+    if commands.iter().any(|c| c.id == id) {
+        return Err(AppError::Invalid(format!(
+            "A command with ID {} already exists",
+            id
+        )));
+    }
+    crate::shortcuts::check_available(commands, &trash[index].command)?;
+    crate::shortcuts::check_not_reserved(&trash[index].command, reserved_allowed)?;
+
+    let restored = trash.remove(index).command;
+    commands.push(restored.clone());
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CommandVariable, DEFAULT_MAX_SCRIPT_LENGTH};
+
+    const NOW: &str = "2026-01-01T00:00:00+00:00";
+
+    fn command(id: &str, locked: Option<bool>) -> Command {
+        Command {
+            id: id.to_string(),
+            name: format!("Command {}", id),
+            script: "true".to_string(),
+            locked,
+            ..Default::default()
+        }
+    }
+
+    fn depending(id: &str, depends_on: &[&str]) -> Command {
+        Command {
+            depends_on: Some(depends_on.iter().map(|d| d.to_string()).collect()),
+            ..command(id, None)
+        }
+    }
+
+    #[test]
+    fn test_run_order_is_topological_and_deduped() {
+        // deploy needs build and test, which both need fetch
+        let commands = vec![
+            depending("deploy", &["build", "test"]),
+            depending("test", &["fetch"]),
+            depending("build", &["fetch"]),
+            command("fetch", None),
+            command("unrelated", None),
+        ];
+        let ids = |id| -> Vec<String> {
+            run_order(&commands, id)
+                .unwrap()
+                .iter()
+                .map(|c| c.id.clone())
+                .collect()
+        };
+
+        assert_eq!(ids("deploy"), vec!["fetch", "build", "test", "deploy"]);
+        assert_eq!(ids("test"), vec!["fetch", "test"]);
+        assert_eq!(ids("fetch"), vec!["fetch"]);
+        assert_eq!(
+            run_order(&commands, "nope").unwrap_err().code(),
+            "not_found"
+        );
+    }
+
+    #[test]
+    fn test_run_order_rejects_cycles_and_missing_dependencies() {
+        let commands = vec![
+            depending("a", &["b"]),
+            depending("b", &["c"]),
+            depending("c", &["a"]),
+            depending("self", &["self"]),
+            depending("orphan", &["gone"]),
+        ];
+
+        assert_eq!(
+            run_order(&commands, "a").unwrap_err().to_string(),
+            "Dependency cycle: a -> b -> c -> a"
+        );
+        assert_eq!(
+            run_order(&commands, "self").unwrap_err().to_string(),
+            "Dependency cycle: self -> self"
+        );
+        assert_eq!(
+            run_order(&commands, "orphan").unwrap_err().to_string(),
+            "'Command orphan' depends on unknown command 'gone'"
+        );
+    }
+
+    fn macro_of(id: &str, steps: &[&str]) -> Command {
+        Command {
+            macro_steps: Some(steps.iter().map(|s| s.to_string()).collect()),
+            ..command(id, None)
+        }
+    }
+
+    #[test]
+    fn test_macro_steps_rejects_cycles_and_unknown_steps() {
+        let commands = vec![
+            macro_of("release", &["build", "nested", "build"]),
+            macro_of("nested", &["test"]),
+            command("build", None),
+            command("test", None),
+            macro_of("a", &["b"]),
+            macro_of("b", &["a"]),
+            macro_of("self", &["self"]),
+            macro_of("orphan", &["gone"]),
+        ];
+        let find = |id: &str| commands.iter().find(|c| c.id == id).unwrap();
+
+        let ids: Vec<&str> = macro_steps(&commands, find("release"))
+            .unwrap()
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["build", "nested", "build"]);
+
+        assert_eq!(
+            macro_steps(&commands, find("a")).unwrap_err().to_string(),
+            "Macro cycle: a -> b -> a"
+        );
+        assert_eq!(
+            macro_steps(&commands, find("self"))
+                .unwrap_err()
+                .to_string(),
+            "Macro cycle: self -> self"
+        );
+        assert_eq!(
+            macro_steps(&commands, find("orphan"))
+                .unwrap_err()
+                .to_string(),
+            "Macro 'Command orphan' runs unknown command 'gone'"
+        );
+        assert_eq!(
+            validate(find("self"), 1024).unwrap_err().to_string(),
+            "A macro can't run itself"
+        );
+    }
+
+    #[test]
+    fn test_verify_reports_dependency_references() {
+        let commands = vec![
+            depending("a", &["b"]),
+            depending("b", &["a"]),
+            depending("self", &["self"]),
+            depending("orphan", &["gone"]),
+            // Depends on the cycle without being part of it
+            depending("outer", &["a"]),
+            // Only the command naming the missing dependency is reported
+            depending("downstream", &["orphan"]),
+        ];
+        let issues = verify(&commands);
+        let found: Vec<(StoreIssueKind, &str, &str)> = issues
+            .iter()
+            .map(|issue| {
+                (
+                    issue.kind,
+                    issue.command_id.as_str(),
+                    issue.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    StoreIssueKind::ReferenceCycle,
+                    "a",
+                    "Dependency cycle: a -> b -> a"
+                ),
+                (
+                    StoreIssueKind::ReferenceCycle,
+                    "b",
+                    "Dependency cycle: b -> a -> b"
+                ),
+                (
+                    StoreIssueKind::ReferenceCycle,
+                    "self",
+                    "Dependency cycle: self -> self"
+                ),
+                (
+                    StoreIssueKind::DanglingReference,
+                    "orphan",
+                    "'Command orphan' depends on unknown command 'gone'"
+                ),
+            ]
+        );
+
+        let chain = vec![depending("deploy", &["build"]), command("build", None)];
+        assert!(verify(&chain).is_empty());
+    }
+
+    #[test]
+    fn test_verify_reports_each_issue() {
+        let mut empty = command("2", None);
+        empty.script = "  ".to_string();
+        let mut steps_only = command("3", None);
+        steps_only.script = String::new();
+        steps_only.steps = Some(vec!["make".to_string()]);
+
+        let mut first = command("4", None);
+        first.shortcut = Some("Ctrl+Shift+K".to_string());
+        let mut clash = command("5", None);
+        clash.shortcut = Some("ctrl+shift+k".to_string());
+        let mut invalid = command("6", None);
+        invalid.shortcut = Some("Ctrl+Banana".to_string());
+
+        let commands = vec![
+            command("1", None),
+            command("1", None),
+            empty,
+            steps_only,
+            first,
+            clash,
+            invalid,
+        ];
+        let issues = verify(&commands);
+        let found: Vec<(StoreIssueKind, &str)> = issues
+            .iter()
+            .map(|issue| (issue.kind, issue.command_id.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (StoreIssueKind::DuplicateId, "1"),
+                (StoreIssueKind::EmptyScript, "2"),
+                (StoreIssueKind::DuplicateShortcut, "5"),
+                (StoreIssueKind::InvalidShortcut, "6"),
+            ]
+        );
+
+        assert!(verify(&[command("1", None), command("2", None)]).is_empty());
+    }
+
+    #[test]
+    fn test_verify_reports_macro_references() {
+        let commands = vec![
+            macro_of("release", &["build", "gone"]),
+            command("build", None),
+            macro_of("a", &["b"]),
+            macro_of("b", &["a"]),
+            // Runs into the cycle without being part of it
+            macro_of("outer", &["a"]),
+        ];
+        let issues = verify(&commands);
+        let found: Vec<(StoreIssueKind, &str, &str)> = issues
+            .iter()
+            .map(|issue| {
+                (
+                    issue.kind,
+                    issue.command_id.as_str(),
+                    issue.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    StoreIssueKind::DanglingReference,
+                    "release",
+                    "Macro 'Command release' runs unknown command 'gone'"
+                ),
+                (
+                    StoreIssueKind::ReferenceCycle,
+                    "a",
+                    "Macro cycle: a -> b -> a"
+                ),
+                (
+                    StoreIssueKind::ReferenceCycle,
+                    "b",
+                    "Macro cycle: b -> a -> b"
+                ),
+            ]
+        );
+
+        let nested = vec![
+            macro_of("release", &["nested"]),
+            macro_of("nested", &["build"]),
+        ];
+        assert_eq!(verify(&nested).len(), 1);
+        assert_eq!(verify(&nested)[0].command_id, "nested");
+    }
+
+    #[test]
+    fn test_removal_warns_about_macros_still_running_the_command() {
+        let mut commands = vec![
+            macro_of("release", &["build", "test"]),
+            command("build", None),
+            command("test", None),
+        ];
+        remove(&mut commands, "build").unwrap();
+        assert_eq!(
+            removal_warnings(&commands, "build"),
+            vec!["Macro 'Command release' still runs this command"]
+        );
+        assert!(removal_warnings(&commands, "release").is_empty());
+
+        let ops = vec![
+            BatchOp::Delete {
+                id: "test".to_string(),
+            },
+            BatchOp::Delete {
+                id: "missing".to_string(),
+            },
+        ];
+        let (results, removed) =
+            apply_batch(&mut commands, ops, DEFAULT_MAX_SCRIPT_LENGTH, &[], "now");
+        assert_eq!(removed.len(), 1);
+        assert_eq!(
+            results[0].warnings,
+            vec!["Macro 'Command release' still runs this command"]
+        );
+        assert!(results[1].ok && results[1].warnings.is_empty());
+    }
+
+    #[test]
+    fn test_validate_color() {
+        let mut valid = command("1", None);
+        valid.color = Some("#1e90FF".to_string());
+        valid.icon = Some("🐳".to_string());
+        assert!(validate(&valid, DEFAULT_MAX_SCRIPT_LENGTH).is_ok());
+
+        for bad in ["1E90FF", "#1E90F", "#1E90FFAA", "#GGGGGG", "red"] {
+            let mut invalid = command("2", None);
+            invalid.color = Some(bad.to_string());
+            let err = validate(&invalid, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+            assert!(
+                err.to_string().contains("Invalid color"),
+                "{} should be rejected",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_script_content() {
+        let mut cmd = command("1", None);
+        cmd.script = "x".repeat(16);
+        assert!(validate(&cmd, 16).is_ok());
+
+        cmd.script.push('x');
+        let err = validate(&cmd, 16).unwrap_err();
+        assert!(err.to_string().contains("17 bytes"), "{}", err);
+
+        cmd.script = "echo \0hi".to_string();
+        assert_eq!(
+            validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH),
+            Err(AppError::Invalid("Script contains null bytes".to_string()))
+        );
+
+        cmd.script = String::new();
+        cmd.steps = Some(vec!["make".to_string(), "make \0install".to_string()]);
+        assert_eq!(
+            validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH),
+            Err(AppError::Invalid("Step 2 contains null bytes".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_read_only_blocks_writes() {
+        let mut config = Config::default();
+        assert!(ensure_writable(&config).is_ok());
+        config.read_only = Some(false);
+        assert!(ensure_writable(&config).is_ok());
+
+        config.read_only = Some(true);
+        let err = ensure_writable(&config).unwrap_err();
+        assert_eq!(err, AppError::ReadOnly);
+        assert_eq!(err.code(), "read_only");
+        assert_eq!(err.to_string(), "App is in read-only mode");
+    }
+
+    #[test]
+    fn test_record_run_in_store_skips_write_when_read_only() {
+        let path = std::env::temp_dir().join("test_record_run_read_only.json");
+        let _ = std::fs::remove_file(&path);
+        let command = Command {
+            id: "1".to_string(),
+            name: "Test".to_string(),
+            script: "echo 1".to_string(),
+            ..Default::default()
+        };
+        crate::store::save_commands(&path, &[command]).unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        let mut config = Config {
+            read_only: Some(true),
+            ..Default::default()
+        };
+        let recorded =
+            record_run_in_store(&path, &config, "1", "2024-01-01T00:00:00Z", Some(0)).unwrap();
+        assert!(!recorded);
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+
+        config.read_only = None;
+        let recorded =
+            record_run_in_store(&path, &config, "1", "2024-01-01T00:00:00Z", Some(0)).unwrap();
+        assert!(recorded);
+        let commands = crate::store::get_commands(&path).unwrap();
+        assert_eq!(commands[0].run_count, Some(1));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_validate_kill_script() {
+        for risky in [
+            "pkill -9 -f sh",
+            "sudo killall node",
+            "lsof -ti :3000 | xargs kill; pkill -f 'py'",
+            "kill -9 -1",
+            "kill 0",
+        ] {
+            let warnings = validate_kill_script(risky);
+            assert_eq!(warnings.len(), 1, "{}: {:?}", risky, warnings);
+        }
+        assert!(validate_kill_script("pkill -9 -f sh")[0].contains("'sh'"));
+
+        for safe in [
+            "pkill -f 'node server.js'",
+            "pkill -f my-dev-server",
+            "kill -TERM 4242",
+            "docker compose down",
+            "",
+        ] {
+            assert!(validate_kill_script(safe).is_empty(), "{}", safe);
+        }
+    }
+
+    #[test]
+    fn test_validate_run_as() {
+        let mut cmd = command("1", None);
+        for user in ["postgres", "root", "CORP\\deploy", "svc-backup.1"] {
+            cmd.run_as = Some(user.to_string());
+            assert!(
+                validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).is_ok(),
+                "{}",
+                user
+            );
+        }
+        for user in ["-s", "bob smith", "eve;rm", "o'brien"] {
+            cmd.run_as = Some(user.to_string());
+            let err = validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+            assert!(err.to_string().contains("Invalid run_as user"), "{}", user);
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_throttles_until_the_interval_passes() {
+        let mut cmd = command("1", None);
+        let start = Instant::now();
+        let after = |millis| start + Duration::from_millis(millis);
+
+        // Off by default
+        assert!(check_rate_limit(&cmd, Some(start), start).is_ok());
+
+        cmd.min_interval_secs = Some(5);
+        assert!(check_rate_limit(&cmd, None, start).is_ok());
+        assert_eq!(
+            check_rate_limit(&cmd, Some(start), after(0)),
+            Err(AppError::RateLimited(5))
+        );
+        assert_eq!(
+            check_rate_limit(&cmd, Some(start), after(3_200)),
+            Err(AppError::RateLimited(2))
+        );
+        assert!(check_rate_limit(&cmd, Some(start), after(5_000)).is_ok());
+        assert!(check_rate_limit(&cmd, Some(start), after(60_000)).is_ok());
+        assert_eq!(
+            check_rate_limit(&cmd, Some(start), after(4_999))
+                .unwrap_err()
+                .to_string(),
+            "Rate limited; wait 1s"
+        );
+    }
+
+    #[test]
+    fn test_validate_variables() {
+        let variable = |name: &str| CommandVariable {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut cmd = command("1", None);
+        cmd.variables = Some(vec![variable("env"), variable("_image_tag2")]);
+        assert!(validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).is_ok());
+
+        for name in ["", "2fast", "my-var", "a b", "{{x}}"] {
+            cmd.variables = Some(vec![variable(name)]);
+            let err = validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+            assert!(
+                err.to_string().contains("Invalid variable name"),
+                "{}",
+                name
+            );
+        }
+
+        cmd.variables = Some(vec![variable("env"), variable("env")]);
+        let err = validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+        assert_eq!(err.to_string(), "Variable 'env' is declared twice");
+    }
+
+    #[test]
+    fn test_validate_env_names() {
+        let with_env = |name: &str| Command {
+            env: Some(std::collections::BTreeMap::from([(
+                name.to_string(),
+                "x".to_string(),
+            )])),
+            ..command("1", None)
+        };
+        for good in ["API_URL", "_private", "v2"] {
+            assert!(validate(&with_env(good), DEFAULT_MAX_SCRIPT_LENGTH).is_ok());
+        }
+        for bad in ["", "2FA", "MY-VAR", "X=1", "A;rm -rf ~", "a b"] {
+            let err = validate(&with_env(bad), DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("Invalid environment variable name"),
+                "{:?} should be rejected",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_nice() {
+        let mut cmd = command("1", None);
+        cmd.nice = Some(10);
+        assert!(validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).is_ok());
+
+        cmd.nice = Some(40);
+        let err = validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+        assert_eq!(err.code(), "invalid");
+    }
+
+    #[test]
+    fn test_validate_success_exit_codes() {
+        let mut cmd = command("1", None);
+        cmd.success_exit_codes = Some(vec![0, 1]);
+        assert!(validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).is_ok());
+
+        cmd.success_exit_codes = Some(Vec::new());
+        let err = validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+        assert_eq!(err.code(), "invalid");
+    }
+
+    #[test]
+    fn test_validate_output_filter() {
+        let mut cmd = command("1", None);
+        cmd.output_filter = Some("^(ERROR|WARN)".to_string());
+        assert!(validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).is_ok());
+
+        cmd.output_filter = Some("[unclosed".to_string());
+        let err = validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+        assert_eq!(err.code(), "invalid");
+        assert!(err.to_string().contains("Invalid output filter"), "{}", err);
+
+        cmd.output_filter = None;
+        cmd.failure_pattern = Some("[unclosed".to_string());
+        let err = validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid failure pattern"),
+            "{}",
+            err
+        );
+
+        cmd.failure_pattern = None;
+        cmd.progress_regex = Some(r"(\d+%".to_string());
+        let err = validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid progress regex"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_output_format() {
+        let mut cmd = command("1", None);
+        assert!(!cmd.output_is_json());
+
+        cmd.output_format = Some("JSON".to_string());
+        assert!(cmd.output_is_json());
+        assert!(validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).is_ok());
+
+        cmd.output_format = Some("text".to_string());
+        assert!(!cmd.output_is_json());
+        cmd.output_is_json = Some(true);
+        assert!(cmd.output_is_json());
+
+        cmd.output_format = Some("yaml".to_string());
+        assert_eq!(
+            validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH)
+                .unwrap_err()
+                .to_string(),
+            "Invalid output format 'yaml': expected json or text"
+        );
+    }
+
+    #[test]
+    fn test_schedule_validation_and_toggle() {
+        let mut cmd = command("1", Some(true));
+        cmd.schedule = Some("every 5 minutes".to_string());
+        assert!(validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).is_err());
+        cmd.schedule = Some("0 3 * * *".to_string());
+        assert!(validate(&cmd, DEFAULT_MAX_SCRIPT_LENGTH).is_ok());
+
+        let mut commands = vec![cmd, command("2", None)];
+        set_schedule_enabled(&mut commands, "1", false).expect("Locked commands can be paused");
+        assert_eq!(commands[0].schedule_enabled, Some(false));
+        assert!(set_schedule_enabled(&mut commands, "2", true).is_err());
+    }
+
+    #[test]
+    fn test_add_many_is_all_or_nothing() {
+        let mut commands = vec![command("1", None)];
+
+        let added = add_many(
+            &mut commands,
+            vec![command("2", None), command("3", None)],
+            DEFAULT_MAX_SCRIPT_LENGTH,
+            &[],
+        )
+        .expect("Unique batch should be added");
+        assert_eq!(added, 2);
+        assert_eq!(commands.len(), 3);
+
+        // Clashes with an existing ID
+        let err = add_many(
+            &mut commands,
+            vec![command("4", None), command("1", None)],
+            DEFAULT_MAX_SCRIPT_LENGTH,
+            &[],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("index 1"), "{}", err);
+        assert_eq!(commands.len(), 3);
+
+        // Duplicated within the batch itself
+        let err = add_many(
+            &mut commands,
+            vec![command("5", None), command("5", None)],
+            DEFAULT_MAX_SCRIPT_LENGTH,
+            &[],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("duplicate ID 5"), "{}", err);
+        assert_eq!(commands.len(), 3);
+    }
+
+    #[test]
+    fn test_shortcuts_are_stored_canonical() {
+        let bound = |id: &str, shortcut: &str| Command {
+            shortcut: Some(shortcut.to_string()),
+            ..command(id, None)
+        };
+        let mut commands = Vec::new();
+        add_many(
+            &mut commands,
+            vec![bound("1", "Ctrl+Shift+K"), bound("2", "")],
+            DEFAULT_MAX_SCRIPT_LENGTH,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(commands[0].shortcut.as_deref(), Some("shift+control+KeyK"));
+        let canonical = crate::shortcuts::normalize_shortcut("shift+ctrl+k");
+        assert_eq!(commands[0].shortcut.as_deref(), Some(canonical.as_str()));
+        assert_eq!(commands[1].shortcut.as_deref(), Some(""));
+
+        let ops = vec![
+            BatchOp::Add {
+                command: bound("3", "alt+F5"),
+            },
+            BatchOp::Update {
+                command: bound("2", "Alt+Shift+J"),
+            },
+        ];
+        let (results, _) = apply_batch(&mut commands, ops, DEFAULT_MAX_SCRIPT_LENGTH, &[], "now");
+        assert!(results.iter().all(|r| r.ok), "{:?}", results);
+        let stored = |id: &str| find(&commands, id).unwrap().shortcut.clone().unwrap();
+        assert_eq!(stored("3"), crate::shortcuts::normalize_shortcut("Alt+F5"));
+        assert_eq!(
+            stored("2"),
+            crate::shortcuts::normalize_shortcut("shift+alt+j")
+        );
+        // The canonical form maps to itself
+        assert_eq!(
+            crate::shortcuts::normalize_shortcut(&stored("2")),
+            stored("2")
+        );
+    }
+
+    #[test]
+    fn test_add_many_checks_shortcuts() {
+        let bound = |id: &str, shortcut: &str| Command {
+            shortcut: Some(shortcut.to_string()),
+            ..command(id, None)
+        };
+        let mut commands = vec![bound("1", "Ctrl+Alt+L")];
+
+        // Taken by an existing command
+        let err = add_many(
+            &mut commands,
+            vec![command("2", None), bound("3", "ctrl+alt+l")],
+            DEFAULT_MAX_SCRIPT_LENGTH,
+            &[],
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), "shortcut_invalid");
+        assert!(err.to_string().contains("index 1"), "{}", err);
+
+        // Taken by an earlier entry in the same batch
+        let err = add_many(
+            &mut commands,
+            vec![bound("2", "Ctrl+Alt+K"), bound("3", "Ctrl+Alt+K")],
+            DEFAULT_MAX_SCRIPT_LENGTH,
+            &[],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("index 1"), "{}", err);
+
+        // Reserved by the system unless overridden
+        let reserved = crate::shortcuts::reserved_shortcuts()[0];
+        let batch = vec![bound("2", reserved)];
+        let err =
+            add_many(&mut commands, batch.clone(), DEFAULT_MAX_SCRIPT_LENGTH, &[]).unwrap_err();
+        assert!(err.to_string().contains("reserved"), "{}", err);
+        assert_eq!(commands.len(), 1);
+
+        add_many(
+            &mut commands,
+            batch,
+            DEFAULT_MAX_SCRIPT_LENGTH,
+            &[reserved.to_string()],
+        )
+        .expect("Overridden shortcut should be allowed");
+        assert_eq!(commands.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_batch_reports_each_op() {
+        let mut commands = vec![command("1", None), command("locked", Some(true))];
+        let mut renamed = command("1", None);
+        renamed.name = "Renamed".to_string();
+        let mut second = command("3", None);
+        second.name = "Added later".to_string();
+
+        let ops = vec![
+            BatchOp::Add {
+                command: command("2", None),
+            },
+            BatchOp::Add {
+                command: command("1", None),
+            },
+            BatchOp::Update { command: renamed },
+            BatchOp::Delete {
+                id: "locked".to_string(),
+            },
+            BatchOp::Delete {
+                id: "2".to_string(),
+            },
+            BatchOp::Update { command: second },
+        ];
+        let (results, removed) =
+            apply_batch(&mut commands, ops, DEFAULT_MAX_SCRIPT_LENGTH, &[], "now");
+
+        let codes: Vec<Option<&str>> = results
+            .iter()
+            .map(|r| r.error.as_ref().map(AppError::code))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                None,
+                Some("invalid"),
+                None,
+                Some("locked"),
+                None,
+                Some("not_found")
+            ]
+        );
+        assert!(results[0].ok && !results[1].ok);
+        assert!(results.iter().all(|r| r.warnings.is_empty()));
+
+        // Later ops see earlier ones: "2" was added, then deleted into `removed`
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, "2");
+        assert_eq!(removed[0].created_at.as_deref(), Some("now"));
+        let ids: Vec<&str> = commands.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "locked"]);
+        assert_eq!(commands[0].name, "Renamed");
+    }
+
+    #[test]
+    fn test_apply_batch_checks_shortcuts() {
+        let reserved = crate::shortcuts::reserved_shortcuts()[0];
+        let bound = |id: &str, shortcut: &str| Command {
+            shortcut: Some(shortcut.to_string()),
+            ..command(id, None)
+        };
+        let mut commands = vec![bound("1", "Ctrl+Alt+L"), command("2", None)];
+
+        let ops = vec![
+            BatchOp::Add {
+                command: bound("3", "ctrl+alt+l"),
+            },
+            BatchOp::Add {
+                command: bound("4", reserved),
+            },
+            BatchOp::Update {
+                command: bound("2", "Ctrl+Alt+L"),
+            },
+            BatchOp::Update {
+                command: bound("2", reserved),
+            },
+        ];
+        let (results, _) = apply_batch(&mut commands, ops, DEFAULT_MAX_SCRIPT_LENGTH, &[], "now");
+        let codes: Vec<Option<&str>> = results
+            .iter()
+            .map(|r| r.error.as_ref().map(AppError::code))
+            .collect();
+        assert_eq!(codes, vec![Some("shortcut_invalid"); 4]);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[1].shortcut, None);
+
+        let ops = vec![BatchOp::Update {
+            command: bound("2", reserved),
+        }];
+        let allowed = [reserved.to_string()];
+        let (results, _) = apply_batch(
+            &mut commands,
+            ops,
+            DEFAULT_MAX_SCRIPT_LENGTH,
+            &allowed,
+            "now",
+        );
+        assert!(results[0].ok, "{:?}", results[0].error);
+    }
+
+    #[test]
+    fn test_apply_batch_returns_kill_script_warnings() {
+        let risky = |id: &str| Command {
+            kill_script: Some("pkill -f sh".to_string()),
+            ..command(id, None)
+        };
+        let mut commands = vec![command("1", None)];
+        let ops = vec![
+            BatchOp::Add {
+                command: risky("2"),
+            },
+            BatchOp::Update {
+                command: risky("1"),
+            },
+            BatchOp::Add {
+                command: risky("1"),
+            },
+        ];
+        let (results, _) = apply_batch(&mut commands, ops, DEFAULT_MAX_SCRIPT_LENGTH, &[], "now");
+
+        assert!(results[0].ok && results[1].ok);
+        assert_eq!(results[0].warnings, validate_kill_script("pkill -f sh"));
+        assert!(!results[1].warnings.is_empty());
+        // A skipped op saved nothing to warn about
+        assert!(!results[2].ok && results[2].warnings.is_empty());
+    }
+
+    #[test]
+    fn test_batch_op_is_tagged() {
+        let op: BatchOp = serde_json::from_str(r#"{"op":"delete","id":"42"}"#).unwrap();
+        assert_eq!(
+            op,
+            BatchOp::Delete {
+                id: "42".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_find_command() {
+        let commands = vec![command("1", None), command("2", Some(true))];
+
+        assert_eq!(find(&commands, "2").map(|c| c.id.as_str()), Ok("2"));
+        assert_eq!(
+            find(&commands, "missing"),
+            Err(AppError::NotFound("Command not found".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_update_rejects_locked_command() {
+        let mut commands = vec![command("1", Some(true)), command("2", None)];
+
+        let mut edited = command("1", Some(false));
+        edited.name = "Renamed".to_string();
+        assert_eq!(
+            update(&mut commands, edited, NOW),
+            Err(AppError::Locked("Command is locked".to_string()))
+        );
+        assert_eq!(commands[0].name, "Command 1");
+
+        let mut edited = command("2", None);
+        edited.name = "Renamed".to_string();
+        update(&mut commands, edited, NOW).expect("Unlocked command should update");
+        assert_eq!(commands[1].name, "Renamed");
+
+        assert_eq!(
+            update(&mut commands, command("3", None), NOW),
+            Err(AppError::NotFound("Command not found".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_update_bumps_updated_at_only() {
+        let mut original = command("1", None);
+        stamp_created(&mut original, NOW);
+        let mut commands = vec![original];
+
+        // The frontend may send the command back without timestamps
+        let mut edited = command("1", None);
+        edited.name = "Renamed".to_string();
+        let later = "2026-02-01T00:00:00+00:00";
+        update(&mut commands, edited, later).unwrap();
+
+        assert_eq!(commands[0].created_at.as_deref(), Some(NOW));
+        assert_eq!(commands[0].updated_at.as_deref(), Some(later));
+    }
+
+    #[test]
+    fn test_update_keeps_server_owned_fields() {
+        let mut commands = vec![command("1", None)];
+        record_run(&mut commands, "1", NOW, Some(0)).unwrap();
+        record_run(&mut commands, "1", NOW, Some(3)).unwrap();
+        assert_eq!(commands[0].run_count, Some(2));
+        assert_eq!(commands[0].last_run_at.as_deref(), Some(NOW));
+        assert_eq!(commands[0].last_exit_code, Some(3));
+
+        // Only the name changed; the frontend didn't send run_count back
+        let mut edited = command("1", None);
+        edited.name = "Renamed".to_string();
+        update(&mut commands, edited, NOW).unwrap();
+
+        assert_eq!(commands[0].name, "Renamed");
+        assert_eq!(commands[0].run_count, Some(2));
+        assert_eq!(commands[0].last_exit_code, Some(3));
+        assert!(record_run(&mut commands, "missing", NOW, None).is_err());
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let mut commands = vec![command("1", None), command("2", None), command("3", None)];
+        let ids = vec!["3".to_string(), "1".to_string(), "missing".to_string()];
+
+        let removed = remove_many(&mut commands, &ids).expect("Bulk delete should succeed");
+        let removed_ids: Vec<&str> = removed.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(removed_ids, vec!["1", "3"]);
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].id, "2");
+
+        // A locked match aborts the whole batch
+        let mut commands = vec![command("1", None), command("2", Some(true))];
+        let ids = vec!["1".to_string(), "2".to_string()];
+        assert!(remove_many(&mut commands, &ids).is_err());
+        assert_eq!(commands.len(), 2);
+    }
+
+    #[test]
+    fn test_move_to() {
+        let mut commands = vec![command("a", None), command("b", None), command("c", None)];
+        let ids = |commands: &[Command]| -> Vec<String> {
+            commands.iter().map(|c| c.id.clone()).collect()
+        };
+
+        assert_eq!(move_to(&mut commands, "c", 0), Ok(0));
+        assert_eq!(ids(&commands), vec!["c", "a", "b"]);
+
+        // Out-of-range targets clamp to the end
+        assert_eq!(move_to(&mut commands, "c", 99), Ok(2));
+        assert_eq!(ids(&commands), vec!["a", "b", "c"]);
+
+        assert_eq!(move_to(&mut commands, "a", 1), Ok(1));
+        assert_eq!(ids(&commands), vec!["b", "a", "c"]);
+
+        assert!(move_to(&mut commands, "missing", 0).is_err());
+    }
+
+    #[test]
+    fn test_toggle_and_filter_favorites() {
+        let mut commands = vec![
+            command("1", None),
+            command("2", None),
+            command("3", Some(true)),
+        ];
+        assert!(favorites(&commands).is_empty());
+
+        // Locked commands can still be pinned
+        assert_eq!(toggle_favorite(&mut commands, "3"), Ok(true));
+        assert_eq!(toggle_favorite(&mut commands, "1"), Ok(true));
+        let ids: Vec<String> = favorites(&commands).into_iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec!["1", "3"], "Favorites keep stored order");
+
+        assert_eq!(toggle_favorite(&mut commands, "1"), Ok(false));
+        assert_eq!(favorites(&commands).len(), 1);
+
+        assert!(toggle_favorite(&mut commands, "missing").is_err());
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let mut commands = vec![command("1", None), command("2", None), command("3", None)];
+        commands[0].description = Some("Builds the app".to_string());
+        commands[1].description = Some("  ".to_string());
+        commands[2].notes = Some("Run after a deploy".to_string());
+
+        assert_eq!(
+            missing_field(&commands, "description").unwrap(),
+            vec!["2", "3"]
+        );
+        assert_eq!(missing_field(&commands, "notes").unwrap(), vec!["1", "2"]);
+        assert_eq!(missing_field(&commands, "group").unwrap().len(), 3);
+
+        let err = missing_field(&commands, "script").unwrap_err();
+        assert_eq!(err.code(), "invalid");
+        assert!(err
+            .to_string()
+            .contains("expected one of description, notes"));
+    }
+
+    #[test]
+    fn test_delete_and_restore_round_trip() {
+        let mut original = command("1", None);
+        original.shortcut = Some("Ctrl+Shift+1".to_string());
+        original.description = Some("Keep me".to_string());
+        let mut commands = vec![original.clone(), command("2", None)];
+        let mut trash = Vec::new();
+
+        let removed = remove(&mut commands, "1").unwrap().unwrap();
+        trash.push(TrashedCommand {
+            command: removed,
+            deleted_at: "2026-01-01T00:00:00+00:00".to_string(),
+        });
+        assert_eq!(commands.len(), 1);
+
+        let restored =
+            restore(&mut commands, &mut trash, "1", &[]).expect("Restore should succeed");
+        assert_eq!(restored, original);
+        assert!(trash.is_empty());
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[1], original);
+
+        assert!(restore(&mut commands, &mut trash, "1", &[]).is_err());
+    }
+
+    #[test]
+    fn test_restore_checks_the_shortcut() {
+        let reserved = crate::shortcuts::reserved_shortcuts()[0];
+        let trashed = |id: &str, shortcut: &str| TrashedCommand {
+            command: Command {
+                shortcut: Some(shortcut.to_string()),
+                ..command(id, None)
+            },
+            deleted_at: "2026-01-01T00:00:00+00:00".to_string(),
+        };
+        let mut commands = vec![Command {
+            shortcut: Some("Ctrl+Alt+L".to_string()),
+            ..command("1", None)
+        }];
+        let mut trash = vec![trashed("2", "ctrl+alt+l"), trashed("3", reserved)];
+
+        for id in ["2", "3"] {
+            let err = restore(&mut commands, &mut trash, id, &[]).unwrap_err();
+            assert_eq!(err.code(), "shortcut_invalid", "{}", id);
+        }
+        // Nothing moved out of the trash
+        assert_eq!(commands.len(), 1);
+        assert_eq!(trash.len(), 2);
+
+        let allowed = [reserved.to_string()];
+        assert!(restore(&mut commands, &mut trash, "3", &allowed).is_ok());
+        assert_eq!(trash.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_rejects_locked_command() {
+        let mut commands = vec![command("1", Some(true)), command("2", Some(false))];
+
+        assert_eq!(
+            remove(&mut commands, "1"),
+            Err(AppError::Locked("Command is locked".to_string()))
+        );
+        assert_eq!(commands.len(), 2);
+
+        let removed = remove(&mut commands, "2").expect("Unlocked command should be removed");
+        assert_eq!(removed.map(|c| c.id), Some("2".to_string()));
+        assert_eq!(remove(&mut commands, "missing"), Ok(None));
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].id, "1");
+    }
+}
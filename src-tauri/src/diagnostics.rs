@@ -0,0 +1,203 @@
+use crate::error::AppError;
+use crate::executor;
+use crate::models::{Command, Config, DiagnosticCheck, Diagnostics, ExecutionResult};
+use std::fs::OpenOptions;
+use std::path::Path;
+
+/// ID of the throwaway command `run_diagnostics` runs; its runs aren't recorded.
+pub const ECHO_COMMAND_ID: &str = "climgr-diagnostics";
+
+/// What the diagnostic command prints.
+pub const ECHO_OUTPUT: &str = "climgr-ok";
+
+/// A check named `name` that passed with the `Ok` message or failed with the `Err` one.
+pub fn check(name: &str, result: Result<String, String>) -> DiagnosticCheck {
+    let (passed, message) = match result {
+        Ok(message) => (true, message),
+        Err(message) => (false, message),
+    };
+    DiagnosticCheck {
+        name: name.to_string(),
+        passed,
+        message,
+    }
+}
+
+/// The report for `checks`, which passes only if every check did.
+pub fn report(checks: Vec<DiagnosticCheck>) -> Diagnostics {
+    Diagnostics {
+        passed: checks.iter().all(|c| c.passed),
+        checks,
+    }
+}
+
+/// Whether the shell scripts run with can be started.
+pub fn shell_available() -> Result<String, String> {
+    let invocation = executor::shell_invocation("exit 0", false);
+    match invocation.to_command().output() {
+        Ok(output) if output.status.success() => {
+            Ok(format!("Shell '{}' is available", executor::SHELL))
+        }
+        Ok(output) => Err(format!(
+            "Shell '{}' exited with {:?}",
+            executor::SHELL,
+            output.status.code()
+        )),
+        Err(e) => Err(executor::spawn_error(&invocation, &e)),
+    }
+}
+
+/// Whether the store at `path` can be saved: the file itself if it exists, otherwise
+/// its directory, which is created if needed.
+///
+/// Nothing in the store is changed; a probe file is written and removed instead.
+pub fn store_writable(path: &Path) -> Result<String, String> {
+    if path.exists() {
+        return OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map(|_| format!("{:?} is writable", path))
+            .map_err(|e| format!("{:?} is not writable: {}", path, e));
+    }
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let probe = path.with_extension("write-test");
+    std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b""))
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map(|_| format!("{:?} doesn't exist yet; {:?} is writable", path, dir))
+        .map_err(|e| format!("{:?} is not writable: {}", dir, e))
+}
+
+/// Describes the outcome of reading the config at `path`.
+pub fn config_readable(path: &Path, config: &Result<Config, AppError>) -> Result<String, String> {
+    match config {
+        Ok(_) if path.exists() => Ok(format!("Read {:?}", path)),
+        Ok(_) => Ok(format!("{:?} doesn't exist yet; defaults are used", path)),
+        Err(e) => Err(format!("{:?} couldn't be read: {}", path, e)),
+    }
+}
+
+/// Fails while safe mode blocks execution.
+pub fn safe_mode(config: &Config) -> Result<String, String> {
+    if config.safe_mode {
+        Err("Safe mode is on, so commands are blocked".to_string())
+    } else {
+        Ok("Safe mode is off".to_string())
+    }
+}
+
+/// The command run to check that scripts execute end to end.
+pub fn echo_command() -> Command {
+    Command {
+        id: ECHO_COMMAND_ID.to_string(),
+        name: "Diagnostics".to_string(),
+        script: format!("echo {}", ECHO_OUTPUT),
+        ..Default::default()
+    }
+}
+
+/// Checks the result of running [`echo_command`].
+pub fn echo_result(result: Result<ExecutionResult, AppError>) -> Result<String, String> {
+    let result = result.map_err(|e| e.to_string())?;
+    if !result.succeeded() {
+        return Err(format!(
+            "The test command exited with {:?}: {}",
+            result.exit_code,
+            result.output.trim()
+        ));
+    }
+    if result.output.trim() != ECHO_OUTPUT {
+        return Err(format!(
+            "Expected '{}' from the test command, got '{}'",
+            ECHO_OUTPUT,
+            result.output.trim()
+        ));
+    }
+    Ok(format!("Ran `{}`", echo_command().script))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_passes_only_if_every_check_passes() {
+        let passing = report(vec![
+            check("shell", Ok("Shell 'sh' is available".to_string())),
+            check("safe_mode", safe_mode(&Config::default())),
+        ]);
+        assert!(passing.passed);
+        assert_eq!(passing.checks[1].message, "Safe mode is off");
+
+        let safe = Config {
+            safe_mode: true,
+            ..Default::default()
+        };
+        let failing = report(vec![
+            check("shell", Ok("Shell 'sh' is available".to_string())),
+            check("safe_mode", safe_mode(&safe)),
+            check("echo", echo_result(Err(AppError::SafeModeBlocked))),
+        ]);
+        assert!(!failing.passed);
+        let failed: Vec<&str> = failing
+            .checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(failed, vec!["safe_mode", "echo"]);
+        assert!(failing.checks[2].message.contains("safe mode"));
+    }
+
+    #[test]
+    fn test_echo_result() {
+        let ran = |output: &str, exit_code: i32| {
+            Ok(ExecutionResult {
+                exit_code: Some(exit_code),
+                ..ExecutionResult::text(output)
+            })
+        };
+        assert_eq!(
+            echo_result(ran("climgr-ok\n", 0)),
+            Ok("Ran `echo climgr-ok`".to_string())
+        );
+        assert_eq!(
+            echo_result(ran("sh: echo: not found\n", 127)),
+            Err("The test command exited with Some(127): sh: echo: not found".to_string())
+        );
+        assert!(echo_result(ran("something else", 0))
+            .unwrap_err()
+            .starts_with("Expected 'climgr-ok'"));
+    }
+
+    #[test]
+    fn test_config_and_store_checks() {
+        let temp_dir = std::env::temp_dir().join("climgr_test_diagnostics");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let config_path = temp_dir.join("config.json");
+
+        let missing = config_readable(&config_path, &Ok(Config::default()));
+        assert!(missing.unwrap().contains("defaults are used"));
+        let broken = config_readable(&config_path, &Err(AppError::Parse("expected value".into())));
+        assert!(broken
+            .unwrap_err()
+            .ends_with("couldn't be read: expected value"));
+
+        let store = temp_dir.join("nested").join("commands.json");
+        assert!(store_writable(&store).is_ok());
+        assert!(!store.exists(), "The check should not create the store");
+        assert!(!store.with_extension("write-test").exists());
+
+        std::fs::write(&store, "[]").unwrap();
+        assert!(store_writable(&store).unwrap().ends_with("is writable"));
+        assert_eq!(std::fs::read_to_string(&store).unwrap(), "[]");
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_available() {
+        assert_eq!(shell_available(), Ok("Shell 'sh' is available".to_string()));
+    }
+}
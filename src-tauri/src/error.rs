@@ -0,0 +1,171 @@
+use serde::ser::SerializeStruct;
+use serde::Serialize;
+use std::fmt;
+
+/// Error returned by the store and every Tauri command.
+///
+/// It serializes as `{ code, message }`: `code` is a stable identifier the frontend can
+/// switch on, `message` is the human-readable text shown to users.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    /// A command (or other entry) with the given ID doesn't exist
+    NotFound(String),
+    /// The command is locked against changes
+    Locked(String),
+    /// Execution is disabled by safe mode
+    SafeModeBlocked,
+    /// Changes are disabled by the read-only presentation mode
+    ReadOnly,
+    /// The command ran too recently; holds the seconds left to wait
+    RateLimited(u64),
+    /// Input was rejected by validation
+    Invalid(String),
+    /// A shortcut couldn't be parsed or registered
+    ShortcutInvalid(String),
+    /// Reading or writing a file failed
+    Io(String),
+    /// Stored data or imported text couldn't be parsed or serialized
+    Parse(String),
+    /// A script couldn't be started or waited for
+    Execution(String),
+    /// Anything else
+    Other(String),
+}
+
+impl AppError {
+    /// The stable identifier sent to the frontend as `code`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Locked(_) => "locked",
+            AppError::SafeModeBlocked => "safe_mode_blocked",
+            AppError::ReadOnly => "read_only",
+            AppError::RateLimited(_) => "rate_limited",
+            AppError::Invalid(_) => "invalid",
+            AppError::ShortcutInvalid(_) => "shortcut_invalid",
+            AppError::Io(_) => "io",
+            AppError::Parse(_) => "parse",
+            AppError::Execution(_) => "execution",
+            AppError::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::SafeModeBlocked => f.write_str(
+                "Command execution disabled in safe mode. Disable safe mode in settings to execute commands.",
+            ),
+            AppError::ReadOnly => f.write_str("App is in read-only mode"),
+            AppError::RateLimited(secs) => write!(f, "Rate limited; wait {}s", secs),
+            AppError::NotFound(message)
+            | AppError::Locked(message)
+            | AppError::Invalid(message)
+            | AppError::ShortcutInvalid(message)
+            | AppError::Io(message)
+            | AppError::Parse(message)
+            | AppError::Execution(message)
+            | AppError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other(message)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            AppError::Io(e.to_string())
+        } else {
+            AppError::Parse(e.to_string())
+        }
+    }
+}
+
+impl From<toml::de::Error> for AppError {
+    fn from(e: toml::de::Error) -> Self {
+        AppError::Parse(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for AppError {
+    fn from(e: toml::ser::Error) -> Self {
+        AppError::Parse(e.to_string())
+    }
+}
+
+impl From<serde_yaml::Error> for AppError {
+    fn from(e: serde_yaml::Error) -> Self {
+        AppError::Parse(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variants_serialize_with_stable_codes() {
+        let cases = [
+            (AppError::NotFound("Command not found".into()), "not_found"),
+            (AppError::Locked("Command is locked".into()), "locked"),
+            (AppError::SafeModeBlocked, "safe_mode_blocked"),
+            (AppError::ReadOnly, "read_only"),
+            (AppError::RateLimited(3), "rate_limited"),
+            (
+                AppError::Invalid("Script contains null bytes".into()),
+                "invalid",
+            ),
+            (
+                AppError::ShortcutInvalid("Invalid shortcut".into()),
+                "shortcut_invalid",
+            ),
+            (AppError::Io("permission denied".into()), "io"),
+            (AppError::Parse("expected value".into()), "parse"),
+            (
+                AppError::Execution("Shell 'sh' not found on PATH".into()),
+                "execution",
+            ),
+            (AppError::Other("something else".into()), "other"),
+        ];
+
+        for (error, code) in cases {
+            let json = serde_json::to_value(&error).unwrap();
+            assert_eq!(json["code"], code);
+            assert_eq!(json["message"], error.to_string());
+        }
+    }
+
+    #[test]
+    fn test_conversions_pick_the_variant() {
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert_eq!(AppError::from(missing), AppError::Io("gone".to_string()));
+
+        let bad_json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert_eq!(AppError::from(bad_json).code(), "parse");
+
+        assert!(AppError::SafeModeBlocked.to_string().contains("safe mode"));
+    }
+}
@@ -0,0 +1,1459 @@
+use crate::models::{CaptureMode, Command, ExecutionResult, OutputDecoding, OutputEncoding};
+use base64::Engine;
+use regex::bytes::Regex;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Output, Stdio};
+use std::sync::mpsc::SyncSender;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// How long a `status_script` may run before the command is reported unhealthy.
+pub const STATUS_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a gracefully signalled process gets to exit before it is force-killed.
+pub const KILL_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+const KILL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The program and arguments used to run a command's script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl Invocation {
+    /// Builds a `std::process::Command` for this invocation.
+    pub fn to_command(&self) -> std::process::Command {
+        let mut cmd = std::process::Command::new(&self.program);
+        cmd.args(&self.args);
+        cmd
+    }
+}
+
+/// Shell every script is run with.
+pub const SHELL: &str = "sh";
+
+/// Builds the invocation for a script.
+///
+/// Scripts run through `sh -c`. With `requires_sudo`, the shell is wrapped in
+/// `sudo -S -k -p ""`: `-S` reads the password from stdin, `-k` ignores cached
+/// credentials so the password line is always consumed by sudo (never by the script),
+/// and the empty prompt keeps stderr clean.
+pub fn shell_invocation(script: &str, requires_sudo: bool) -> Invocation {
+    let mut args = Vec::new();
+    let program = if requires_sudo {
+        args.extend(["-S", "-k", "-p", "", SHELL].map(String::from));
+        "sudo"
+    } else {
+        SHELL
+    };
+    args.push("-c".to_string());
+    args.push(script.to_string());
+
+    Invocation {
+        program: program.to_string(),
+        args,
+    }
+}
+
+/// Extra shell options that make `sh` read the user's startup files before the script.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShellFlags {
+    /// `-l`: a login shell, which reads the profile (`~/.profile`)
+    pub login: bool,
+    /// `-i`: an interactive shell, which reads the file named by `$ENV`
+    pub interactive: bool,
+}
+
+impl ShellFlags {
+    /// The options, in the order they are passed to the shell.
+    pub fn args(self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.login {
+            args.push("-l".to_string());
+        }
+        if self.interactive {
+            args.push("-i".to_string());
+        }
+        args
+    }
+}
+
+/// Passes `flags` to the shell of `invocation`, just before its `-c`.
+///
+/// The Windows `runas` form hands the shell its whole command line as one argument, so
+/// it's left as is.
+pub fn with_shell_flags(mut invocation: Invocation, flags: ShellFlags) -> Invocation {
+    if let Some(index) = invocation.args.iter().position(|arg| arg == "-c") {
+        invocation.args.splice(index..index, flags.args());
+    }
+    invocation
+}
+
+/// Builds the invocation for a script run as another user.
+///
+/// Without `run_as` this is [`shell_invocation`]. Otherwise, on unix, the shell runs
+/// under `sudo -u <user>` (plain `sudo` for `root`). Captured stdio has no terminal to
+/// prompt on, so without `requires_sudo` sudo runs with `-n` and fails at once if it
+/// needs a password; with it, the password is read from stdin as in
+/// [`shell_invocation`]. On Windows the shell runs under `runas /user:<user>`, which
+/// prompts in its own console.
+pub fn user_invocation(script: &str, requires_sudo: bool, run_as: Option<&str>) -> Invocation {
+    let Some(user) = run_as else {
+        return shell_invocation(script, requires_sudo);
+    };
+    if cfg!(target_os = "windows") {
+        return Invocation {
+            program: "runas".to_string(),
+            args: vec![
+                format!("/user:{}", user),
+                format!("{} -c {}", SHELL, windows_quote(script)),
+            ],
+        };
+    }
+
+    let mut args: Vec<String> = if requires_sudo {
+        ["-S", "-k", "-p", ""].map(String::from).to_vec()
+    } else {
+        vec!["-n".to_string()]
+    };
+    if user != "root" {
+        args.extend(["-u".to_string(), user.to_string()]);
+    }
+    args.extend([SHELL.to_string(), "-c".to_string(), script.to_string()]);
+    Invocation {
+        program: "sudo".to_string(),
+        args,
+    }
+}
+
+/// The shell line that runs `script` as `user`, for a terminal window.
+///
+/// Unlike [`user_invocation`], sudo may prompt here since the terminal is a real TTY.
+pub fn run_as_command_line(script: &str, user: &str) -> String {
+    if cfg!(target_os = "windows") {
+        let command = format!("{} -c {}", SHELL, windows_quote(script));
+        return format!("runas /user:{} {}", user, windows_quote(&command));
+    }
+    let quoted = format!("'{}'", script.replace('\'', r"'\''"));
+    if user == "root" {
+        format!("sudo {} -c {}", SHELL, quoted)
+    } else {
+        format!("sudo -u {} {} -c {}", user, SHELL, quoted)
+    }
+}
+
+fn windows_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\\\""))
+}
+
+/// Builds the invocation that opens a new terminal window running `script`.
+///
+/// * macOS: Terminal.app via `osascript` (`do script`)
+/// * Windows: a new console via `cmd /C start`, kept open with `/K`
+/// * Elsewhere: the Debian-style `x-terminal-emulator` alternative
+pub fn terminal_invocation(script: &str) -> Invocation {
+    let (program, args) = if cfg!(target_os = "macos") {
+        let apple_script = format!(
+            "tell application \"Terminal\"\nactivate\ndo script {}\nend tell",
+            apple_script_string(script)
+        );
+        ("osascript", vec!["-e".to_string(), apple_script])
+    } else if cfg!(target_os = "windows") {
+        let args = ["/C", "start", "", "cmd", "/K", script];
+        ("cmd", args.map(String::from).to_vec())
+    } else {
+        let args = ["-e", "sh", "-c", script];
+        ("x-terminal-emulator", args.map(String::from).to_vec())
+    };
+
+    Invocation {
+        program: program.to_string(),
+        args,
+    }
+}
+
+/// Quotes a value as an AppleScript string literal.
+fn apple_script_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Builds the invocation that shows `path` in the file manager of `os` (a value of
+/// `std::env::consts::OS`).
+///
+/// * macOS: `open -R`, which opens the folder with the file selected
+/// * Windows: `explorer /select,`, likewise
+/// * Elsewhere: `xdg-open` on the containing directory, since there's no portable way
+///   to select a file
+pub fn reveal_invocation(os: &str, path: &Path) -> Invocation {
+    let (program, args) = match os {
+        "macos" => ("open", vec!["-R".to_string(), path.display().to_string()]),
+        "windows" => ("explorer", vec![format!("/select,{}", path.display())]),
+        _ => {
+            let dir = path.parent().unwrap_or(path);
+            ("xdg-open", vec![dir.display().to_string()])
+        }
+    };
+    Invocation {
+        program: program.to_string(),
+        args,
+    }
+}
+
+/// Pipes the streams `mode` captures and discards the others.
+pub fn apply_capture(cmd: &mut std::process::Command, mode: CaptureMode) {
+    cmd.stdout(if mode.stdout() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
+    cmd.stderr(if mode.stderr() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
+}
+
+/// Joins the captured streams of a finished process: stdout first, then stderr.
+///
+/// Streams `mode` doesn't capture are left out even if they were piped.
+pub fn collect_output(output: &Output, mode: CaptureMode) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if mode.stdout() {
+        bytes.extend_from_slice(&output.stdout);
+    }
+    if mode.stderr() {
+        bytes.extend_from_slice(&output.stderr);
+    }
+    bytes
+}
+
+/// Compiles a command's `output_filter`.
+pub fn output_filter(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid output filter '{}': {}", pattern, e))
+}
+
+/// Removes ANSI escape sequences, such as color codes, from `bytes`.
+///
+/// Covers CSI sequences (`ESC [ ... m` colors, cursor movement and erasing), OSC
+/// sequences (`ESC ] ... BEL`, e.g. window titles and hyperlinks), character set
+/// selection (`ESC ( B`) and the other two-byte escapes. Works on raw bytes, like [`filter_lines`].
+pub fn strip_ansi(bytes: &[u8]) -> Vec<u8> {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| {
+        Regex::new(
+            r"\x1b(?:\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(?:\x07|\x1b\\)|[()][0-9A-Za-z]|[@-Z\\-_])",
+        )
+        .expect("ANSI pattern is valid")
+    });
+    ansi.replace_all(bytes, &b""[..]).into_owned()
+}
+
+/// Compiles a command's `failure_pattern`.
+pub fn failure_pattern(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid failure pattern '{}': {}", pattern, e))
+}
+
+/// Whether `output` matches `command`'s `failure_pattern`, marking the run as failed.
+///
+/// A pattern that doesn't compile (only possible through hand edits, since saving
+/// validates it) is logged and never matches.
+pub fn output_failed(command: &Command, output: &[u8]) -> bool {
+    let Some(pattern) = command.failure_pattern.as_deref() else {
+        return false;
+    };
+    match failure_pattern(pattern) {
+        Ok(regex) => regex.is_match(output),
+        Err(e) => {
+            log::warn!("Command {}: {}", command.id, e);
+            false
+        }
+    }
+}
+
+/// Keeps only the lines of `bytes` that match `filter`, each with its line ending.
+///
+/// The line ending isn't part of what's matched, so `$` anchors at the end of the text.
+/// Works on raw bytes, so output that isn't valid UTF-8 can still be filtered.
+pub fn filter_lines(bytes: &[u8], filter: &Regex) -> Vec<u8> {
+    bytes
+        .split_inclusive(|&b| b == b'\n')
+        .filter(|line| {
+            let text = line.strip_suffix(b"\n").unwrap_or(line);
+            filter.is_match(text.strip_suffix(b"\r").unwrap_or(text))
+        })
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// Converts captured bytes into an [`ExecutionResult`], decoded as `decoding` says.
+///
+/// Only `raw-base64`, and `utf8` for bytes that aren't valid UTF-8, return base64,
+/// so binary data survives and the UI can decide how to show it.
+pub fn encode_output(bytes: Vec<u8>, decoding: OutputDecoding) -> ExecutionResult {
+    let base64 = |bytes: Vec<u8>| ExecutionResult {
+        output: base64::engine::general_purpose::STANDARD.encode(bytes),
+        encoding: OutputEncoding::Base64,
+        ..Default::default()
+    };
+    match decoding {
+        OutputDecoding::Lossy => ExecutionResult::text(String::from_utf8_lossy(&bytes)),
+        OutputDecoding::Utf8 => match String::from_utf8(bytes) {
+            Ok(text) => ExecutionResult::text(text),
+            Err(e) => base64(e.into_bytes()),
+        },
+        OutputDecoding::Latin1 => {
+            ExecutionResult::text(bytes.into_iter().map(char::from).collect::<String>())
+        }
+        OutputDecoding::RawBase64 => base64(bytes),
+    }
+}
+
+/// Appended to output cut short by [`truncate_output`].
+pub const TRUNCATION_MARKER: &str = "\n... (truncated)";
+
+/// Cuts text output down to its first `max_chars` characters, plus
+/// [`TRUNCATION_MARKER`].
+///
+/// Returns the full output if anything was cut. Base64 output is left whole, since a
+/// cut would leave it undecodable.
+pub fn truncate_output(result: &mut ExecutionResult, max_chars: usize) -> Option<String> {
+    if result.encoding != OutputEncoding::Utf8 {
+        return None;
+    }
+    let (end, _) = result.output.char_indices().nth(max_chars)?;
+    let shown = format!("{}{}", &result.output[..end], TRUNCATION_MARKER);
+    Some(std::mem::replace(&mut result.output, shown))
+}
+
+/// Parses the stdout of a command with `output_is_json` or an `output_format` of `json`.
+///
+/// Returns the value, or the parse error for the caller to report alongside the raw
+/// output.
+pub fn parse_json_output(stdout: &[u8]) -> Result<serde_json::Value, String> {
+    serde_json::from_slice(stdout).map_err(|e| format!("Output is not valid JSON: {}", e))
+}
+
+/// The text a `copy_output` command puts on the clipboard.
+///
+/// Trailing line breaks are dropped, so pasting a generated token doesn't submit a
+/// form or break a line. Output that isn't valid UTF-8, or is blank, isn't copied.
+pub fn clipboard_text(output: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(output)
+        .ok()?
+        .trim_end_matches(['\n', '\r']);
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// A reaped process with its output and resource usage.
+#[derive(Debug)]
+pub struct Completed {
+    pub output: Output,
+    /// Time from `start` until the process exited
+    pub duration: Duration,
+    /// Peak resident set size of the process and its reaped children (unix only)
+    pub peak_memory_bytes: Option<u64>,
+}
+
+/// Reads `stream` to the end on a new thread, sending each line to `lines` as it
+/// arrives.
+///
+/// Sending blocks while the channel is full. If the receiver goes away the remaining
+/// output is still collected, just no longer sent.
+fn drain(
+    stream: Option<impl std::io::Read + Send + 'static>,
+    lines: Option<SyncSender<String>>,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        use std::io::BufRead;
+
+        let mut bytes = Vec::new();
+        let Some(stream) = stream else {
+            return bytes;
+        };
+        let mut reader = std::io::BufReader::new(stream);
+        let mut lines = lines;
+        loop {
+            let line_start = bytes.len();
+            match reader.read_until(b'\n', &mut bytes) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            if let Some(sender) = &lines {
+                let line = &bytes[line_start..];
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                if sender
+                    .send(String::from_utf8_lossy(line).to_string())
+                    .is_err()
+                {
+                    lines = None;
+                }
+            }
+        }
+        bytes
+    })
+}
+
+/// Like [`Child::wait_with_output`], but also reports duration and peak memory.
+///
+/// The piped streams are drained on separate threads so a chatty script can't block on
+/// a full pipe; with `lines`, each line is also sent there as it is read. On unix the
+/// child is reaped with `wait4` so its own `rusage` can be read. Elsewhere only the
+/// duration is measured.
+pub fn wait_for_completion(
+    mut child: Child,
+    start: Instant,
+    lines: Option<SyncSender<String>>,
+) -> std::io::Result<Completed> {
+    drop(child.stdin.take());
+    let stdout = drain(child.stdout.take(), lines.clone());
+    let stderr = drain(child.stderr.take(), lines);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        let pid = child.id() as libc::pid_t;
+        let mut status = 0;
+        // SAFETY: rusage is plain data, fully written by wait4 on success
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: both pointers are valid for the duration of the call
+            let result = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+            if result == pid {
+                break;
+            }
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+        let duration = start.elapsed();
+
+        // ru_maxrss is in kilobytes on Linux and bytes on macOS
+        let max_rss = usage.ru_maxrss.max(0) as u64;
+        let peak_memory_bytes = if cfg!(target_os = "macos") {
+            max_rss
+        } else {
+            max_rss * 1024
+        };
+
+        Ok(Completed {
+            output: Output {
+                status: std::process::ExitStatus::from_raw(status),
+                stdout: stdout.join().unwrap_or_default(),
+                stderr: stderr.join().unwrap_or_default(),
+            },
+            duration,
+            peak_memory_bytes: Some(peak_memory_bytes),
+        })
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = child.wait()?;
+        let duration = start.elapsed();
+        Ok(Completed {
+            output: Output {
+                status,
+                stdout: stdout.join().unwrap_or_default(),
+                stderr: stderr.join().unwrap_or_default(),
+            },
+            duration,
+            peak_memory_bytes: None,
+        })
+    }
+}
+
+/// Turns a spawn failure into a message that names the missing program.
+///
+/// A bare "No such file or directory" from a minimal container says nothing about
+/// which binary is missing, so `NotFound` is reported as `Shell '<shell>' not found on
+/// PATH` (or the same for `sudo` when it is the launcher).
+pub fn spawn_error(invocation: &Invocation, e: &std::io::Error) -> String {
+    if e.kind() != std::io::ErrorKind::NotFound {
+        return format!(
+            "Failed to spawn command via '{}': {}",
+            invocation.program, e
+        );
+    }
+    if invocation.program == "sudo" {
+        "'sudo' not found on PATH".to_string()
+    } else {
+        format!("Shell '{}' not found on PATH", invocation.program)
+    }
+}
+
+/// Writes a secret followed by a newline to the child's stdin, then closes it.
+///
+/// The child must have been spawned with a piped stdin.
+pub fn feed_secret(child: &mut Child, secret: &str) -> Result<(), String> {
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| "Child stdin is not piped".to_string())?;
+    stdin
+        .write_all(secret.as_bytes())
+        .and_then(|_| stdin.write_all(b"\n"))
+        .map_err(|e| format!("Failed to pass password to sudo: {}", e))
+}
+
+/// A signal `kill_command` can send to a running command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Hang up; many daemons reload their configuration on it
+    Hup,
+    /// Interrupt, as if Ctrl+C was pressed
+    Int,
+    /// Quit, usually with a core dump
+    Quit,
+    /// Polite termination the process can handle
+    Term,
+    /// Immediate termination that can't be caught
+    Kill,
+}
+
+impl Signal {
+    /// Parses a signal name such as `TERM` or `SIGINT`, ignoring case.
+    pub fn parse(name: &str) -> Result<Signal, String> {
+        let upper = name.trim().to_ascii_uppercase();
+        match upper.strip_prefix("SIG").unwrap_or(&upper) {
+            "HUP" => Ok(Signal::Hup),
+            "INT" => Ok(Signal::Int),
+            "QUIT" => Ok(Signal::Quit),
+            "TERM" => Ok(Signal::Term),
+            "KILL" => Ok(Signal::Kill),
+            _ => Err(format!(
+                "Unknown signal '{}': expected HUP, INT, QUIT, TERM or KILL",
+                name
+            )),
+        }
+    }
+
+    /// The platform's signal number.
+    #[cfg(unix)]
+    pub fn number(self) -> i32 {
+        match self {
+            Signal::Hup => libc::SIGHUP,
+            Signal::Int => libc::SIGINT,
+            Signal::Quit => libc::SIGQUIT,
+            Signal::Term => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+        }
+    }
+
+    /// Whether the process gets no chance to clean up; on Windows this selects
+    /// `taskkill /F` over a graceful close request.
+    pub fn is_forced(self) -> bool {
+        self == Signal::Kill
+    }
+}
+
+/// Puts the child in a new process group led by itself.
+///
+/// Scripts run through `sh -c`, so the tracked PID is the shell; anything it starts
+/// (a dev server, a pipeline) would survive a kill aimed at the shell alone. With the
+/// child leading its own group (`setpgid(0, 0)` in the child before exec, which is
+/// what `process_group(0)` does), the PID doubles as the group ID and
+/// [`signal_process`] can stop the whole tree. No-op on non-unix platforms, where
+/// `taskkill /T` covers the tree instead.
+pub fn isolate_process_group(cmd: &mut std::process::Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// Starts the child in a new session so it outlives climgr.
+///
+/// `setsid` in the child before exec detaches it from climgr's process group and
+/// controlling terminal, so neither quitting the app nor closing the terminal it was
+/// launched from (SIGHUP) reaches it. No-op on non-unix platforms, where a child
+/// already keeps running after its parent exits.
+pub fn detach_session(cmd: &mut std::process::Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setsid is async-signal-safe and the closure allocates nothing
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// The niceness values `setpriority` accepts, from highest to lowest priority.
+pub const NICE_RANGE: std::ops::RangeInclusive<i32> = -20..=19;
+
+/// Checks a command's `nice` value, rejecting one outside [`NICE_RANGE`].
+pub fn check_nice(nice: i32) -> Result<i32, String> {
+    if NICE_RANGE.contains(&nice) {
+        Ok(nice)
+    } else {
+        Err(format!(
+            "Invalid nice value {}: expected {} to {}",
+            nice,
+            NICE_RANGE.start(),
+            NICE_RANGE.end()
+        ))
+    }
+}
+
+/// Pulls `nice` into [`NICE_RANGE`], for stores edited by hand past validation.
+pub fn clamp_nice(nice: i32) -> i32 {
+    nice.clamp(*NICE_RANGE.start(), *NICE_RANGE.end())
+}
+
+/// The Windows priority class closest to a niceness.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn priority_class(nice: i32) -> u32 {
+    const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    const NORMAL_PRIORITY_CLASS: u32 = 0x0000_0020;
+    const ABOVE_NORMAL_PRIORITY_CLASS: u32 = 0x0000_8000;
+    const HIGH_PRIORITY_CLASS: u32 = 0x0000_0080;
+    match clamp_nice(nice) {
+        -20..=-11 => HIGH_PRIORITY_CLASS,
+        -10..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+        0 => NORMAL_PRIORITY_CLASS,
+        1..=10 => BELOW_NORMAL_PRIORITY_CLASS,
+        _ => IDLE_PRIORITY_CLASS,
+    }
+}
+
+/// Starts the child at the given niceness, clamped to [`NICE_RANGE`].
+///
+/// On unix the child calls `setpriority` before exec; lowering priority (a higher
+/// value than climgr's own) needs no privileges, but raising it usually does, and
+/// then the spawn fails with a permission error. On Windows the niceness maps to a
+/// priority class; elsewhere it is ignored.
+pub fn set_priority(cmd: &mut std::process::Command, nice: i32) {
+    let nice = clamp_nice(nice);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setpriority is async-signal-safe and the closure allocates nothing
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(priority_class(nice));
+    }
+    #[cfg(not(any(unix, windows)))]
+    let _ = (cmd, nice);
+}
+
+/// Sends a signal to a process and everything it started.
+///
+/// On unix `pid` must lead its own process group (see [`isolate_process_group`]); the
+/// signal goes to the negated PID, i.e. the whole group. On Windows, which has no
+/// signals, `taskkill /T` stops the process tree: forced for [`Signal::Kill`], as a
+/// close request for the others.
+pub fn signal_process(pid: u32, signal: Signal) -> Result<(), String> {
+    #[cfg(unix)]
+    let output = {
+        std::process::Command::new("kill")
+            .arg(format!("-{}", signal.number()))
+            .arg("--")
+            .arg(format!("-{}", pid))
+            .output()
+            .map_err(|e| format!("Failed to execute kill command: {}", e))?
+    };
+
+    #[cfg(windows)]
+    let output = {
+        let mut taskkill = std::process::Command::new("taskkill");
+        if signal.is_forced() {
+            taskkill.arg("/F");
+        }
+        taskkill
+            .arg("/T")
+            .arg("/PID")
+            .arg(pid.to_string())
+            .output()
+            .map_err(|e| format!("Failed to execute taskkill command: {}", e))?
+    };
+
+    if !output.status.success() {
+        return Err(format!(
+            "Kill command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+/// Whether a process with `pid` exists, via `kill -0` (`tasklist` on Windows).
+///
+/// A process that exited but hasn't been reaped yet still counts as alive on unix. If
+/// liveness can't be determined, the process is assumed alive.
+pub fn is_process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+        // SAFETY: signal 0 only checks that the process exists and may be signalled
+        if unsafe { libc::kill(pid, 0) } == 0 {
+            return true;
+        }
+        // EPERM: it exists, but belongs to someone else
+        std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+
+    #[cfg(windows)]
+    {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+            .output()
+            .map_or(true, |output| {
+                String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid))
+            })
+    }
+}
+
+/// Polls `still_running` until it returns `false` or `grace` elapses.
+///
+/// # Returns
+///
+/// * `true` - The process exited within the grace period
+/// * `false` - It was still running when the grace period ran out
+pub fn wait_for_exit(grace: Duration, mut still_running: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + grace;
+    while still_running() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(KILL_POLL_INTERVAL);
+    }
+    true
+}
+
+/// How a PID-based kill without an explicit signal goes.
+///
+/// `signal` is sent first; unless it is already `KILL`, a process still running after
+/// `grace` is force-killed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KillPolicy {
+    pub signal: Signal,
+    pub grace: Duration,
+}
+
+impl KillPolicy {
+    /// Force-kill at once.
+    pub const FORCE: KillPolicy = KillPolicy {
+        signal: Signal::Kill,
+        grace: KILL_GRACE_PERIOD,
+    };
+
+    /// Whether the process gets a chance to exit before being force-killed.
+    pub fn is_graceful(self) -> bool {
+        !self.signal.is_forced()
+    }
+}
+
+/// Asks a process to stop with `policy.signal`, then force-kills it if it hasn't exited
+/// after `policy.grace`.
+///
+/// If the graceful signal can't be delivered (e.g. Windows refuses a non-forced
+/// `taskkill` for console processes), escalation happens immediately.
+///
+/// # Returns
+///
+/// * `Ok(true)` - The process had to be force-killed
+/// * `Ok(false)` - It exited on its own after the graceful signal
+/// * `Err(String)` - The forced kill failed
+pub fn graceful_kill(
+    pid: u32,
+    policy: KillPolicy,
+    still_running: impl FnMut() -> bool,
+) -> Result<bool, String> {
+    match signal_process(pid, policy.signal) {
+        Ok(()) if wait_for_exit(policy.grace, still_running) => return Ok(false),
+        Ok(()) => log::info!("Process {} ignored the graceful signal; escalating", pid),
+        Err(e) => log::warn!("Graceful kill of {} failed ({}); escalating", pid, e),
+    }
+    signal_process(pid, Signal::Kill).map(|_| true)
+}
+
+/// Stops several processes the way [`signal_process`] and [`graceful_kill`] stop one.
+///
+/// With a forced `policy` each process is killed at once. With a graceful one, all of
+/// them get its signal first and share a single grace period, so stopping many takes
+/// no longer than stopping one, before the survivors are force-killed.
+/// `still_running` reports whether a PID has yet to exit. Returns an error for each
+/// process the forced kill failed on.
+pub fn stop_all(
+    pids: &[u32],
+    policy: KillPolicy,
+    mut still_running: impl FnMut(u32) -> bool,
+) -> Vec<String> {
+    let graceful = policy.is_graceful();
+    if graceful {
+        for &pid in pids {
+            if let Err(e) = signal_process(pid, policy.signal) {
+                log::warn!("Graceful kill of {} failed ({}); escalating", pid, e);
+            }
+        }
+        wait_for_exit(policy.grace, || pids.iter().any(|&pid| still_running(pid)));
+    }
+    pids.iter()
+        .filter(|&&pid| !graceful || still_running(pid))
+        .filter_map(|&pid| {
+            signal_process(pid, Signal::Kill)
+                .err()
+                .map(|e| format!("Failed to kill process {}: {}", pid, e))
+        })
+        .collect()
+}
+
+/// Runs a health check script and reports whether it passed.
+///
+/// Exit 0 is healthy; a non-zero exit, death by signal, or still running after
+/// `timeout` (the check is then killed) are unhealthy. Output is discarded.
+///
+/// # Returns
+///
+/// * `Ok(bool)` - Whether the check passed
+/// * `Err(String)` - The script couldn't be started or waited for
+pub fn run_status_check(script: &str, timeout: Duration) -> Result<bool, String> {
+    let invocation = shell_invocation(script, false);
+    let mut process = invocation.to_command();
+    process
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    isolate_process_group(&mut process);
+    let mut child = process.spawn().map_err(|e| spawn_error(&invocation, &e))?;
+
+    let mut status = None;
+    let mut wait_error = None;
+    let finished = wait_for_exit(timeout, || match child.try_wait() {
+        Ok(Some(exited)) => {
+            status = Some(exited);
+            false
+        }
+        Ok(None) => true,
+        Err(e) => {
+            wait_error = Some(e);
+            false
+        }
+    });
+    if let Some(e) = wait_error {
+        return Err(format!("Failed to wait for status script: {}", e));
+    }
+    if !finished {
+        log::warn!("Status script timed out after {:?}", timeout);
+        let _ = signal_process(child.id(), Signal::Kill);
+        let _ = child.wait();
+        return Ok(false);
+    }
+    Ok(status.is_some_and(|status| status.success()))
+}
+
+/// The hook to run after a command finished: `on_success_script` when it exited 0,
+/// `on_failure_script` otherwise. Blank hooks count as unset.
+pub fn post_run_hook(command: &Command, success: bool) -> Option<(&'static str, &str)> {
+    let (name, script) = if success {
+        ("on_success", command.on_success_script.as_deref())
+    } else {
+        ("on_failure", command.on_failure_script.as_deref())
+    };
+    script.filter(|s| !s.trim().is_empty()).map(|s| (name, s))
+}
+
+/// Runs a post-execution hook and returns a section to append to the command's output.
+///
+/// Hooks run as plain shell scripts without sudo, capture settings or hooks of their
+/// own, so a hook can never trigger another hook. A hook that fails to start or exits
+/// non-zero is reported in the section rather than failing the command.
+pub fn run_hook(name: &str, script: &str) -> Vec<u8> {
+    let mut section = format!("\n--- {} hook ---\n", name).into_bytes();
+    match shell_invocation(script, false).to_command().output() {
+        Ok(output) => {
+            section.extend(collect_output(&output, CaptureMode::Both));
+            if !output.status.success() {
+                section.extend(format!("{} hook exited with {}\n", name, output.status).bytes());
+            }
+        }
+        Err(e) => section.extend(format!("{} hook failed to start: {}\n", name, e).bytes()),
+    }
+    section
+}
+
+/// Whether sudo's stderr indicates the supplied password was rejected.
+pub fn is_sudo_auth_failure(stderr: &str) -> bool {
+    stderr.contains("incorrect password")
+        || stderr.contains("Sorry, try again")
+        || stderr.contains("no password was provided")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_invocation() {
+        let inv = shell_invocation("echo hi", false);
+        assert_eq!(inv.program, "sh");
+        assert_eq!(inv.args, vec!["-c", "echo hi"]);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_user_invocation() {
+        assert_eq!(
+            user_invocation("ls", false, None),
+            shell_invocation("ls", false)
+        );
+
+        let inv = user_invocation("whoami", false, Some("postgres"));
+        assert_eq!(inv.program, "sudo");
+        assert_eq!(inv.args, vec!["-n", "-u", "postgres", "sh", "-c", "whoami"]);
+
+        let inv = user_invocation("apt update", true, Some("root"));
+        assert_eq!(
+            inv.args,
+            vec!["-S", "-k", "-p", "", "sh", "-c", "apt update"]
+        );
+
+        assert_eq!(
+            run_as_command_line("echo 'hi'", "deploy"),
+            r"sudo -u deploy sh -c 'echo '\''hi'\'''"
+        );
+        assert_eq!(run_as_command_line("id", "root"), "sudo sh -c 'id'");
+    }
+
+    #[test]
+    fn test_with_shell_flags() {
+        let flags = |login, interactive| ShellFlags { login, interactive };
+
+        let plain = with_shell_flags(shell_invocation("make", false), ShellFlags::default());
+        assert_eq!(plain, shell_invocation("make", false));
+
+        let login = with_shell_flags(shell_invocation("make", false), flags(true, false));
+        assert_eq!(login.program, "sh");
+        assert_eq!(login.args, vec!["-l", "-c", "make"]);
+
+        let interactive = with_shell_flags(shell_invocation("make", false), flags(false, true));
+        assert_eq!(interactive.args, vec!["-i", "-c", "make"]);
+
+        let both = with_shell_flags(shell_invocation("make", true), flags(true, true));
+        assert_eq!(both.program, "sudo");
+        assert_eq!(
+            both.args,
+            vec!["-S", "-k", "-p", "", "sh", "-l", "-i", "-c", "make"]
+        );
+
+        // A script that is itself "-c" isn't mistaken for the shell option
+        let odd = with_shell_flags(shell_invocation("-c", false), flags(true, false));
+        assert_eq!(odd.args, vec!["-l", "-c", "-c"]);
+    }
+
+    #[test]
+    fn test_sudo_invocation_reads_password_from_stdin() {
+        let inv = shell_invocation("apt update", true);
+        assert_eq!(inv.program, "sudo");
+        assert!(inv.args.contains(&"-S".to_string()));
+        assert!(inv.args.ends_with(&[
+            "sh".to_string(),
+            "-c".to_string(),
+            "apt update".to_string()
+        ]));
+    }
+
+    #[test]
+    fn test_missing_shell_is_reported_by_name() {
+        let invocation = Invocation {
+            program: "climgr-no-such-shell".to_string(),
+            args: vec!["-c".to_string(), "true".to_string()],
+        };
+        let e = invocation.to_command().spawn().unwrap_err();
+        assert_eq!(
+            spawn_error(&invocation, &e),
+            "Shell 'climgr-no-such-shell' not found on PATH"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_feed_secret_writes_to_stdin() {
+        // Stand-in for sudo: read one line from stdin and echo it back
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("read secret; echo \"got:$secret\"")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .expect("Failed to spawn sh");
+
+        feed_secret(&mut child, "hunter2").expect("Failed to feed secret");
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "got:hunter2"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_modes() {
+        let cases = [
+            (CaptureMode::Both, "out\nerr\n"),
+            (CaptureMode::StdoutOnly, "out\n"),
+            (CaptureMode::StderrOnly, "err\n"),
+            (CaptureMode::None, ""),
+        ];
+        for (mode, expected) in cases {
+            let mut cmd = shell_invocation("echo out; echo err >&2", false).to_command();
+            apply_capture(&mut cmd, mode);
+            let output = cmd.output().expect("Failed to run sh");
+            let text = String::from_utf8(collect_output(&output, mode)).unwrap();
+            assert_eq!(text, expected, "{:?}", mode);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_for_completion_reports_duration_and_memory() {
+        let start = Instant::now();
+        let mut cmd = shell_invocation("sleep 0.2; echo done; exit 4", false).to_command();
+        apply_capture(&mut cmd, CaptureMode::Both);
+        let child = cmd.spawn().expect("Failed to run sh");
+
+        let completed = wait_for_completion(child, start, None).expect("Failed to wait");
+        assert_eq!(completed.output.status.code(), Some(4));
+        assert_eq!(completed.output.stdout, b"done\n");
+        assert!(
+            completed.duration >= Duration::from_millis(200)
+                && completed.duration < Duration::from_secs(5),
+            "{:?}",
+            completed.duration
+        );
+        assert!(completed.peak_memory_bytes.is_some_and(|bytes| bytes > 0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_for_completion_streams_lines() {
+        let mut cmd = shell_invocation("printf 'one\\r\\ntwo\\nthree'", false).to_command();
+        apply_capture(&mut cmd, CaptureMode::Both);
+        let child = cmd.spawn().expect("Failed to run sh");
+
+        let (sender, receiver) = std::sync::mpsc::sync_channel(16);
+        let completed =
+            wait_for_completion(child, Instant::now(), Some(sender)).expect("Failed to wait");
+
+        // Every line is sent without its ending; the output itself is untouched
+        let lines: Vec<String> = receiver.iter().collect();
+        assert_eq!(lines, vec!["one", "two", "three"]);
+        assert_eq!(completed.output.stdout, b"one\r\ntwo\nthree");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_status_check_maps_exit_codes_to_health() {
+        let timeout = Duration::from_secs(5);
+        assert_eq!(run_status_check("exit 0", timeout), Ok(true));
+        assert_eq!(run_status_check("exit 1", timeout), Ok(false));
+        assert_eq!(run_status_check("exit 7", timeout), Ok(false));
+        assert_eq!(run_status_check("kill -9 $$", timeout), Ok(false));
+
+        // A hung check counts as unhealthy instead of blocking
+        let start = Instant::now();
+        assert_eq!(
+            run_status_check("sleep 30", Duration::from_millis(200)),
+            Ok(false)
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_post_run_hooks_follow_exit_status() {
+        let command = Command {
+            on_success_script: Some("echo succeeded".to_string()),
+            on_failure_script: Some("echo failed".to_string()),
+            ..Default::default()
+        };
+
+        for (script, expected) in [("exit 0", "succeeded"), ("exit 3", "failed")] {
+            let status = shell_invocation(script, false)
+                .to_command()
+                .status()
+                .expect("Failed to run sh");
+            let (name, hook) = post_run_hook(&command, status.success()).unwrap();
+            let section = String::from_utf8(run_hook(name, hook)).unwrap();
+            assert_eq!(section, format!("\n--- {} hook ---\n{}\n", name, expected));
+        }
+
+        let no_failure_hook = Command {
+            on_success_script: Some("echo succeeded".to_string()),
+            on_failure_script: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(post_run_hook(&no_failure_hook, false), None);
+    }
+
+    #[test]
+    fn test_apple_script_string_escapes_quotes() {
+        assert_eq!(
+            apple_script_string(r#"echo "hi" \ there"#),
+            r#""echo \"hi\" \\ there""#
+        );
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_terminal_invocation() {
+        let inv = terminal_invocation("top");
+        assert_eq!(inv.program, "x-terminal-emulator");
+        assert_eq!(inv.args, vec!["-e", "sh", "-c", "top"]);
+    }
+
+    #[test]
+    fn test_reveal_invocation() {
+        let path = Path::new("/data/climgr/commands.json");
+
+        let mac = reveal_invocation("macos", path);
+        assert_eq!(mac.program, "open");
+        assert_eq!(mac.args, vec!["-R", "/data/climgr/commands.json"]);
+
+        let windows = reveal_invocation("windows", path);
+        assert_eq!(windows.program, "explorer");
+        assert_eq!(windows.args, vec!["/select,/data/climgr/commands.json"]);
+
+        let linux = reveal_invocation("linux", path);
+        assert_eq!(linux.program, "xdg-open");
+        assert_eq!(linux.args, vec!["/data/climgr"]);
+    }
+
+    #[test]
+    fn test_encode_output() {
+        let invalid = vec![b'f', 0xff, b'o', 0xe9];
+
+        let lossy = encode_output(invalid.clone(), OutputDecoding::Lossy);
+        assert_eq!(lossy, ExecutionResult::text("f\u{FFFD}o\u{FFFD}"));
+
+        let strict = encode_output(invalid.clone(), OutputDecoding::Utf8);
+        assert_eq!(strict.encoding, OutputEncoding::Base64);
+        assert_eq!(strict.output, "Zv9v6Q==");
+
+        let latin1 = encode_output(invalid.clone(), OutputDecoding::Latin1);
+        assert_eq!(latin1, ExecutionResult::text("f\u{FF}o\u{E9}"));
+
+        let raw = encode_output(invalid, OutputDecoding::RawBase64);
+        assert_eq!(raw.encoding, OutputEncoding::Base64);
+        assert_eq!(raw.output, "Zv9v6Q==");
+
+        // Valid UTF-8 stays text in every mode but raw-base64
+        for decoding in [OutputDecoding::Utf8, OutputDecoding::Lossy] {
+            assert_eq!(
+                encode_output("plain é".as_bytes().to_vec(), decoding),
+                ExecutionResult::text("plain é")
+            );
+        }
+        assert_eq!(
+            encode_output(b"plain".to_vec(), OutputDecoding::RawBase64).output,
+            "cGxhaW4="
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let cases: [(&[u8], &[u8]); 6] = [
+            (b"\x1b[0;32mok\x1b[0m", b"ok"),
+            (
+                b"\x1b[1m\x1b[38;5;196mbold red\x1b[39;49m done",
+                b"bold red done",
+            ),
+            (b"50%\x1b[2K\x1b[1G100%\n", b"50%100%\n"),
+            (
+                b"\x1b]0;title\x07\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\",
+                b"link",
+            ),
+            (b"\x1b(Bplain \xff bytes", b"plain \xff bytes"),
+            (b"no escapes [0;32m here", b"no escapes [0;32m here"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(strip_ansi(input), expected.to_vec());
+        }
+    }
+
+    #[test]
+    fn test_output_failed_matches_the_failure_pattern() {
+        let mut command = Command::default();
+        assert!(!output_failed(&command, b"ERROR: disk full"));
+
+        command.failure_pattern = Some("(?m)^ERROR".to_string());
+        assert!(output_failed(&command, b"starting\nERROR: disk full\n"));
+        assert!(!output_failed(&command, b"no ERROR here\n"));
+
+        command.failure_pattern = Some("(unclosed".to_string());
+        assert!(!output_failed(&command, b"(unclosed"));
+        assert!(failure_pattern("(unclosed")
+            .unwrap_err()
+            .starts_with("Invalid failure pattern '(unclosed'"));
+    }
+
+    #[test]
+    fn test_filter_lines() {
+        let filter = output_filter("^(ERROR|WARN)").unwrap();
+        let output = b"INFO start\nWARN disk 90%\r\nINFO ok\nERROR failed";
+        assert_eq!(
+            filter_lines(output, &filter),
+            b"WARN disk 90%\r\nERROR failed".to_vec()
+        );
+
+        // `$` anchors before the line ending; invalid UTF-8 lines can still match
+        let filter = output_filter("done$").unwrap();
+        assert_eq!(
+            filter_lines(b"\xff done\nnot done yet\n", &filter),
+            b"\xff done\n".to_vec()
+        );
+
+        let err = output_filter("(unclosed").unwrap_err();
+        assert!(
+            err.starts_with("Invalid output filter '(unclosed'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_wait_for_exit() {
+        let mut polls = 0;
+        assert!(wait_for_exit(Duration::from_secs(5), || {
+            polls += 1;
+            polls < 3
+        }));
+        assert_eq!(polls, 3);
+
+        let start = Instant::now();
+        assert!(!wait_for_exit(Duration::from_millis(120), || true));
+        assert!(start.elapsed() >= Duration::from_millis(120));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_graceful_kill_escalates_only_when_needed() {
+        let spawn = |script: &str| {
+            let mut cmd = shell_invocation(script, false).to_command();
+            isolate_process_group(&mut cmd);
+            cmd.spawn().expect("Failed to spawn sh")
+        };
+        let term = KillPolicy {
+            signal: Signal::Term,
+            grace: Duration::from_millis(500),
+        };
+
+        // Exits on SIGTERM, so no escalation
+        let mut child = spawn("exec sleep 30");
+        let pid = child.id();
+        let escalated = graceful_kill(pid, term, || child.try_wait().unwrap().is_none());
+        assert_eq!(escalated, Ok(false));
+
+        // Ignores SIGTERM (the ignored disposition survives exec), so SIGKILL follows
+        let mut child = spawn("trap '' TERM; exec sleep 30");
+        let pid = child.id();
+        // Give the shell time to install the trap before signalling
+        std::thread::sleep(Duration::from_millis(200));
+        let escalated = graceful_kill(pid, term, || child.try_wait().unwrap().is_none());
+        assert_eq!(escalated, Ok(true));
+        child.wait().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_all_shares_one_grace_period() {
+        let mut children: Vec<Child> = ["exec sleep 30", "trap '' TERM; exec sleep 30"]
+            .iter()
+            .map(|script| {
+                let mut cmd = shell_invocation(script, false).to_command();
+                isolate_process_group(&mut cmd);
+                cmd.spawn().expect("Failed to spawn sh")
+            })
+            .collect();
+        let pids: Vec<u32> = children.iter().map(Child::id).collect();
+        // Give the second shell time to install its trap
+        std::thread::sleep(Duration::from_millis(200));
+
+        let start = Instant::now();
+        let term = KillPolicy {
+            signal: Signal::Term,
+            grace: Duration::from_millis(500),
+        };
+        let errors = stop_all(&pids, term, |pid| {
+            children
+                .iter_mut()
+                .find(|child| child.id() == pid)
+                .is_some_and(|child| child.try_wait().unwrap().is_none())
+        });
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        for mut child in children {
+            child.wait().unwrap();
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_signal_process_stops_the_whole_group() {
+        use std::io::{BufRead, BufReader};
+
+        // The shell starts a grandchild and reports its PID
+        let mut cmd = shell_invocation("sleep 30 & echo $!; wait", false).to_command();
+        cmd.stdout(Stdio::piped());
+        isolate_process_group(&mut cmd);
+        let mut child = cmd.spawn().expect("Failed to spawn sh");
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let grandchild: u32 = line.trim().parse().expect("Expected a PID");
+
+        signal_process(child.id(), Signal::Kill).expect("Failed to kill group");
+        child.wait().unwrap();
+
+        // Once killed the grandchild is gone or, until reparented and reaped, a zombie
+        let gone = wait_for_exit(Duration::from_secs(2), || {
+            std::fs::read_to_string(format!("/proc/{}/stat", grandchild))
+                .map(|stat| !stat.contains(") Z "))
+                .unwrap_or(false)
+        });
+        assert!(gone, "Grandchild {} survived the group kill", grandchild);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_process_alive() {
+        assert!(is_process_alive(std::process::id()));
+
+        let mut child = shell_invocation("exit 0", false)
+            .to_command()
+            .spawn()
+            .expect("Failed to spawn sh");
+        let pid = child.id();
+        child.wait().unwrap();
+        assert!(!is_process_alive(pid));
+    }
+
+    #[test]
+    fn test_parse_json_output() {
+        let parsed = parse_json_output(b"[{\"name\": \"web\", \"up\": true}]\n").unwrap();
+        assert_eq!(parsed, serde_json::json!([{ "name": "web", "up": true }]));
+
+        let err = parse_json_output(b"Starting...\n{}").unwrap_err();
+        assert!(err.starts_with("Output is not valid JSON"), "{}", err);
+        assert!(parse_json_output(b"").is_err());
+    }
+
+    #[test]
+    fn test_clipboard_text_drops_trailing_line_breaks() {
+        assert_eq!(clipboard_text(b"3f9a\n"), Some("3f9a"));
+        assert_eq!(
+            clipboard_text(b"line 1\r\nline 2\r\n"),
+            Some("line 1\r\nline 2")
+        );
+        assert_eq!(clipboard_text(b"  indented  \n"), Some("  indented  "));
+        assert_eq!(clipboard_text(b" \n\n"), None);
+        assert_eq!(clipboard_text(&[0xff, 0xfe]), None);
+    }
+
+    #[test]
+    fn test_truncate_output_keeps_a_prefix() {
+        let mut short = ExecutionResult::text("héllo");
+        assert_eq!(truncate_output(&mut short, 5), None);
+        assert_eq!(short.output, "héllo");
+
+        // Counted in characters, so a multi-byte one is never split
+        let mut long = ExecutionResult::text("héllo world");
+        assert_eq!(
+            truncate_output(&mut long, 2).as_deref(),
+            Some("héllo world")
+        );
+        assert_eq!(long.output, format!("hé{}", TRUNCATION_MARKER));
+
+        let mut binary = encode_output(vec![0xff; 64], OutputDecoding::Utf8);
+        let encoded = binary.output.clone();
+        assert_eq!(truncate_output(&mut binary, 2), None);
+        assert_eq!(binary.output, encoded);
+    }
+
+    #[test]
+    fn test_nice_range_and_priority_classes() {
+        assert_eq!(check_nice(-20), Ok(-20));
+        assert_eq!(check_nice(19), Ok(19));
+        assert_eq!(
+            check_nice(20),
+            Err("Invalid nice value 20: expected -20 to 19".to_string())
+        );
+        assert!(check_nice(-21).is_err());
+
+        assert_eq!(clamp_nice(-40), -20);
+        assert_eq!(clamp_nice(5), 5);
+        assert_eq!(clamp_nice(100), 19);
+
+        assert_eq!(priority_class(0), 0x20);
+        assert_eq!(priority_class(5), 0x4000);
+        assert_eq!(priority_class(19), 0x40);
+        assert_eq!(priority_class(-5), 0x8000);
+        assert_eq!(priority_class(-100), 0x80);
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(Signal::parse("TERM"), Ok(Signal::Term));
+        assert_eq!(Signal::parse("sigint"), Ok(Signal::Int));
+        assert_eq!(Signal::parse(" Hup "), Ok(Signal::Hup));
+        assert!(Signal::parse("USR1").is_err());
+        assert!(Signal::Kill.is_forced());
+        assert!(!Signal::Term.is_forced());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_numbers() {
+        let numbers: Vec<i32> = [
+            Signal::Hup,
+            Signal::Int,
+            Signal::Quit,
+            Signal::Term,
+            Signal::Kill,
+        ]
+        .iter()
+        .map(|s| s.number())
+        .collect();
+        assert_eq!(numbers, vec![1, 2, 3, 15, 9]);
+    }
+
+    #[test]
+    fn test_sudo_auth_failure_detection() {
+        assert!(is_sudo_auth_failure(
+            "Sorry, try again.\nsudo: 1 incorrect password attempt"
+        ));
+        assert!(!is_sudo_auth_failure("E: Could not open lock file"));
+    }
+}
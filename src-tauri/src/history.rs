@@ -0,0 +1,412 @@
+use crate::error::AppError;
+use crate::models::Command;
+use crate::store::{create_file, FileLock, LOCK_TIMEOUT};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+/// Maximum number of entries kept in the history file; oldest entries are dropped first.
+pub const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// Lines the history file may grow to before [`append_entry`] trims it back to
+/// [`MAX_HISTORY_ENTRIES`], so most runs append a line instead of rewriting the file.
+const COMPACT_AFTER_ENTRIES: usize = 2 * MAX_HISTORY_ENTRIES;
+
+/// A single recorded command execution.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub command_id: String,
+    /// RFC 3339 timestamp of when the script was spawned
+    pub started_at: String,
+    pub duration_ms: u64,
+    /// `None` when the process was terminated by a signal
+    pub exit_code: Option<i32>,
+}
+
+/// Retrieves the last [`MAX_HISTORY_ENTRIES`] executions, oldest first.
+///
+/// The file holds one JSON entry per line; a JSON array written by older versions is
+/// read too. Returns an empty vector if no history has been recorded yet.
+///
+/// # Arguments
+///
+/// * `path` - Path to the history JSON file
+///
+/// # Returns
+///
+/// * `Ok(Vec<HistoryEntry>)` - Recorded executions (empty if file doesn't exist)
+/// * `Err(AppError)` - Error if file cannot be read or JSON is invalid
+pub fn get_history(path: &Path) -> Result<Vec<HistoryEntry>, AppError> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut entries = parse_history(&std::fs::read_to_string(path)?)?;
+    let excess = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+    entries.drain(..excess);
+    Ok(entries)
+}
+
+fn is_legacy_history(text: &str) -> bool {
+    text.trim_start().starts_with('[')
+}
+
+fn parse_history(text: &str) -> Result<Vec<HistoryEntry>, AppError> {
+    if is_legacy_history(text) {
+        return serde_json::from_str(text).map_err(AppError::from);
+    }
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(AppError::from))
+        .collect()
+}
+
+/// Appends an entry to the history file as one JSON line.
+///
+/// The lock is held from the read to the write, so concurrent runs don't lose
+/// entries. Once the file passes twice [`MAX_HISTORY_ENTRIES`] (or is still an older
+/// JSON array) it is rewritten with the newest entries only. The file is owner-only
+/// with `restrict`, like the store: entries name the commands run and when.
+///
+/// # Arguments
+///
+/// * `path` - Path to the history JSON file
+/// * `entry` - The execution to record
+/// * `restrict` - Create the file with mode 0600 (unix)
+pub fn append_entry(path: &Path, entry: HistoryEntry, restrict: bool) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let _lock = FileLock::acquire(path, LOCK_TIMEOUT)?;
+    if !path.exists() {
+        return write_lines(create_file(path, restrict)?, &[entry]);
+    }
+
+    let text = std::fs::read_to_string(path)?;
+    let lines = text.lines().filter(|line| !line.trim().is_empty()).count();
+    if !is_legacy_history(&text) && lines < COMPACT_AFTER_ENTRIES {
+        let file = std::fs::OpenOptions::new().append(true).open(path)?;
+        return write_lines(file, &[entry]);
+    }
+
+    let mut entries = parse_history(&text)?;
+    entries.push(entry);
+    let excess = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+    entries.drain(..excess);
+    write_lines(create_file(path, restrict)?, &entries)
+}
+
+/// Writes each entry as a line of JSON, in one write so an append is never torn.
+fn write_lines(mut file: File, entries: &[HistoryEntry]) -> Result<(), AppError> {
+    let mut buffer = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut buffer, entry)?;
+        buffer.push(b'\n');
+    }
+    file.write_all(&buffer).map_err(AppError::from)
+}
+
+/// Writes history entries as newline-delimited JSON (one object per line).
+///
+/// An empty slice produces an empty file, so downstream tooling never sees a missing file.
+///
+/// # Arguments
+///
+/// * `path` - Destination file; its parent directory is created if needed
+/// * `entries` - The entries to export
+pub fn export_ndjson(path: &Path, entries: &[HistoryEntry]) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(file);
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush().map_err(AppError::from)
+}
+
+/// Reads the ID saved by [`save_last_command`]; `None` if nothing has been saved.
+pub fn load_last_command(path: &Path) -> Result<Option<String>, AppError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let id = std::fs::read_to_string(path)?;
+    Ok(Some(id.trim().to_string()).filter(|id| !id.is_empty()))
+}
+
+/// Saves the ID of the last command run to its own small file.
+///
+/// It changes on every run, so it isn't kept in the config: rewriting the config that
+/// often would race config updates and wake the file watcher each time.
+pub fn save_last_command(path: &Path, id: &str) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, id).map_err(AppError::from)
+}
+
+/// The most recently started command, for `rerun_last_command` and `rerun_last`.
+///
+/// Held in managed state and mirrored to its own file with [`save_last_command`], so
+/// it survives restarts. The command started since launch is tracked separately, for
+/// `rerun_last`, which only repeats something run in this session.
+#[derive(Default)]
+pub struct LastCommand {
+    id: Mutex<Option<String>>,
+    this_session: Mutex<Option<String>>,
+}
+
+impl LastCommand {
+    pub fn new(id: Option<String>) -> Self {
+        Self {
+            id: Mutex::new(id),
+            this_session: Mutex::new(None),
+        }
+    }
+
+    /// Records `id` as the last command, returning whether it changed.
+    pub fn record(&self, id: &str) -> bool {
+        *self
+            .this_session
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(id.to_string());
+        let mut last = self.id.lock().unwrap_or_else(PoisonError::into_inner);
+        if last.as_deref() == Some(id) {
+            return false;
+        }
+        *last = Some(id.to_string());
+        true
+    }
+
+    pub fn get(&self) -> Option<String> {
+        self.id
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Finds the last command in `commands`; with `this_session`, only a command
+    /// started since launch counts, not one restored from the last session.
+    ///
+    /// Fails with `NotFound` when nothing has run yet, or the command has since been
+    /// deleted.
+    pub fn resolve<'a>(
+        &self,
+        commands: &'a [Command],
+        this_session: bool,
+    ) -> Result<&'a Command, AppError> {
+        let (id, never_run) = if this_session {
+            let id = self
+                .this_session
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone();
+            (id, "No command has been run yet this session")
+        } else {
+            (self.get(), "No command has been run yet")
+        };
+        let id = id.ok_or_else(|| AppError::NotFound(never_run.to_string()))?;
+        commands.iter().find(|c| c.id == id).ok_or_else(|| {
+            AppError::NotFound(format!("The last command run ({}) no longer exists", id))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn entry(command_id: &str, exit_code: Option<i32>) -> HistoryEntry {
+        HistoryEntry {
+            command_id: command_id.to_string(),
+            started_at: "2026-01-01T00:00:00+00:00".to_string(),
+            duration_ms: 42,
+            exit_code,
+        }
+    }
+
+    #[test]
+    fn test_export_ndjson() {
+        let temp_dir = std::env::temp_dir().join("climgr_history_export");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let history_path = temp_dir.join("history.json");
+        let export_path = temp_dir.join("history.ndjson");
+
+        append_entry(&history_path, entry("1", Some(0)), true).expect("Failed to append");
+        append_entry(&history_path, entry("2", None), true).expect("Failed to append");
+        let entries = get_history(&history_path).expect("Failed to load history");
+
+        export_ndjson(&export_path, &entries).expect("Failed to export");
+
+        let contents = fs::read_to_string(&export_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let first: HistoryEntry = serde_json::from_str(lines[0]).unwrap();
+        let second: HistoryEntry = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(first, entries[0]);
+        assert_eq!(second.exit_code, None);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_concurrent_appends_are_all_kept() {
+        let temp_dir = std::env::temp_dir().join("climgr_history_concurrent");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let history_path = temp_dir.join("history.json");
+
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let history_path = history_path.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        append_entry(&history_path, entry(&i.to_string(), Some(0)), true)
+                            .expect("Append should wait for the lock");
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(get_history(&history_path).unwrap().len(), 80);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_legacy_history_is_read_and_trimmed() {
+        let temp_dir = std::env::temp_dir().join("climgr_history_legacy");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let history_path = temp_dir.join("history.json");
+
+        let old: Vec<HistoryEntry> = (0..MAX_HISTORY_ENTRIES)
+            .map(|i| entry(&i.to_string(), Some(0)))
+            .collect();
+        fs::write(&history_path, serde_json::to_string_pretty(&old).unwrap()).unwrap();
+        assert_eq!(get_history(&history_path).unwrap(), old);
+
+        // The first append rewrites it as lines, dropping the oldest entry
+        append_entry(&history_path, entry("new", None), true).unwrap();
+        let entries = get_history(&history_path).unwrap();
+        assert_eq!(entries.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(entries[0].command_id, "1");
+        assert_eq!(entries.last().unwrap().command_id, "new");
+        assert!(!is_legacy_history(
+            &fs::read_to_string(&history_path).unwrap()
+        ));
+
+        // Later ones only add a line
+        append_entry(&history_path, entry("newer", None), true).unwrap();
+        let text = fs::read_to_string(&history_path).unwrap();
+        assert_eq!(text.lines().count(), MAX_HISTORY_ENTRIES + 1);
+        let entries = get_history(&history_path).unwrap();
+        assert_eq!(entries.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(entries.last().unwrap().command_id, "newer");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_history_file_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = std::env::temp_dir().join("climgr_history_permissions");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let history_path = temp_dir.join("history.json");
+        let mode = || fs::metadata(&history_path).unwrap().permissions().mode() & 0o777;
+
+        append_entry(&history_path, entry("1", Some(0)), true).unwrap();
+        assert_eq!(mode(), 0o600);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_export_ndjson_without_history_writes_empty_file() {
+        let temp_dir = std::env::temp_dir().join("climgr_history_empty");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let export_path = temp_dir.join("history.ndjson");
+
+        let entries = get_history(&temp_dir.join("missing.json")).unwrap();
+        export_ndjson(&export_path, &entries).expect("Empty export should succeed");
+
+        assert!(export_path.exists());
+        assert_eq!(fs::read_to_string(&export_path).unwrap(), "");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_last_command_follows_the_latest_run() {
+        let commands: Vec<Command> = ["build", "deploy"]
+            .iter()
+            .map(|id| Command {
+                id: id.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let last = LastCommand::default();
+        assert_eq!(
+            last.resolve(&commands, false).unwrap_err().code(),
+            "not_found"
+        );
+
+        assert!(last.record("build"));
+        assert!(last.record("deploy"));
+        assert!(!last.record("deploy"));
+        assert_eq!(last.get().as_deref(), Some("deploy"));
+        assert_eq!(last.resolve(&commands, false).unwrap().id, "deploy");
+
+        // Restored from disk, then deleted from the store
+        let stale = LastCommand::new(Some("gone".to_string()));
+        let err = stale.resolve(&commands, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The last command run (gone) no longer exists"
+        );
+    }
+
+    #[test]
+    fn test_this_session_ignores_the_restored_command() {
+        let commands = vec![Command {
+            id: "build".to_string(),
+            ..Default::default()
+        }];
+        let last = LastCommand::new(Some("build".to_string()));
+        assert_eq!(last.resolve(&commands, false).unwrap().id, "build");
+        let err = last.resolve(&commands, true).unwrap_err();
+        assert_eq!(err.code(), "not_found");
+        assert_eq!(err.to_string(), "No command has been run yet this session");
+
+        // Tracked even when it matches the restored one
+        assert!(!last.record("build"));
+        assert_eq!(last.resolve(&commands, true).unwrap().id, "build");
+    }
+
+    #[test]
+    fn test_last_command_file_round_trip() {
+        let temp_dir = std::env::temp_dir().join("climgr_last_command");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.join("last_command");
+
+        assert_eq!(load_last_command(&path).unwrap(), None);
+        save_last_command(&path, "build").unwrap();
+        save_last_command(&path, "deploy").unwrap();
+        assert_eq!(load_last_command(&path).unwrap().as_deref(), Some("deploy"));
+
+        fs::write(&path, "\n").unwrap();
+        assert_eq!(load_last_command(&path).unwrap(), None);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -1,430 +1,2967 @@
+pub mod aliases;
+pub mod commands;
+pub mod diagnostics;
+pub mod error;
+pub mod executor;
+pub mod history;
 pub mod models;
+pub mod passphrase;
+pub mod queue;
+pub mod runner;
+pub mod schedule;
+pub mod search;
+pub mod shortcuts;
+pub mod stats;
 pub mod store;
+pub mod stream;
+pub mod template;
+pub mod watcher;
 
-use crate::models::{Command, Config};
+use crate::error::AppError;
+use crate::history::HistoryEntry;
+use crate::models::{
+    AppPaths, BatchOp, BatchOpResult, Command, CommandNotification, CommandRunResult,
+    CommandStatus, CommandVariable, Config, ExecutionResult, PathInfo, RelocationSummary,
+    SaveReport, TrashedCommand,
+};
+use crate::search::ScoredCommand;
+use crate::shortcuts::ShortcutError;
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::Mutex;
-use tauri::{AppHandle, Manager, State};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_clipboard_manager::ClipboardExt;
 use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use zeroize::Zeroizing;
 
 struct ProcessManager {
     processes: Mutex<HashMap<String, u32>>,
 }
 
-fn run_command_script(
+impl ProcessManager {
+    /// Locks the running-process map.
+    ///
+    /// A thread that panicked while holding the lock leaves the map itself intact, so a
+    /// poisoned lock is recovered rather than breaking command tracking for the session.
+    fn processes(&self) -> MutexGuard<'_, HashMap<String, u32>> {
+        self.processes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stops tracking processes that no longer exist, returning their command IDs.
+    ///
+    /// Normally a run removes its own entry once it has waited for the process; this
+    /// cleans up after a process that died without that happening. Liveness is checked
+    /// without holding the lock, and an entry is only removed if it still holds the same
+    /// PID, so a run started in the meantime is kept.
+    fn prune_dead(&self) -> Vec<String> {
+        let tracked: Vec<(String, u32)> = self
+            .processes()
+            .iter()
+            .map(|(id, pid)| (id.clone(), *pid))
+            .collect();
+        let dead: Vec<(String, u32)> = tracked
+            .into_iter()
+            .filter(|(_, pid)| !executor::is_process_alive(*pid))
+            .collect();
+
+        let mut processes = self.processes();
+        let mut pruned = Vec::new();
+        for (id, pid) in dead {
+            if processes.get(&id) == Some(&pid) {
+                processes.remove(&id);
+                pruned.push(id);
+            }
+        }
+        pruned.sort();
+        pruned
+    }
+}
+
+/// How often tracked processes are checked for ones that died unnoticed.
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// When each command last started, for `min_interval_secs` throttling.
+struct RunTimes {
+    last_runs: Mutex<HashMap<String, std::time::Instant>>,
+}
+
+impl RunTimes {
+    /// Records a run of `command` starting now, unless its throttle refuses it.
+    fn start(&self, command: &Command) -> Result<(), AppError> {
+        if command.min_interval_secs.is_none() {
+            return Ok(());
+        }
+        let now = std::time::Instant::now();
+        let mut last_runs = self
+            .last_runs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        commands::check_rate_limit(command, last_runs.get(&command.id).copied(), now)?;
+        last_runs.insert(command.id.clone(), now);
+        Ok(())
+    }
+}
+
+/// Where the commands loaded from `commands_dir` came from, as of the last load.
+struct IncludedCommands {
+    included: Mutex<store::Included>,
+}
+
+/// How often `command-heartbeat` is emitted while a command is running.
+const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Payload of the `command-heartbeat` event.
+#[derive(Clone, serde::Serialize)]
+struct Heartbeat {
+    command_id: String,
+    elapsed_ms: u64,
+}
+
+/// Payload of the `command-step` event, emitted as each step of a multi-step command ends.
+#[derive(Clone, serde::Serialize)]
+struct StepProgress {
+    command_id: String,
+    #[serde(flatten)]
+    step: models::StepResult,
+}
+
+/// Payload of the `command-output` event, a batch of lines streamed while a command runs.
+#[derive(Clone, serde::Serialize)]
+struct OutputBatch {
+    command_id: String,
+    lines: Vec<String>,
+}
+
+/// Payload of the `command://progress` event, emitted when output reports progress.
+#[derive(Clone, serde::Serialize)]
+struct ProgressUpdate {
+    command_id: String,
+    percent: f64,
+}
+
+/// Emits `command-output` for lines sent to the returned sender, batched per `settings`.
+///
+/// With a `progress` pattern, the latest percentage in each batch is also emitted as
+/// `command://progress`, unless it is the one emitted last.
+///
+/// The channel is bounded (see [`stream::CHANNEL_CAPACITY`]), so a flood of output
+/// slows the reader threads down rather than the event channel. The thread ends, after
+/// flushing, once every clone of the sender is dropped; join it to know all output
+/// events were emitted.
+fn spawn_output_emitter(
     app_handle: &AppHandle,
     command_id: &str,
-    script: &str,
-) -> Result<String, String> {
+    settings: stream::BatchSettings,
+    progress: Option<regex::Regex>,
+) -> (
+    std::sync::mpsc::SyncSender<String>,
+    std::thread::JoinHandle<()>,
+) {
+    let (lines_tx, lines_rx) = std::sync::mpsc::sync_channel(stream::CHANNEL_CAPACITY);
+    let app_handle = app_handle.clone();
+    let command_id = command_id.to_string();
+
+    let emitter = std::thread::spawn(move || {
+        let mut last_percent = None;
+        stream::forward(lines_rx, settings, |lines| {
+            let percent = progress
+                .as_ref()
+                .and_then(|pattern| stream::latest_progress(pattern, &lines));
+            let payload = OutputBatch {
+                command_id: command_id.clone(),
+                lines,
+            };
+            if let Err(e) = app_handle.emit("command-output", payload) {
+                log::warn!("Failed to emit output of {}: {}", command_id, e);
+            }
+            if let Some(percent) = percent.filter(|p| last_percent != Some(*p)) {
+                last_percent = Some(percent);
+                let update = ProgressUpdate {
+                    command_id: command_id.clone(),
+                    percent,
+                };
+                if let Err(e) = app_handle.emit("command://progress", update) {
+                    log::warn!("Failed to emit progress of {}: {}", command_id, e);
+                }
+            }
+        });
+    });
+    (lines_tx, emitter)
+}
+
+/// Emits `command-heartbeat` every [`HEARTBEAT_INTERVAL`] until the returned sender is dropped.
+///
+/// Lets the UI tell a quiet-but-alive command apart from a hung one.
+fn spawn_heartbeat(
+    app_handle: &AppHandle,
+    command_id: &str,
+    start: std::time::Instant,
+) -> std::sync::mpsc::Sender<()> {
+    let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+    let app_handle = app_handle.clone();
+    let command_id = command_id.to_string();
+
+    std::thread::spawn(move || {
+        while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+            stop_rx.recv_timeout(HEARTBEAT_INTERVAL)
+        {
+            let payload = Heartbeat {
+                command_id: command_id.clone(),
+                elapsed_ms: start.elapsed().as_millis() as u64,
+            };
+            if let Err(e) = app_handle.emit("command-heartbeat", payload) {
+                log::warn!("Failed to emit heartbeat for {}: {}", command_id, e);
+            }
+        }
+    });
+
+    stop_tx
+}
+
+/// Runs a command's script and returns the streams selected by its `capture` mode
+/// (stdout and stderr combined by default).
+///
+/// Commands with `steps` run each step in turn, stopping at the first failure and
+/// emitting `command-step` as each one ends.
+/// Commands with `run_in_terminal` are handed to [`open_in_terminal`] instead.
+/// With `Config.serialize_executions`, the run waits its turn in the
+/// [`queue::ExecutionQueue`] and happens on its worker thread.
+/// Macros run each command in `macro_steps` through this function in turn (see
+/// [`runner::run_macro`]), with the same password and params.
+/// Afterwards `on_success_script` or `on_failure_script` runs, depending on the exit
+/// status and `failure_pattern`, unless safe mode was turned on meanwhile; its output
+/// is appended.
+/// `sudo_password` is only used for commands with `requires_sudo`; it is fed to
+/// `sudo -S` over stdin, never logged or persisted, and zeroed when dropped.
+fn run_command_script(
+    app_handle: &AppHandle,
+    command: &Command,
+    sudo_password: Option<Zeroizing<String>>,
+    params: HashMap<String, String>,
+    bypass_token: Option<&str>,
+) -> Result<ExecutionResult, AppError> {
+    let command_id = command.id.as_str();
+
     // Check safe mode
     let config_path = get_config_path(app_handle)?;
     let config = store::get_config(&config_path)?;
 
+    if config.serialize_executions.unwrap_or(false) && !queue::on_worker() {
+        let handle = app_handle.clone();
+        let command = command.clone();
+        let bypass_token = bypass_token.map(String::from);
+        return app_handle
+            .state::<queue::ExecutionQueue>()
+            .run(command_id, move || {
+                run_command_script(
+                    &handle,
+                    &command,
+                    sudo_password,
+                    params,
+                    bypass_token.as_deref(),
+                )
+            })
+            .map_err(AppError::Execution)?;
+    }
+
     if config.safe_mode {
-        return Err("Command execution disabled in safe mode. Disable safe mode in settings to execute commands.".to_string());
+        if !passphrase::bypass_allowed(&config, bypass_token) {
+            return Err(AppError::SafeModeBlocked);
+        }
+        log::warn!(
+            "Running command {} in safe mode with the bypass token",
+            command_id
+        );
+    }
+    app_handle.state::<RunTimes>().start(command)?;
+    let diagnostic = command_id == diagnostics::ECHO_COMMAND_ID;
+    if !diagnostic {
+        remember_last_command(app_handle, command_id);
+    }
+
+    if command.is_macro() {
+        let commands = load_store(app_handle, &get_store_path(app_handle)?)?;
+        let steps = commands::macro_steps(&commands, command)?;
+        log::info!("Running macro {}: {:?}", command_id, command.macro_steps);
+        let continue_on_error = command.continue_on_error.unwrap_or(false);
+        return Ok(runner::run_macro(&steps, continue_on_error, |step| {
+            run_command_script(
+                app_handle,
+                step,
+                sudo_password.clone(),
+                params.clone(),
+                bypass_token,
+            )
+        }));
     }
 
-    log::info!("Executing script for command {}: {}", command_id, script);
+    let mut rendered = render_command(app_handle, command, &config, params)?;
+    rendered.strip_ansi = Some(config.strip_ansi(&rendered));
+    rendered.login_shell = Some(config.login_shell(&rendered));
+    rendered.env = Some(config.command_env(&rendered)).filter(|env| !env.is_empty());
+    rendered.interactive_shell = Some(config.interactive_shell(&rendered));
+    let command = &rendered;
+    let script = command.script.as_str();
 
-    let child = std::process::Command::new("sh")
-        .arg("-c")
-        .arg(script)
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("Failed to spawn command: {}", e))?;
+    if let Some(user) = command.run_as() {
+        log::warn!(
+            "Command {} runs as user '{}' with elevated privileges",
+            command_id,
+            user
+        );
+    }
+
+    if command.run_in_terminal.unwrap_or(false) {
+        return open_in_terminal(command);
+    }
+    if command.detached.unwrap_or(false) {
+        return start_detached(command, sudo_password);
+    }
+
+    let requires_sudo = command.requires_sudo.unwrap_or(false);
+
+    match command.steps.as_deref().filter(|steps| !steps.is_empty()) {
+        Some(steps) => log::info!("Executing steps for command {}: {:?}", command_id, steps),
+        None => log::info!("Executing script for command {}: {}", command_id, script),
+    }
+
+    let started_at = chrono::Utc::now();
+    let start = std::time::Instant::now();
+    let runner = app_handle.state::<runner::Runner>();
+
+    // Validated on save, but a hand-edited store may still hold a bad pattern
+    let progress = match command
+        .progress_regex
+        .as_deref()
+        .map(stream::progress_pattern)
+    {
+        Some(Ok(pattern)) => Some(pattern),
+        Some(Err(e)) => {
+            log::warn!("Not reporting progress of {}: {}", command_id, e);
+            None
+        }
+        None => None,
+    };
+    let (output_lines, emitter) =
+        spawn_output_emitter(app_handle, command_id, config.output_batch(), progress);
+
+    let mut spawned = false;
+    let mut heartbeat = None;
+    let result = runner::execute(
+        runner.0.as_ref(),
+        command,
+        sudo_password,
+        start,
+        Some(output_lines),
+        // Called once per step; kill_command always targets the current step
+        &mut |pid| {
+            spawned = true;
+            let state = app_handle.state::<ProcessManager>();
+            state.processes().insert(command_id.to_string(), pid);
+            if heartbeat.is_none() {
+                heartbeat = Some(spawn_heartbeat(app_handle, command_id, start));
+            }
+        },
+        &mut |step| {
+            let payload = StepProgress {
+                command_id: command_id.to_string(),
+                step: step.clone(),
+            };
+            if let Err(e) = app_handle.emit("command-step", payload) {
+                log::warn!(
+                    "Failed to emit step {} of {}: {}",
+                    step.index,
+                    command_id,
+                    e
+                );
+            }
+        },
+    );
+    drop(heartbeat);
+    // The senders went with the finished processes; wait for the last batch
+    if emitter.join().is_err() {
+        log::warn!("Output emitter for {} panicked", command_id);
+    }
+
+    if spawned {
+        let state = app_handle.state::<ProcessManager>();
+        state.processes().remove(command_id);
+    }
+
+    let execution = result.map_err(AppError::Execution)?;
+    let duration_ms = execution.duration.as_millis() as u64;
+
+    let entry = HistoryEntry {
+        command_id: command_id.to_string(),
+        started_at: started_at.to_rfc3339(),
+        duration_ms,
+        exit_code: execution.status.code(),
+    };
+    if !diagnostic {
+        record_run(app_handle, &entry);
+        record_history(app_handle, entry);
+    }
+
+    let stderr = String::from_utf8_lossy(&execution.stderr);
+    if requires_sudo && !execution.status.success() && executor::is_sudo_auth_failure(&stderr) {
+        return Err(AppError::Invalid("Incorrect sudo password".to_string()));
+    }
+
+    let (json, json_error) = if command.output_is_json() {
+        match executor::parse_json_output(&execution.stdout) {
+            Ok(value) => (Some(value), None),
+            Err(e) => {
+                log::warn!("Command {}: {}", command_id, e);
+                (None, Some(e))
+            }
+        }
+    } else {
+        (None, None)
+    };
+
+    let mut success = command.is_success(execution.status.code());
+    if success && executor::output_failed(command, &execution.output) {
+        log::info!(
+            "Output of command {} matched its failure pattern",
+            command_id
+        );
+        success = false;
+    }
+    let mut bytes = execution.output;
+    if command.copy_output.unwrap_or(false) && success {
+        copy_to_clipboard(app_handle, command_id, &bytes);
+    }
+    if let Some((name, hook)) = executor::post_run_hook(command, success) {
+        // Safe mode may have been switched on while the script was running
+        let safe_mode = store::get_config(&config_path).map_or(true, |c| c.safe_mode);
+        if safe_mode {
+            log::info!("Safe mode is on; skipping {} hook of {}", name, command_id);
+        } else {
+            log::info!("Running {} hook for command {}", name, command_id);
+            let hook_output = executor::run_hook(name, hook);
+            if config.strip_ansi(command) {
+                bytes.extend(executor::strip_ansi(&hook_output));
+            } else {
+                bytes.extend(hook_output);
+            }
+        }
+    }
+    Ok(ExecutionResult {
+        duration_ms: Some(duration_ms),
+        peak_memory_bytes: execution.peak_memory_bytes,
+        steps: execution.steps,
+        exit_code: execution.status.code(),
+        success: Some(success),
+        json,
+        json_error,
+        ..executor::encode_output(bytes, config.output_decoding(command))
+    })
+}
+
+/// Puts a `copy_output` command's output on the system clipboard.
+///
+/// Failing to copy doesn't fail the run; the output is still returned.
+fn copy_to_clipboard(app_handle: &AppHandle, command_id: &str, output: &[u8]) {
+    let Some(text) = executor::clipboard_text(output) else {
+        log::info!("Nothing to copy from command {}", command_id);
+        return;
+    };
+    match app_handle.clipboard().write_text(text) {
+        Ok(()) => log::info!("Copied output of command {} to the clipboard", command_id),
+        Err(e) => log::warn!("Failed to copy output of {}: {}", command_id, e),
+    }
+}
+
+/// `command` with the template variables in its script and steps substituted.
+///
+/// See [`template::Params`] for the variables; `params` are the values supplied for
+/// this run, completed with the defaults of the command's declared `variables`. Fails
+/// if a required variable has no value. Unknown tokens are kept unless
+/// `Config.strict_templates` is set.
+fn render_command(
+    app_handle: &AppHandle,
+    command: &Command,
+    config: &Config,
+    params: HashMap<String, String>,
+) -> Result<Command, AppError> {
+    let declared = command.variables.as_deref().unwrap_or_default();
+    let user = template::resolve_variables(declared, params).map_err(AppError::Invalid)?;
+    let store_path = get_store_path(app_handle)?;
+    let params = template::Params {
+        store_dir: store_path
+            .parent()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default(),
+        home: app_handle
+            .path()
+            .home_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default(),
+        now: chrono::Local::now().to_rfc3339(),
+        user,
+        strict: config.strict_templates.unwrap_or(false),
+    };
+    template::render_command(command, &params).map_err(AppError::Invalid)
+}
+
+/// Starts a `detached` command and returns without waiting.
+///
+/// The PID isn't tracked, so `kill_command` doesn't apply; output isn't captured and
+/// no history is recorded.
+fn start_detached(
+    command: &Command,
+    sudo_password: Option<Zeroizing<String>>,
+) -> Result<ExecutionResult, AppError> {
+    let request =
+        runner::RunRequest::for_command(command, sudo_password).map_err(AppError::Execution)?;
+    let pid = runner::spawn_detached(request).map_err(AppError::Execution)?;
+    log::info!("Started detached command {} (pid {})", command.id, pid);
+    Ok(ExecutionResult::text(format!("Started (pid {})", pid)))
+}
+
+/// Launches a command's script in a new terminal window and returns without waiting.
+///
+/// The terminal provides a real TTY, so `requires_sudo` is not applied here: sudo
+/// prompts in the window itself. Output isn't captured and no history is recorded.
+fn open_in_terminal(command: &Command) -> Result<ExecutionResult, AppError> {
+    let script = match command.run_as() {
+        Some(user) => executor::run_as_command_line(&command.script, user),
+        None => command.script.clone(),
+    };
+    let invocation = executor::terminal_invocation(&script);
+    log::info!(
+        "Opening command {} in terminal via {}",
+        command.id,
+        invocation.program
+    );
 
+    let mut child = invocation
+        .to_command()
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| {
+            AppError::Execution(format!(
+                "Failed to open terminal ({}): {}",
+                invocation.program, e
+            ))
+        })?;
     let pid = child.id();
 
+    // Some launchers exit at once, others live as long as the window; reap either way
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => log::warn!("Terminal launcher exited with {}", status),
+        Ok(_) => {}
+        Err(e) => log::warn!("Failed to wait for terminal launcher: {}", e),
+    });
+
+    Ok(ExecutionResult::text(format!(
+        "Opened in a new terminal (pid {})",
+        pid
+    )))
+}
+
+/// Executes a command by its ID.
+///
+/// This Tauri command looks up a command by its unique ID, checks if safe mode is enabled,
+/// and executes the associated shell script if allowed.
+///
+/// Commands listed in `depends_on` run first, transitively, each once and before the
+/// commands that need them; the chain stops at the first one that fails. Only the
+/// result of the requested command is returned.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `command_id` - The unique identifier of the command to execute
+/// * `password` - Transient sudo password, required for commands with `requires_sudo`
+/// * `params` - Values for `{{name}}` tokens in the script, alongside the built-in
+///   template variables (`{{command.id}}`, `{{command.name}}`, `{{store_dir}}`,
+///   `{{home}}`, `{{now}}`)
+/// * `bypass_token` - Runs this one command despite safe mode when it equals
+///   `Config.safe_mode_bypass_token`
+///
+/// # Returns
+///
+/// * `Ok(ExecutionResult)` - The run's result:
+///   - `output` - The captured output (stdout and stderr combined by default); text
+///     longer than `Config.ui_output_max_chars` is cut off with a truncation marker
+///   - `encoding` - `"base64"` if `output` is base64-encoded bytes: always when
+///     `Config.output_encoding` is `"raw-base64"`, and for non-UTF-8 output when it
+///     is `"utf8"` or the command has `base64_fallback`; `"utf8"` otherwise
+///   - `duration_ms` - The wall-clock run time
+///   - `peak_memory_bytes` - The peak RSS (unix only)
+/// * `Err(AppError)` - Error message if:
+///   - Safe mode is enabled (command execution disabled) and no matching
+///     `bypass_token` was given
+///   - Command ID not found in the stored commands
+///   - A `depends_on` entry is unknown or forms a cycle, or a prerequisite failed
+///   - The command has `min_interval_secs` and last started too recently
+///   - A declared variable without a default is missing from `params`
+///   - A sudo password is required but missing, or was rejected
+///   - `Config.strict_templates` is set and the script has an unknown `{{...}}` token
+///   - Failed to access app data directory
+///   - Failed to read commands from storage
+///   - Script execution failed
+///
+/// # Example
+///
+/// ```javascript
+/// // From the frontend
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// try {
+///   const result = await invoke('execute_command', {
+///     commandId: '123e4567-e89b-12d3-a456-426614174000'
+///   });
+///   console.log('Command output:', result.output);
+/// } catch (error) {
+///   console.error('Execution failed:', error);
+/// }
+/// ```
+///
+/// # Security
+///
+/// This function checks the safe mode configuration before executing any command.
+/// If safe mode is enabled, execution will fail with an appropriate error message,
+/// unless a `bypass_token` matching the configured one is passed. Each bypassed run is
+/// logged as a warning.
+#[tauri::command]
+async fn execute_command(
+    app_handle: tauri::AppHandle,
+    command_id: String,
+    password: Option<String>,
+    params: Option<HashMap<String, String>>,
+    bypass_token: Option<String>,
+) -> Result<ExecutionResult, AppError> {
+    // Wrap immediately so the secret is zeroed on every exit path
+    let password = password.map(Zeroizing::new);
+
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+
+    let mut order: Vec<Command> = commands::run_order(&commands, &command_id)?
+        .into_iter()
+        .cloned()
+        .collect();
+    let command = order.pop().expect("run_order ends with the command itself");
+
+    let app_handle_clone = app_handle.clone();
+
+    let mut result = tauri::async_runtime::spawn_blocking(move || {
+        let bypass_token = bypass_token.as_deref();
+        for prerequisite in &order {
+            log::info!("Running {} before {}", prerequisite.id, command.id);
+            run_prerequisite(&app_handle_clone, prerequisite, bypass_token)?;
+        }
+        run_command_script(
+            &app_handle_clone,
+            &command,
+            password,
+            params.unwrap_or_default(),
+            bypass_token,
+        )
+    })
+    .await
+    .map_err(|e| AppError::Execution(format!("Failed to execute command task: {}", e)))??;
+
+    let max_chars = store::get_config(&get_config_path(&app_handle)?)?.ui_output_max_chars;
+    truncate_for_ui(&command_id, &mut result, max_chars);
+    Ok(result)
+}
+
+/// Records `command_id` as the last command run, saving it if it changed.
+///
+/// Failing to save is only logged; the run goes ahead either way.
+fn remember_last_command(app_handle: &AppHandle, command_id: &str) {
+    if !app_handle
+        .state::<history::LastCommand>()
+        .record(command_id)
+    {
+        return;
+    }
+    let saved = get_last_command_path(app_handle)
+        .and_then(|path| history::save_last_command(&path, command_id));
+    if let Err(e) = saved {
+        log::warn!("Failed to save the last command run: {}", e);
+    }
+}
+
+/// Runs a command from another's `depends_on`, failing unless it succeeds.
+///
+/// It gets no password or params of its own, so it runs with its variables' defaults.
+fn run_prerequisite(
+    app_handle: &AppHandle,
+    command: &Command,
+    bypass_token: Option<&str>,
+) -> Result<(), AppError> {
+    let result = run_command_script(app_handle, command, None, HashMap::new(), bypass_token)
+        .map_err(|e| {
+            AppError::Execution(format!("Prerequisite '{}' failed: {}", command.name, e))
+        })?;
+    if result.succeeded() {
+        return Ok(());
+    }
+    Err(AppError::Execution(match result.exit_code {
+        Some(code) => format!("Prerequisite '{}' exited with code {}", command.name, code),
+        None => format!("Prerequisite '{}' was terminated", command.name),
+    }))
+}
+
+/// Cuts output returned to the UI down to `max_chars`, logging it in full first.
+fn truncate_for_ui(command_id: &str, result: &mut ExecutionResult, max_chars: Option<usize>) {
+    if let Some(max_chars) = max_chars {
+        if let Some(full) = executor::truncate_output(result, max_chars) {
+            log::info!("Full output of command {}:\n{}", command_id, full);
+        }
+    }
+}
+
+/// Executes several commands, one after another or all at once.
+///
+/// Each command runs like it would through `execute_command` without a password or
+/// params: safe mode, throttling and template defaults apply to every one of them.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `ids` - The commands to run, in order
+/// * `parallel` - Start them all at once and wait for every one, instead of in order
+/// * `fail_fast` - In sequential mode, stop after the first command that fails (can't
+///   run or exits non-zero); the rest are left out of the results
+///
+/// # Returns
+///
+/// * `Ok(Vec<CommandRunResult>)` - `{ command_id, result, error }` per command that was
+///   attempted, in the order of `ids`; `error` is set instead of `result` when it couldn't
+///   run
+/// * `Err(AppError)` - The store couldn't be read
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const runs = await invoke('execute_commands', {
+///   ids: [dbId, apiId, webId],
+///   parallel: true,
+/// });
+/// const failed = runs.filter((run) => run.error || run.result.exit_code !== 0);
+/// ```
+#[tauri::command]
+async fn execute_commands(
+    app_handle: tauri::AppHandle,
+    ids: Vec<String>,
+    parallel: Option<bool>,
+    fail_fast: Option<bool>,
+) -> Result<Vec<CommandRunResult>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    let max_chars = store::get_config(&get_config_path(&app_handle)?)?.ui_output_max_chars;
+
+    let runs = tauri::async_runtime::spawn_blocking(move || {
+        runner::run_all(
+            &ids,
+            parallel.unwrap_or(false),
+            fail_fast.unwrap_or(false),
+            |id| {
+                let command = commands::find(&commands, id)?;
+                run_command_script(&app_handle, command, None, HashMap::new(), None)
+            },
+            |result| !result.as_ref().is_ok_and(ExecutionResult::succeeded),
+        )
+    })
+    .await
+    .map_err(|e| AppError::Execution(format!("Failed to execute commands task: {}", e)))?;
+
+    Ok(runs
+        .into_iter()
+        .map(|(command_id, mut result)| {
+            if let Ok(result) = &mut result {
+                truncate_for_ui(&command_id, result, max_chars);
+            }
+            CommandRunResult::new(command_id, result)
+        })
+        .collect())
+}
+
+/// Returns the ID of the most recently started command, if any.
+///
+/// This Tauri command backs a "repeat last command" shortcut. The ID is kept across
+/// restarts, but may name a command that has since been deleted.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing managed state
+///
+/// # Returns
+///
+/// * `Option<String>` - The command ID, or `None` if nothing has run yet
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const lastId = await invoke('get_last_command');
+/// ```
+#[tauri::command]
+fn get_last_command(app_handle: tauri::AppHandle) -> Option<String> {
+    app_handle.state::<history::LastCommand>().get()
+}
+
+/// Runs the most recently started command again.
+///
+/// This Tauri command goes through `execute_command`, so safe mode, sudo and throttling
+/// apply as usual. Template variables fall back to their defaults.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `password` - Transient sudo password, required if the command has `requires_sudo`
+/// * `bypass_token` - Runs it despite safe mode when it equals
+///   `Config.safe_mode_bypass_token`
+///
+/// # Returns
+///
+/// * `Ok(ExecutionResult)` - The result, as returned by `execute_command`
+/// * `Err(AppError)` - `NotFound` if nothing has run yet or the last command was
+///   deleted, or any error `execute_command` returns
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const result = await invoke('rerun_last_command');
+/// ```
+#[tauri::command]
+async fn rerun_last_command(
+    app_handle: tauri::AppHandle,
+    password: Option<String>,
+    bypass_token: Option<String>,
+) -> Result<ExecutionResult, AppError> {
+    rerun(app_handle, false, password, bypass_token).await
+}
+
+/// Runs the command started most recently in this session again.
+///
+/// `rerun_last_command` restricted to this session: a command run before the app was
+/// restarted doesn't count.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `password` - Transient sudo password, required if the command has `requires_sudo`
+/// * `bypass_token` - Runs it despite safe mode when it equals
+///   `Config.safe_mode_bypass_token`
+///
+/// # Returns
+///
+/// * `Ok(ExecutionResult)` - The result, as returned by `execute_command`
+/// * `Err(AppError)` - `NotFound` ("No command has been run yet this session") if
+///   nothing has run since launch or the command was deleted, or any error
+///   `execute_command` returns
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const result = await invoke('rerun_last');
+/// ```
+#[tauri::command]
+async fn rerun_last(
+    app_handle: tauri::AppHandle,
+    password: Option<String>,
+    bypass_token: Option<String>,
+) -> Result<ExecutionResult, AppError> {
+    rerun(app_handle, true, password, bypass_token).await
+}
+
+/// Runs the last command again through `execute_command`, for `rerun_last_command`
+/// and, with `this_session`, `rerun_last`.
+async fn rerun(
+    app_handle: tauri::AppHandle,
+    this_session: bool,
+    password: Option<String>,
+    bypass_token: Option<String>,
+) -> Result<ExecutionResult, AppError> {
+    let commands = load_store(&app_handle, &get_store_path(&app_handle)?)?;
+    let command_id = app_handle
+        .state::<history::LastCommand>()
+        .resolve(&commands, this_session)?
+        .id
+        .clone();
+    execute_command(app_handle, command_id, password, None, bypass_token).await
+}
+
+/// Kills a running command by its ID.
+///
+/// A custom `kill_script` is run if the command has one. Otherwise the process is
+/// killed by PID with `Config.kill_signal`: by default `SIGKILL` (`taskkill /F` on
+/// Windows), at once. Any other signal is sent first (`taskkill` without `/F`), and
+/// `SIGKILL` follows only if the process is still running after `Config.kill_grace_secs`
+/// (3 by default). The graceful path returns as soon as the first signal is queued.
+/// Without a `kill_signal`, the older `Config.graceful_kill` selects `SIGTERM`.
+///
+/// PID-based kills reach the script's whole process group (process tree on Windows),
+/// so children it started, like a dev server, are stopped too.
+///
+/// An explicit `signal` replaces both of those defaults for PID-based kills: it is sent
+/// once, with no escalation. On Windows `KILL` maps to `taskkill /F` and every other
+/// signal to a graceful `taskkill`.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle
+/// * `command_id` - The unique identifier of the command to kill
+/// * `signal` - Optional signal name: `HUP`, `INT`, `QUIT`, `TERM` or `KILL`
+///
+/// # Returns
+///
+/// * `Ok(())` - Command was successfully killed or was not running
+/// * `Err(AppError)` - Error message if killing failed or `signal` is unknown
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// // Let a dev server shut down cleanly
+/// await invoke('kill_command', { commandId: command.id, signal: 'INT' });
+/// ```
+#[tauri::command]
+fn kill_command(
+    app_handle: AppHandle,
+    state: State<ProcessManager>,
+    command_id: String,
+    signal: Option<String>,
+) -> Result<(), AppError> {
+    let signal = signal
+        .as_deref()
+        .map(executor::Signal::parse)
+        .transpose()
+        .map_err(AppError::Invalid)?;
+
+    // 1. Try custom kill script if it exists
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+
+    if let Some(command) = commands.iter().find(|c| c.id == command_id) {
+        if let Some(kill_script) = &command.kill_script {
+            if !kill_script.trim().is_empty() {
+                log::info!("Executing custom kill script for command {}: {}", command_id, kill_script);
+                let output = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(kill_script)
+                    .output()
+                    .map_err(|e| {
+                        AppError::Execution(format!("Failed to execute kill script: {}", e))
+                    })?;
+
+                if !output.status.success() {
+                    log::warn!(
+                        "Kill script exited with error: {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                }
+                // We return Ok here because the script was executed. 
+                // The process manager will clean up the PID if/when the main process dies.
+                return Ok(());
+            }
+        }
+    }
+
+    // 2. Fallback to PID-based kill
+    let pid = {
+        let procs = state.processes();
+        procs.get(&command_id).copied()
+    };
+
+    if let Some(pid) = pid {
+        log::info!("Killing process {} for command {}", pid, command_id);
+
+        if let Some(signal) = signal {
+            return executor::signal_process(pid, signal).map_err(AppError::Execution);
+        }
+
+        let policy = get_config_path(&app_handle)
+            .and_then(|path| store::get_config(&path))
+            .map(|config| config.kill_policy())
+            .unwrap_or(executor::KillPolicy::FORCE);
+
+        if policy.is_graceful() {
+            // Escalation waits up to the grace period, so keep it off the IPC thread.
+            // The process counts as exited once run_command_script has reaped it.
+            std::thread::spawn(move || {
+                let still_running = || {
+                    let state = app_handle.state::<ProcessManager>();
+                    let procs = state.processes();
+                    procs.get(&command_id) == Some(&pid)
+                };
+                match executor::graceful_kill(pid, policy, still_running) {
+                    Ok(true) => log::info!("Force-killed process {} after grace period", pid),
+                    Ok(false) => log::info!("Process {} exited after {:?}", pid, policy.signal),
+                    Err(e) => log::error!("Failed to kill process {}: {}", pid, e),
+                }
+            });
+            return Ok(());
+        }
+
+        executor::signal_process(pid, executor::Signal::Kill).map_err(AppError::Execution)?;
+
+        // The process removal from the map will happen in the run_command_script thread
+        // when wait_for_completion returns.
+    }
+
+    Ok(())
+}
+
+/// Stops tracking processes that have died without being cleaned up.
+///
+/// A process that exits normally is removed once its run finishes. If that never
+/// happens, its stale PID makes the command look like it's still running; this Tauri
+/// command removes such entries. The same check also runs every minute in the
+/// background.
+///
+/// # Arguments
+///
+/// * `state` - The process manager tracking running commands
+///
+/// # Returns
+///
+/// * `Vec<String>` - IDs of the commands whose processes were found dead, sorted
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const pruned = await invoke('prune_dead_processes');
+/// ```
+#[tauri::command]
+fn prune_dead_processes(state: State<ProcessManager>) -> Vec<String> {
+    let pruned = state.prune_dead();
+    if !pruned.is_empty() {
+        log::info!("Stopped tracking dead processes of {:?}", pruned);
+    }
+    pruned
+}
+
+/// Stops every tracked process and clears the process map.
+///
+/// Each process is stopped like a PID-based `kill_command`: its process group (tree on
+/// Windows) is force-killed, or, with a graceful `Config.kill_signal`, sent that signal
+/// first and force-killed if it outlives the grace period. Custom `kill_script`s are
+/// not run.
+/// Returns how many processes were stopped.
+fn cancel_all_processes(app_handle: &AppHandle) -> Result<usize, AppError> {
+    let state = app_handle.state::<ProcessManager>();
+    let running: Vec<(String, u32)> = state
+        .processes()
+        .iter()
+        .map(|(id, pid)| (id.clone(), *pid))
+        .collect();
+    if running.is_empty() {
+        return Ok(0);
+    }
+    for (command_id, pid) in &running {
+        log::info!("Killing process {} for command {}", pid, command_id);
+    }
+
+    let policy = get_config_path(app_handle)
+        .and_then(|path| store::get_config(&path))
+        .map(|config| config.kill_policy())
+        .unwrap_or(executor::KillPolicy::FORCE);
+    let pids: Vec<u32> = running.iter().map(|(_, pid)| *pid).collect();
+    // A process counts as exited once run_command_script has reaped it
+    let errors = executor::stop_all(&pids, policy, |pid| {
+        state.processes().values().any(|&p| p == pid)
+    });
+    state.processes().clear();
+
+    if errors.is_empty() {
+        Ok(running.len())
+    } else {
+        Err(AppError::Execution(errors.join("; ")))
+    }
+}
+
+/// Stops every running command when the panic shortcut is pressed.
+///
+/// Runs on its own thread, so a graceful kill's grace period doesn't hold up the
+/// shortcut handler. Safe mode doesn't apply, since nothing is started. Emits
+/// `shortcuts://panic` with how many processes were stopped.
+fn press_panic_button(app_handle: &AppHandle) {
+    let handle = app_handle.clone();
+    std::thread::spawn(move || {
+        log::warn!("Panic shortcut pressed; stopping every running command");
+        let stopped = cancel_all_processes(&handle).unwrap_or_else(|e| {
+            log::error!("Panic shortcut failed to stop some commands: {}", e);
+            0
+        });
+        if let Err(e) = handle.emit("shortcuts://panic", stopped) {
+            log::warn!("Failed to emit panic result: {}", e);
+        }
+    });
+}
+
+/// Kills every running command.
+///
+/// Meant for shutting everything down at once; the same happens automatically when
+/// the app exits, so no script outlives it. Each process is stopped like a PID-based
+/// `kill_command` without a `signal`, including `Config.kill_signal`, but custom
+/// `kill_script`s are not run. `detached` commands aren't tracked and keep running.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle
+///
+/// # Returns
+///
+/// * `Ok(usize)` - How many processes were stopped
+/// * `Err(AppError)` - Killing one or more processes failed; the others were still stopped
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const stopped = await invoke('cancel_all');
+/// console.log(`Stopped ${stopped} running commands`);
+/// ```
+#[tauri::command]
+async fn cancel_all(app_handle: AppHandle) -> Result<usize, AppError> {
+    // A graceful stop waits out the grace period, so keep it off the IPC thread
+    tauri::async_runtime::spawn_blocking(move || cancel_all_processes(&app_handle))
+        .await
+        .map_err(|e| AppError::Execution(format!("Failed to cancel commands: {}", e)))?
+}
+
+/// Resolves the commands file path: `CLIMGR_STORE_PATH` > `config.commands_path` > app data.
+fn get_store_path(app: &AppHandle) -> Result<PathBuf, AppError> {
+    let app_data_dir = get_app_data_dir(app)?;
+
+    // An unreadable config falls back to the default location
+    let config = get_config_path(app)
+        .ok()
+        .and_then(|path| store::get_config(&path).ok());
+
+    Ok(store::resolve_store_path(&app_data_dir, config.as_ref()))
+}
+
+/// The configured `max_script_length`, or the default when the config can't be read.
+fn script_length_limit(app: &AppHandle) -> usize {
+    get_config_path(app)
+        .and_then(|path| store::get_config(&path))
+        .map(|config| config.script_length_limit())
+        .unwrap_or(models::DEFAULT_MAX_SCRIPT_LENGTH)
+}
+
+/// The configured `panic_shortcut`, or none when the config can't be read.
+fn panic_shortcut(app: &AppHandle) -> Option<String> {
+    get_config_path(app)
+        .and_then(|path| store::get_config(&path))
+        .ok()
+        .and_then(|config| config.panic_shortcut)
+}
+
+/// The configured `reserved_shortcut_overrides`, or none when the config can't be read.
+fn shortcut_overrides(app: &AppHandle) -> Vec<String> {
+    get_config_path(app)
+        .and_then(|path| store::get_config(&path))
+        .ok()
+        .and_then(|config| config.reserved_shortcut_overrides)
+        .unwrap_or_default()
+}
+
+/// `Config.restrict_permissions`, on if the config can't be read.
+fn restrict_permissions(app: &AppHandle) -> bool {
+    get_config_path(app)
+        .and_then(|path| store::get_config(&path))
+        .map(|config| config.restrict_permissions())
+        .unwrap_or(true)
+}
+
+/// Fails with `ReadOnly` while `read_only` is set in the config.
+///
+/// Called first by every command that changes the store or trash. An unreadable config
+/// doesn't block writes.
+fn ensure_writable(app: &AppHandle) -> Result<(), AppError> {
+    match get_config_path(app).and_then(|path| store::get_config(&path)) {
+        Ok(config) => commands::ensure_writable(&config),
+        Err(_) => Ok(()),
+    }
+}
+
+/// The configured `commands_dir`, resolved like `commands_path`.
+fn get_commands_dir(app: &AppHandle) -> Option<PathBuf> {
+    let config = get_config_path(app)
+        .and_then(|path| store::get_config(&path))
+        .ok()?;
+    let dir = config.commands_dir?;
+    Some(store::resolve_commands_path(
+        &get_app_data_dir(app).ok()?,
+        &dir,
+    ))
+}
+
+/// Reads the store at `path`, merged with the files in `commands_dir` when it's set.
+///
+/// Remembers which file each included command came from, for [`save_store`], and logs
+/// the commands skipped for reusing an ID.
+fn load_store(app: &AppHandle, path: &Path) -> Result<Vec<Command>, AppError> {
+    let Some(dir) = get_commands_dir(app) else {
+        return store::get_commands(path);
+    };
+    let (commands, included) = store::load_merged(path, &dir)?;
+    for duplicate in &included.duplicates {
+        log::warn!("{}", duplicate.issue().message);
+    }
+    *app.state::<IncludedCommands>()
+        .included
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = included;
+    Ok(commands)
+}
+
+/// Saves the store, keeping scripts inline or in separate files per `scripts_external`,
+/// and owner-only per `restrict_permissions`.
+///
+/// With a `commands_dir`, commands loaded from one of its files are written back there
+/// and the rest go to `path`. See [`store_saved`] for what follows a save.
+fn save_store(app: &AppHandle, path: &Path, commands: &[Command]) -> Result<(), AppError> {
+    let config = get_config_path(app).and_then(|path| store::get_config(&path));
+    let options = config
+        .as_ref()
+        .map(|config| config.save_options())
+        .unwrap_or_default();
+    if config.is_ok_and(|config| config.commands_dir.is_some()) {
+        let state = app.state::<IncludedCommands>();
+        let included = state
+            .included
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        store::save_merged(path, commands, &included, options)?;
+    } else {
+        store::save_commands_with(path, commands, options)?;
+    }
+    store_saved(app, path);
+    Ok(())
+}
+
+/// Tells the watcher the store files now hold the app's own write, so it doesn't
+/// reload them and re-register every shortcut, and emits `store-changed` so every
+/// window refreshes its list. Callers re-register shortcuts themselves when a binding
+/// may have changed.
+fn store_saved(app: &AppHandle, path: &Path) {
+    watcher::note_store_write(app);
+    if let Err(e) = app.emit("store-changed", path.to_string_lossy().to_string()) {
+        log::warn!("Failed to emit store-changed: {}", e);
+    }
+}
+
+/// Saves the config and points the watcher at the paths it now resolves to, without
+/// the watcher treating the write as an outside edit.
+fn save_config(app: &AppHandle, path: &Path, config: &Config) -> Result<(), AppError> {
+    store::save_config(path, config)?;
+    watcher::note_own_write(app, path);
+    if let Err(e) = watcher::rewatch(app) {
+        log::error!("Failed to re-point file watcher: {}", e);
+    }
+    Ok(())
+}
+
+/// `CLIMGR_DATA_DIR` if set, otherwise the executable's directory in `portable` builds
+/// and the platform app data directory in regular ones.
+fn get_app_data_dir(app: &AppHandle) -> Result<PathBuf, AppError> {
+    store::resolve_data_dir(|| {
+        if cfg!(feature = "portable") {
+            return store::portable_data_dir();
+        }
+        app.path()
+            .app_data_dir()
+            .map_err(|e| AppError::Io(format!("Failed to get app data dir: {}", e)))
+    })
+}
+
+/// `config.toml` in the app data directory if it exists, `config.json` otherwise.
+fn get_config_path(app: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(store::resolve_config_path(&get_app_data_dir(app)?))
+}
+
+fn get_history_path(app: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(get_app_data_dir(app)?.join("history.json"))
+}
+
+fn get_last_command_path(app: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(get_app_data_dir(app)?.join("last_command"))
+}
+
+/// Best-effort: a history write failure never fails the execution itself.
+fn record_history(app_handle: &AppHandle, entry: HistoryEntry) {
+    let restrict = restrict_permissions(app_handle);
+    let result =
+        get_history_path(app_handle).and_then(|path| history::append_entry(&path, entry, restrict));
+    if let Err(e) = result {
+        log::warn!("Failed to record execution history: {}", e);
+    }
+}
+
+/// Updates the stored command's run metadata after an execution, except in
+/// `read_only` mode, where the store is left untouched.
+///
+/// The store is re-read here so edits made while the script was running are kept.
+fn record_run(app_handle: &AppHandle, entry: &HistoryEntry) {
+    let result = get_store_path(app_handle).and_then(|path| {
+        let config = get_config_path(app_handle)
+            .and_then(|path| store::get_config(&path))
+            .unwrap_or_default();
+        // Only the file holding the command is touched, under its lock from read to
+        // write, so runs finishing together don't overwrite each other's counts
+        let file = {
+            let state = app_handle.state::<IncludedCommands>();
+            let included = state
+                .included
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            included.files.get(&entry.command_id).cloned()
+        };
+        let file = file.unwrap_or_else(|| path.clone());
+        let recorded = commands::record_run_in_store(
+            &file,
+            &config,
+            &entry.command_id,
+            &entry.started_at,
+            entry.exit_code,
+        )?;
+        if recorded {
+            store_saved(app_handle, &path);
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::warn!(
+            "Failed to record run for command {}: {}",
+            entry.command_id,
+            e
+        );
+    }
+}
+
+/// Re-registers `Config.panic_shortcut` and every command's global shortcut.
+///
+/// The panic shortcut is always registered first, and a command bound to the same keys
+/// is skipped. Registration is best effort: shortcuts that fail are logged, returned,
+/// and emitted as a `shortcuts://errors` event (an empty list once everything binds) so
+/// the UI can flag the offending commands.
+fn refresh_shortcuts(app_handle: &tauri::AppHandle) -> Result<Vec<ShortcutError>, AppError> {
+    app_handle
+        .global_shortcut()
+        .unregister_all()
+        .map_err(|e| AppError::Other(e.to_string()))?;
+
+    let path = get_store_path(app_handle)?;
+    // Ignore errors reading store, maybe empty
+    let commands = load_store(app_handle, &path).unwrap_or_default();
+    let panic_shortcut = panic_shortcut(app_handle);
+    // Cmd-style aliases map to Ctrl off macOS
+    let errors = shortcuts::register_all(&commands, panic_shortcut.as_deref(), |shortcut| {
+        app_handle
+            .global_shortcut()
+            .register(shortcut)
+            .map_err(|e| e.to_string())
+    });
+    for error in &errors {
+        log::error!(
+            "Failed to register shortcut '{}' of {}: {}",
+            error.shortcut,
+            error.command_id,
+            error.message
+        );
+    }
+    if let Err(e) = app_handle.emit("shortcuts://errors", &errors) {
+        log::warn!("Failed to emit shortcut errors: {}", e);
+    }
+    Ok(errors)
+}
+
+/// Re-registers shortcuts from the current store and tells the UI to reload it.
+///
+/// Emits `store-changed` with the resolved store path as payload.
+fn reload_store_and_notify(app_handle: &AppHandle) -> Result<(), AppError> {
+    refresh_shortcuts(app_handle)?;
+    let path = get_store_path(app_handle)?;
+    app_handle
+        .emit("store-changed", path.to_string_lossy().to_string())
+        .map_err(|e| AppError::Other(format!("Failed to emit store-changed: {}", e)))
+}
+
+/// Reloads the command store from disk.
+///
+/// Use this after editing `commands.json` by hand. Global shortcuts are re-registered
+/// and a `store-changed` event is emitted so every window refreshes its list. The
+/// file watcher calls the same routine automatically when it sees the file change.
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+/// import { listen } from '@tauri-apps/api/event';
+///
+/// await listen('store-changed', () => loadCommands());
+/// await invoke('reload_store');
+/// ```
+#[tauri::command]
+fn reload_store(app_handle: tauri::AppHandle) -> Result<(), AppError> {
+    reload_store_and_notify(&app_handle)
+}
+
+/// Retrieves all stored commands.
+///
+/// This Tauri command fetches all commands from persistent storage.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+///
+/// # Returns
+///
+/// * `Ok(Vec<Command>)` - A vector containing all stored commands
+/// * `Err(AppError)` - Error message if:
+///   - Failed to access app data directory
+///   - Failed to read from storage file
+///   - JSON parsing failed
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const commands = await invoke('get_commands');
+/// console.log('All commands:', commands);
+/// ```
+#[tauri::command]
+fn get_commands(app_handle: tauri::AppHandle) -> Result<Vec<Command>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    load_store(&app_handle, &path)
+}
+
+/// Retrieves a single command by its ID.
+///
+/// Use this for detail views instead of fetching the whole list with `get_commands`.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The ID of the command to fetch
+///
+/// # Returns
+///
+/// * `Ok(Command)` - The matching command
+/// * `Err(AppError)` - `"Command not found"`, or an error reading the store
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const command = await invoke('get_command', { id: commandId });
+/// ```
+#[tauri::command]
+fn get_command(app_handle: tauri::AppHandle, id: String) -> Result<Command, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    commands::find(&commands, &id).cloned()
+}
+
+/// Shows a command's script with the environment variables it runs with listed above it.
+///
+/// The variables come from the config's `profile_env` and the command's own `env`; see
+/// [`template::annotate_env`] for the format. Users can use it to audit what is injected.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The ID of the command
+///
+/// # Returns
+///
+/// * `Ok(String)` - The annotated script
+/// * `Err(AppError)` - `"Command not found"`, or an error reading the store or config
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const annotated = await invoke('resolve_script', { id: commandId });
+/// ```
+#[tauri::command]
+fn resolve_script(app_handle: tauri::AppHandle, id: String) -> Result<String, AppError> {
+    let commands = load_store(&app_handle, &get_store_path(&app_handle)?)?;
+    let command = commands::find(&commands, &id)?;
+    let config = store::get_config(&get_config_path(&app_handle)?)?;
+    let profile_env = config.profile_env.unwrap_or_default();
+    Ok(template::annotate_env(command, &profile_env))
+}
+
+/// Turns a command into a standalone shell script, e.g. for sharing or running it
+/// outside climgr.
+///
+/// Template variables are filled in as for a run, and the command's shell settings are
+/// resolved against the config. See [`template::shell_script`] for the layout; values
+/// from `profile_env` are left out, since they may be secrets.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The ID of the command
+/// * `params` - Values for the command's `{{name}}` template variables
+///
+/// # Returns
+///
+/// * `Ok(String)` - The script, starting with a `#!/bin/sh` line
+/// * `Err(AppError)` - Error message if:
+///   - The command doesn't exist (`"Command not found"`)
+///   - It is a macro, which has no script of its own
+///   - A required template variable has no value
+///   - The store or config couldn't be read
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const script = await invoke('command_to_script', { id: commandId });
+/// ```
+#[tauri::command]
+fn command_to_script(
+    app_handle: tauri::AppHandle,
+    id: String,
+    params: Option<HashMap<String, String>>,
+) -> Result<String, AppError> {
+    let commands = load_store(&app_handle, &get_store_path(&app_handle)?)?;
+    let command = commands::find(&commands, &id)?;
+    if command.is_macro() {
+        return Err(AppError::Invalid(format!(
+            "'{}' is a macro, which runs other commands instead of a script",
+            command.name
+        )));
+    }
+    let config = store::get_config(&get_config_path(&app_handle)?)?;
+    let mut rendered = render_command(&app_handle, command, &config, params.unwrap_or_default())?;
+    rendered.login_shell = Some(config.login_shell(&rendered));
+    rendered.interactive_shell = Some(config.interactive_shell(&rendered));
+    let profile_env = config.profile_env.unwrap_or_default();
+    Ok(template::shell_script(&rendered, &profile_env))
+}
+
+/// Retrieves the variables a command declares, so the UI can render a form for them.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The ID of the command
+///
+/// # Returns
+///
+/// * `Ok(Vec<CommandVariable>)` - `{ name, default, description }` for each declared
+///   variable, in order; empty if it declares none
+/// * `Err(AppError)` - `"Command not found"`, or an error reading the store
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const variables = await invoke('get_command_variables', { id: commandId });
+/// const params = Object.fromEntries(variables.map((v) => [v.name, v.default ?? '']));
+/// await invoke('execute_command', { commandId, params });
+/// ```
+#[tauri::command]
+fn get_command_variables(
+    app_handle: tauri::AppHandle,
+    id: String,
+) -> Result<Vec<CommandVariable>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    Ok(commands::find(&commands, &id)?
+        .variables
+        .clone()
+        .unwrap_or_default())
+}
+
+/// Retrieves all commands bucketed by their `group`.
+///
+/// Storage stays a flat list; this only gives the UI structure. Commands without a
+/// group are returned under `"Ungrouped"`, and stored order is kept within each group.
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const groups = await invoke('get_grouped_commands');
+/// console.log(groups['Docker'], groups['Ungrouped']);
+/// ```
+#[tauri::command]
+fn get_grouped_commands(
+    app_handle: tauri::AppHandle,
+) -> Result<HashMap<String, Vec<Command>>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    Ok(models::group_commands(&commands))
+}
+
+/// Retrieves only the favorited commands, in stored order.
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const pinned = await invoke('get_favorites');
+/// ```
+#[tauri::command]
+fn get_favorites(app_handle: tauri::AppHandle) -> Result<Vec<Command>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    Ok(commands::favorites(&commands))
+}
+
+/// Lists the commands lacking a field, for data hygiene, e.g. finding commands without
+/// a description.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `field` - One of `description`, `notes`, `group`, `shortcut` or `icon`
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` - IDs of the commands where the field is unset or blank, in
+///   stored order
+/// * `Err(AppError)` - `Invalid` for an unknown field, or an error reading the store
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const undocumented = await invoke('get_commands_missing_field', { field: 'description' });
+/// ```
+#[tauri::command]
+fn get_commands_missing_field(
+    app_handle: tauri::AppHandle,
+    field: String,
+) -> Result<Vec<String>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    commands::missing_field(&commands, &field)
+}
+
+/// Pins or unpins a command for quick access.
+///
+/// Flips the command's `favorite` flag and persists it, without needing a full
+/// `update_command` round-trip.
+///
+/// # Returns
+///
+/// * `Ok(bool)` - The new favorite state
+/// * `Err(AppError)` - Error message if the command is not found or storage failed
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const isFavorite = await invoke('toggle_favorite', { id: command.id });
+/// ```
+#[tauri::command]
+fn toggle_favorite(app_handle: tauri::AppHandle, id: String) -> Result<bool, AppError> {
+    ensure_writable(&app_handle)?;
+    let path = get_store_path(&app_handle)?;
+    let mut commands = load_store(&app_handle, &path)?;
+    let favorite = commands::toggle_favorite(&mut commands, &id)?;
+    save_store(&app_handle, &path, &commands)?;
+    Ok(favorite)
+}
+
+/// Moves a command to a new position in the list, for single-step up/down buttons.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The ID of the command to move
+/// * `to_index` - Target position; values past the end move the command to the end
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` - Command IDs in their new order
+/// * `Err(AppError)` - `"Command not found"`, or a storage error
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const order = await invoke('move_command', { id: command.id, toIndex: index - 1 });
+/// ```
+#[tauri::command]
+fn move_command(
+    app_handle: tauri::AppHandle,
+    id: String,
+    to_index: usize,
+) -> Result<Vec<String>, AppError> {
+    ensure_writable(&app_handle)?;
+    let path = get_store_path(&app_handle)?;
+    let mut commands = load_store(&app_handle, &path)?;
+    commands::move_to(&mut commands, &id, to_index)?;
+    save_store(&app_handle, &path, &commands)?;
+    Ok(commands.into_iter().map(|c| c.id).collect())
+}
+
+/// Pauses or resumes a command's `schedule` without removing it.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The ID of a command that has a `schedule`
+/// * `enabled` - `false` to pause, `true` to resume
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// await invoke('set_schedule_enabled', { id: command.id, enabled: false });
+/// ```
+#[tauri::command]
+fn set_schedule_enabled(
+    app_handle: tauri::AppHandle,
+    id: String,
+    enabled: bool,
+) -> Result<(), AppError> {
+    ensure_writable(&app_handle)?;
+    let path = get_store_path(&app_handle)?;
+    let mut commands = load_store(&app_handle, &path)?;
+    commands::set_schedule_enabled(&mut commands, &id, enabled)?;
+    save_store(&app_handle, &path, &commands)
+}
+
+/// Lists the next run time of every active schedule, soonest first.
+///
+/// Schedules are picked up by the scheduler within a few seconds of being saved, so a
+/// just-added schedule may not be listed yet.
+///
+/// # Returns
+///
+/// * `Vec<ScheduledRun>` - `{ command_id, schedule, next_run_at }` entries
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const runs = await invoke('get_scheduled_runs');
+/// const next = runs.find((r) => r.command_id === command.id)?.next_run_at;
+/// ```
+#[tauri::command]
+fn get_scheduled_runs(scheduler: State<schedule::Scheduler>) -> Vec<schedule::ScheduledRun> {
+    scheduler.upcoming()
+}
+
+/// Reports CPU and memory usage of a running command.
+///
+/// Usage covers the command's shell and everything it spawned. CPU usage is measured
+/// since the previous call, so poll this at a steady interval for a live indicator.
+///
+/// # Arguments
+///
+/// * `command_id` - The ID of the command to sample
+///
+/// # Returns
+///
+/// * `Some(ProcessStats)` - `{ cpu_percent, memory_bytes }` of the running command
+/// * `None` - The command isn't running
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const stats = await invoke('get_process_stats', { commandId: command.id });
+/// if (stats) console.log(`${stats.cpu_percent.toFixed(1)}% CPU`);
+/// ```
+#[tauri::command]
+fn get_process_stats(
+    command_id: String,
+    process_manager: State<ProcessManager>,
+    monitor: State<stats::ProcessMonitor>,
+) -> Option<stats::ProcessStats> {
+    let pid = *process_manager.processes().get(&command_id)?;
+    monitor.sample(pid)
+}
+
+/// Reports whether a command is running and whether its service is healthy.
+///
+/// `running` reflects the processes climgr is tracking. If the command has a
+/// `status_script`, it is run (for at most 10 seconds) and exit 0 is reported as
+/// `healthy: true`, anything else as `false`. In safe mode the script isn't run and
+/// `healthy` is `null`.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The ID of the command to check
+///
+/// # Returns
+///
+/// * `Ok(CommandStatus)` - `{ running, healthy }`
+/// * `Err(AppError)` - The command doesn't exist, the store couldn't be read, or the
+///   status script couldn't be started
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const { running, healthy } = await invoke('check_command_status', { id: command.id });
+/// ```
+#[tauri::command]
+async fn check_command_status(
+    app_handle: tauri::AppHandle,
+    id: String,
+) -> Result<CommandStatus, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    let command = commands::find(&commands, &id)?;
+    let running = app_handle
+        .state::<ProcessManager>()
+        .processes()
+        .contains_key(&id);
+
+    let Some(script) = command
+        .status_script
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+    else {
+        return Ok(CommandStatus {
+            running,
+            healthy: None,
+        });
+    };
+    if store::get_config(&get_config_path(&app_handle)?)?.safe_mode {
+        log::info!("Safe mode is on; skipping status script of {}", id);
+        return Ok(CommandStatus {
+            running,
+            healthy: None,
+        });
+    }
+
+    log::info!("Running status script for command {}", id);
+    let healthy = executor::run_status_check(script, executor::STATUS_CHECK_TIMEOUT)
+        .map_err(AppError::Execution)?;
+    Ok(CommandStatus {
+        running,
+        healthy: Some(healthy),
+    })
+}
+
+/// Checks the stored commands for problems, e.g. after a manual edit or an import.
+///
+/// # Returns
+///
+/// * `Ok(Vec<StoreIssue>)` - `{ kind, command_id, message }` for every issue found;
+///   `kind` is `duplicate_id`, `empty_script`, `duplicate_shortcut`, `invalid_shortcut`
+///   or `unreadable`, for an entry that couldn't be parsed and is left out of the list.
+///   Unreadable entries come first; their `command_id` is empty if they have no ID
+/// * `Err(AppError)` - Error message if the store can't be read
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const issues = await invoke('verify_store');
+/// issues.forEach((issue) => console.warn(issue.message));
+/// ```
+#[tauri::command]
+fn verify_store(app_handle: tauri::AppHandle) -> Result<Vec<models::StoreIssue>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let (_, unreadable) = store::load_commands(&path)?;
+    let commands = load_store(&app_handle, &path)?;
+    let mut issues: Vec<models::StoreIssue> = unreadable.iter().map(|u| u.issue()).collect();
+    if get_commands_dir(&app_handle).is_some() {
+        let state = app_handle.state::<IncludedCommands>();
+        let included = state
+            .included
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        issues.extend(included.duplicates.iter().map(|d| d.issue()));
+    }
+    issues.extend(commands::verify(&commands));
+    Ok(issues)
+}
+
+/// Searches stored commands by case-insensitive substring.
+///
+/// Matches against the command name, description and script. An empty query
+/// returns every command.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `query` - The text to search for
+///
+/// # Returns
+///
+/// * `Ok(Vec<Command>)` - Matching commands in stored order
+/// * `Err(AppError)` - Error message if the commands could not be read
+#[tauri::command]
+fn search_commands(app_handle: tauri::AppHandle, query: String) -> Result<Vec<Command>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    Ok(search::search_commands(&commands, &query))
+}
+
+/// Fuzzy-searches stored command names for the command palette.
+///
+/// Tolerates abbreviations and a single typo. Results are sorted by descending
+/// score; if nothing scores, falls back to substring search with a score of 0.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `query` - The text to match against command names
+///
+/// # Returns
+///
+/// * `Ok(Vec<ScoredCommand>)` - Matching commands with their scores
+/// * `Err(AppError)` - Error message if the commands could not be read
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const results = await invoke('fuzzy_search_commands', { query: 'grc' });
+/// results.forEach(({ command, score }) => console.log(score, command.name));
+/// ```
+#[tauri::command]
+fn fuzzy_search_commands(
+    app_handle: tauri::AppHandle,
+    query: String,
+) -> Result<Vec<ScoredCommand>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    let commands = load_store(&app_handle, &path)?;
+    Ok(search::fuzzy_search_commands(&commands, &query))
+}
+
+/// Adds a new command to storage.
+///
+/// This Tauri command creates a new command entry and updates the global shortcuts.
+/// `created_at` and `updated_at` are set to the current time, and the shortcut is
+/// stored in one spelling (`shift+control+KeyL` for `Ctrl+Shift+L`, `cmd+KeyL` for
+/// `Cmd+L`).
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `command` - The command object to add (must have a unique ID)
+///
+/// # Returns
+///
+/// * `Ok(SaveReport)` - Command was added and shortcuts refreshed. `shortcut_errors`
+///   lists shortcuts (this command's or another's) that failed to register, `warnings`
+///   advisory findings such as a `kill_script` that could stop unrelated processes
+/// * `Err(AppError)` - Error message if:
+///   - The command fails validation (e.g. a malformed `color`, or a script that is
+///     over `max_script_length` or contains null bytes)
+///   - The shortcut is already bound to another command (compared case- and
+///     alias-insensitively, so `cmd+l` clashes with `Cmd+L`)
+///   - The shortcut is reserved by the OS (like `Cmd+Q`) and not listed in
+///     `reserved_shortcut_overrides`
+///   - Failed to access app data directory
+///   - Failed to read existing commands
+///   - Failed to save updated commands
+///   - Failed to refresh global shortcuts
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const newCommand = {
+///   id: crypto.randomUUID(),
+///   name: 'List Files',
+///   script: 'ls -la',
+///   description: 'List all files',
+///   shortcut: 'Ctrl+L'
+/// };
+///
+/// const { shortcut_errors, warnings } = await invoke('add_command', { command: newCommand });
+/// ```
+///
+/// # Note
+///
+/// Unlike `add_commands`, this does not check for duplicate IDs. Ensure the ID is
+/// unique before calling, or add it through `add_commands` to have it checked.
+#[tauri::command]
+fn add_command(app_handle: tauri::AppHandle, mut command: Command) -> Result<SaveReport, AppError> {
+    ensure_writable(&app_handle)?;
+    commands::validate(&command, script_length_limit(&app_handle))?;
+    commands::stamp_created(&mut command, &chrono::Utc::now().to_rfc3339());
+    let path = get_store_path(&app_handle)?;
+    let mut commands = load_store(&app_handle, &path)?;
+    shortcuts::check_available(&commands, &command)?;
+    shortcuts::check_not_reserved(&command, &shortcut_overrides(&app_handle))?;
+    let warnings = save_warnings(&command);
+    shortcuts::canonicalize(&mut command);
+    commands.push(command);
+    save_store(&app_handle, &path, &commands)?;
+    Ok(SaveReport {
+        shortcut_errors: refresh_shortcuts(&app_handle)?,
+        warnings,
+    })
+}
+
+/// Advisory findings about a command being saved, logged as they are returned.
+fn save_warnings(command: &Command) -> Vec<String> {
+    let warnings = commands::kill_script_warnings(command);
+    for warning in &warnings {
+        log::warn!("Kill script of {}: {}", command.id, warning);
+    }
+    warnings
+}
+
+/// [`save_warnings`] for several saved commands, each prefixed with the command's name.
+fn bulk_save_warnings(saved: &[Command]) -> Vec<String> {
+    saved
+        .iter()
+        .flat_map(|command| {
+            save_warnings(command)
+                .into_iter()
+                .map(move |warning| format!("{}: {}", command.name, warning))
+        })
+        .collect()
+}
+
+/// Adds several commands at once with all-or-nothing semantics.
+///
+/// Every command is validated (non-empty IDs, unique within the batch and against the
+/// existing store; shortcuts neither reserved nor already bound, as in `add_command`)
+/// before anything is written. If any check fails, nothing is persisted
+/// and the error identifies the offending entry. Shortcuts are refreshed once at the end,
+/// which makes this the right call for import flows.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `commands` - The commands to add
+///
+/// # Returns
+///
+/// * `Ok(BulkSaveReport)` - `{ added, shortcut_errors, warnings }`: the number of
+///   commands added, plus what `add_command` reports, with each warning prefixed by
+///   its command's name
+/// * `Err(AppError)` - Validation or storage error; the store is unchanged
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const { added, warnings } = await invoke('add_commands', { commands: imported });
+/// ```
+#[tauri::command]
+fn add_commands(
+    app_handle: tauri::AppHandle,
+    mut commands: Vec<Command>,
+) -> Result<models::BulkSaveReport, AppError> {
+    ensure_writable(&app_handle)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    for command in &mut commands {
+        commands::stamp_created(command, &now);
+    }
+    let path = get_store_path(&app_handle)?;
+    let mut stored = load_store(&app_handle, &path)?;
+    let added = commands::add_many(
+        &mut stored,
+        commands,
+        script_length_limit(&app_handle),
+        &shortcut_overrides(&app_handle),
+    )?;
+    save_store(&app_handle, &path, &stored)?;
+    Ok(models::BulkSaveReport {
+        added,
+        report: SaveReport {
+            shortcut_errors: refresh_shortcuts(&app_handle)?,
+            warnings: bulk_save_warnings(&stored[stored.len() - added..]),
+        },
+    })
+}
+
+/// Imports commands from a YAML list, e.g. one produced by `export_commands_yaml`.
+///
+/// The import goes through the same checks as `add_commands` and is all-or-nothing:
+/// invalid YAML or any invalid command leaves the store untouched.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `yaml` - A YAML sequence of commands
+///
+/// # Returns
+///
+/// * `Ok(BulkSaveReport)` - The number of commands imported and their findings, as
+///   returned by `add_commands`
+/// * `Err(AppError)` - `"Invalid YAML: ..."`, a validation error, or a storage error
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const { added } = await invoke('import_commands_yaml', { yaml: fileContents });
+/// ```
+#[tauri::command]
+fn import_commands_yaml(
+    app_handle: tauri::AppHandle,
+    yaml: String,
+) -> Result<models::BulkSaveReport, AppError> {
+    let commands = store::commands_from_yaml(&yaml)?;
+    add_commands(app_handle, commands)
+}
+
+/// Creates commands from the `alias name='cmd'` lines of a shell aliases file.
+///
+/// Each alias becomes a command named after it, with the alias value as its script,
+/// in the "Aliases" group. Lines that aren't a well-formed alias are skipped, as are
+/// aliases imported before, so importing the same file again only adds new ones. The
+/// commands that were created go through the same checks as `add_commands`.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `path` - The aliases file, e.g. `~/.bash_aliases`; `~` is expanded
+///
+/// # Returns
+///
+/// * `Ok(AliasImportSummary)` - `{ imported, skipped, shortcut_errors, warnings }`: the
+///   names of the new commands, each skipped line with its number and the reason, and
+///   the findings `add_commands` reports
+/// * `Err(AppError)` - The file can't be read, an alias fails validation, or the store
+///   can't be saved; nothing is imported then
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const { imported, skipped } = await invoke('import_aliases', { path: '~/.bash_aliases' });
+/// ```
+#[tauri::command]
+fn import_aliases(
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<models::AliasImportSummary, AppError> {
+    ensure_writable(&app_handle)?;
+    let file = PathBuf::from(store::expand_path(&path));
+    let text = std::fs::read_to_string(&file)
+        .map_err(|e| AppError::Io(format!("Failed to read {:?}: {}", file, e)))?;
+
+    let store_path = get_store_path(&app_handle)?;
+    let mut stored = load_store(&app_handle, &store_path)?;
+    let (mut imported, skipped) = aliases::import(&text, &stored);
+    let now = chrono::Utc::now().to_rfc3339();
+    for command in &mut imported {
+        commands::stamp_created(command, &now);
+    }
+    let names = imported.iter().map(|c| c.name.clone()).collect();
+
+    let mut report = SaveReport::default();
+    if !imported.is_empty() {
+        let added = commands::add_many(
+            &mut stored,
+            imported,
+            script_length_limit(&app_handle),
+            &shortcut_overrides(&app_handle),
+        )?;
+        save_store(&app_handle, &store_path, &stored)?;
+        report.shortcut_errors = refresh_shortcuts(&app_handle)?;
+        report.warnings = bulk_save_warnings(&stored[stored.len() - added..]);
+    }
+    Ok(models::AliasImportSummary {
+        imported: names,
+        skipped,
+        report,
+    })
+}
+
+/// Exports all commands as YAML.
+///
+/// Multi-line scripts are written as block scalars and unset fields are omitted, which
+/// makes the result pleasant to edit by hand and re-import.
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const yaml = await invoke('export_commands_yaml');
+/// ```
+#[tauri::command]
+fn export_commands_yaml(app_handle: tauri::AppHandle) -> Result<String, AppError> {
+    let path = get_store_path(&app_handle)?;
+    store::commands_to_yaml(&load_store(&app_handle, &path)?)
+}
+
+/// Updates an existing command.
+///
+/// This Tauri command finds a command by its ID and updates it with new values,
+/// then refreshes global shortcuts. Server-owned fields (`created_at`, `run_count`,
+/// `last_run_at`, `last_exit_code`) are merged from the stored entry, so the frontend
+/// may omit them, and `updated_at` is set to the current time.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `command` - The updated command object (ID must match an existing command)
+///
+/// # Returns
+///
+/// * `Ok(SaveReport)` - Command was updated and shortcuts refreshed. `shortcut_errors`
+///   lists shortcuts (this command's or another's) that failed to register, `warnings`
+///   advisory findings such as a `kill_script` that could stop unrelated processes
+/// * `Err(AppError)` - Error message if:
+///   - Command with the given ID not found
+///   - Command is locked
+///   - The command fails validation (e.g. a malformed `color`, or a script that is
+///     over `max_script_length` or contains null bytes)
+///   - The shortcut is already bound to another command (compared case- and
+///     alias-insensitively, so `cmd+l` clashes with `Cmd+L`)
+///   - The shortcut is reserved by the OS (like `Cmd+Q`) and not listed in
+///     `reserved_shortcut_overrides`
+///   - Failed to access app data directory
+///   - Failed to read existing commands
+///   - Failed to save updated commands
+///   - Failed to refresh global shortcuts
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const updatedCommand = {
+///   id: '123e4567-e89b-12d3-a456-426614174000',
+///   name: 'List Files (Updated)',
+///   script: 'ls -lah',
+///   description: 'List all files with human-readable sizes',
+///   shortcut: 'Cmd+L'
+/// };
+///
+/// const { shortcut_errors, warnings } = await invoke('update_command', {
+///   command: updatedCommand
+/// });
+/// ```
+#[tauri::command]
+fn update_command(
+    app_handle: tauri::AppHandle,
+    mut command: Command,
+) -> Result<SaveReport, AppError> {
+    ensure_writable(&app_handle)?;
+    commands::validate(&command, script_length_limit(&app_handle))?;
+    let path = get_store_path(&app_handle)?;
+    let mut commands = load_store(&app_handle, &path)?;
+    shortcuts::check_available(&commands, &command)?;
+    shortcuts::check_not_reserved(&command, &shortcut_overrides(&app_handle))?;
+    let warnings = save_warnings(&command);
+    shortcuts::canonicalize(&mut command);
+    commands::update(&mut commands, command, &chrono::Utc::now().to_rfc3339())?;
+    save_store(&app_handle, &path, &commands)?;
+    Ok(SaveReport {
+        shortcut_errors: refresh_shortcuts(&app_handle)?,
+        warnings,
+    })
+}
+
+/// Deletes a command by its ID, moving it to the trash.
+///
+/// This Tauri command removes a command from storage and updates global shortcuts.
+/// The command is kept in `trash/trashed_commands.json` next to the store, with a
+/// `deleted_at` timestamp, until it is restored or the trash is emptied.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The unique identifier of the command to delete
+///
+/// # Returns
+///
+/// * `Ok(SaveReport)` - Command was deleted; lists shortcuts that failed to register and
+///   warns about macros that still run the deleted command
+/// * `Err(AppError)` - Error message if:
+///   - Command is locked
+///   - Failed to access app data directory
+///   - Failed to read existing commands
+///   - Failed to save updated commands or the trash
+///   - Failed to refresh global shortcuts
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const { warnings } = await invoke('delete_command', {
+///   id: '123e4567-e89b-12d3-a456-426614174000'
+/// });
+/// ```
+///
+/// # Note
+///
+/// If the command ID doesn't exist, this function still succeeds (idempotent operation).
+/// Locked commands can only be removed by editing the JSON directly.
+#[tauri::command]
+fn delete_command(app_handle: tauri::AppHandle, id: String) -> Result<SaveReport, AppError> {
+    ensure_writable(&app_handle)?;
+    let path = get_store_path(&app_handle)?;
+    let mut commands = load_store(&app_handle, &path)?;
+    let mut warnings = Vec::new();
+    if let Some(removed) = commands::remove(&mut commands, &id)? {
+        warnings = commands::removal_warnings(&commands, &id);
+        for warning in &warnings {
+            log::warn!("Deleted {}: {}", id, warning);
+        }
+        // Write the trash first so a failed store write never loses the command
+        let trash_path = store::trash_path_for(&path);
+        let mut trash = store::get_trash(&trash_path)?;
+        trash.push(TrashedCommand {
+            command: removed,
+            deleted_at: chrono::Utc::now().to_rfc3339(),
+        });
+        store::save_trash(&trash_path, &trash, restrict_permissions(&app_handle))?;
+    }
+    save_store(&app_handle, &path, &commands)?;
+    Ok(SaveReport {
+        shortcut_errors: refresh_shortcuts(&app_handle)?,
+        warnings,
+    })
+}
+
+/// Deletes several commands at once, moving them to the trash.
+///
+/// All matching IDs are removed in a single store read/write followed by one shortcut
+/// refresh, which is much faster than calling `delete_command` repeatedly. Unknown IDs
+/// are ignored; if any matching command is locked, nothing is deleted. Macros that
+/// still run a deleted command are logged as warnings.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `ids` - IDs of the commands to delete
+///
+/// # Returns
+///
+/// * `Ok(usize)` - Number of commands actually removed
+/// * `Err(AppError)` - Error message if a command is locked or storage failed
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const removed = await invoke('delete_commands', { ids: selectedIds });
+/// ```
+#[tauri::command]
+fn delete_commands(app_handle: tauri::AppHandle, ids: Vec<String>) -> Result<usize, AppError> {
+    ensure_writable(&app_handle)?;
+    let path = get_store_path(&app_handle)?;
+    let mut commands = load_store(&app_handle, &path)?;
+    let removed = commands::remove_many(&mut commands, &ids)?;
+    if removed.is_empty() {
+        return Ok(0);
+    }
+    for command in &removed {
+        for warning in commands::removal_warnings(&commands, &command.id) {
+            log::warn!("Deleted {}: {}", command.id, warning);
+        }
+    }
+
+    let trash_path = store::trash_path_for(&path);
+    let mut trash = store::get_trash(&trash_path)?;
+    let deleted_at = chrono::Utc::now().to_rfc3339();
+    let count = removed.len();
+    trash.extend(removed.into_iter().map(|command| TrashedCommand {
+        command,
+        deleted_at: deleted_at.clone(),
+    }));
+    store::save_trash(&trash_path, &trash, restrict_permissions(&app_handle))?;
+
+    save_store(&app_handle, &path, &commands)?;
+    refresh_shortcuts(&app_handle)?;
+    Ok(count)
+}
+
+/// Applies several add, update and delete operations in one call.
+///
+/// The ops run in order against a single store read, with one write (and one trash
+/// write for deletes) and one shortcut refresh at the end, so a sync doesn't cost a
+/// round-trip per command. An op that fails its checks is skipped and reported while
+/// the others still apply; nothing is written if no op applied.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `ops` - Operations tagged by `op`: `add` and `update` carry a `command`, `delete`
+///   an `id`
+///
+/// # Returns
+///
+/// * `Ok(Vec<BatchOpResult>)` - `{ ok, error, warnings }` for each op, in order;
+///   `warnings` are the kill-script warnings `add_command` and `update_command` return
+/// * `Err(AppError)` - Error message if the store couldn't be read or written
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const results = await invoke('batch', {
+///   ops: [
+///     { op: 'add', command: newCommand },
+///     { op: 'update', command: changedCommand },
+///     { op: 'delete', id: staleId },
+///   ],
+/// });
+/// ```
+#[tauri::command]
+fn batch(app_handle: tauri::AppHandle, ops: Vec<BatchOp>) -> Result<Vec<BatchOpResult>, AppError> {
+    ensure_writable(&app_handle)?;
+    let path = get_store_path(&app_handle)?;
+    let mut commands = load_store(&app_handle, &path)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let (results, removed) = commands::apply_batch(
+        &mut commands,
+        ops,
+        script_length_limit(&app_handle),
+        &shortcut_overrides(&app_handle),
+        &now,
+    );
+    if !results.iter().any(|result| result.ok) {
+        return Ok(results);
+    }
+    for (index, result) in results.iter().enumerate() {
+        for warning in &result.warnings {
+            log::warn!("Kill script of batch op {}: {}", index, warning);
+        }
+    }
+
+    if !removed.is_empty() {
+        let trash_path = store::trash_path_for(&path);
+        let mut trash = store::get_trash(&trash_path)?;
+        trash.extend(removed.into_iter().map(|command| TrashedCommand {
+            command,
+            deleted_at: now.clone(),
+        }));
+        store::save_trash(&trash_path, &trash, restrict_permissions(&app_handle))?;
+    }
+
+    save_store(&app_handle, &path, &commands)?;
+    refresh_shortcuts(&app_handle)?;
+    Ok(results)
+}
+
+/// Lists the commands currently in the trash, oldest deletion first.
+#[tauri::command]
+fn get_trash(app_handle: tauri::AppHandle) -> Result<Vec<TrashedCommand>, AppError> {
+    let path = get_store_path(&app_handle)?;
+    store::get_trash(&store::trash_path_for(&path))
+}
+
+/// Restores a trashed command to the store and re-registers its shortcut.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `id` - The ID of the trashed command
+///
+/// # Returns
+///
+/// * `Ok(Command)` - The restored command
+/// * `Err(AppError)` - Error message if the command isn't in the trash, a command with
+///   the same ID already exists, its shortcut is now bound to another command or
+///   reserved, or storage could not be read/written
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const command = await invoke('restore_command', { id: deletedId });
+/// ```
+#[tauri::command]
+fn restore_command(app_handle: tauri::AppHandle, id: String) -> Result<Command, AppError> {
+    ensure_writable(&app_handle)?;
+    let path = get_store_path(&app_handle)?;
+    let trash_path = store::trash_path_for(&path);
+    let mut commands = load_store(&app_handle, &path)?;
+    let mut trash = store::get_trash(&trash_path)?;
+
+    let restored = commands::restore(
+        &mut commands,
+        &mut trash,
+        &id,
+        &shortcut_overrides(&app_handle),
+    )?;
+    save_store(&app_handle, &path, &commands)?;
+    store::save_trash(&trash_path, &trash, restrict_permissions(&app_handle))?;
+    refresh_shortcuts(&app_handle)?;
+    Ok(restored)
+}
+
+/// Permanently deletes everything in the trash.
+///
+/// # Returns
+///
+/// * `Ok(usize)` - Number of commands that were purged
+/// * `Err(AppError)` - Error message if the trash could not be read or written
+#[tauri::command]
+fn empty_trash(app_handle: tauri::AppHandle) -> Result<usize, AppError> {
+    ensure_writable(&app_handle)?;
+    let path = get_store_path(&app_handle)?;
+    let trash_path = store::trash_path_for(&path);
+    let purged = store::get_trash(&trash_path)?.len();
+    store::save_trash(&trash_path, &[], restrict_permissions(&app_handle))?;
+    Ok(purged)
+}
+
+/// Retrieves the current application configuration.
+///
+/// This Tauri command fetches the app configuration, including safe mode status.
+/// If no config file exists, returns default configuration (safe_mode: false).
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+///
+/// # Returns
+///
+/// * `Ok(Config)` - The current configuration object
+/// * `Err(AppError)` - Error message if:
+///   - Failed to access app data directory
+///   - Failed to parse configuration file
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const config = await invoke('get_config');
+/// console.log('Safe mode:', config.safe_mode);
+/// ```
+#[tauri::command]
+fn get_config(app_handle: tauri::AppHandle) -> Result<Config, AppError> {
+    let path = get_config_path(&app_handle)?;
+    store::get_config(&path).map(|config| config.changed_event())
+}
+
+/// Updates the application configuration.
+///
+/// This Tauri command saves the provided configuration to persistent storage.
+/// Changes take effect immediately for subsequent command executions. A moved store is
+/// migrated and a `scripts_external` change re-saves it (see
+/// [`store::apply_config_change`]), except in `read_only` mode, where only the config
+/// is written.
+///
+/// # Arguments
+///
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `config` - The new configuration object to save
+/// * `passphrase` - The safe mode passphrase, needed to turn safe mode off when one is set
+///
+/// # Returns
+///
+/// * `Ok(())` - Configuration was successfully saved
+/// * `Err(AppError)` - Error message if:
+///   - Safe mode is being turned off, or the bypass token changed, without the correct
+///     passphrase
+///   - Failed to access app data directory
+///   - `commands_path` points to a location that cannot be written
+///   - Failed to write configuration file
+///   - Failed to serialize configuration to JSON
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// await invoke('update_config', {
+///   config: { safe_mode: true }
+/// });
+/// ```
+///
+/// # Note
+///
+/// When safe mode is enabled, all command executions (manual and shortcuts) will be blocked.
+///
+/// A custom `commands_path` is test-written before the config is saved, so an unusable
+/// location is rejected up front instead of on the next command save. When it changes
+/// to a location with no store yet, the current commands are copied over (the old file
+/// is left in place).
+///
+/// After a successful save, shortcuts are refreshed and a `config://changed` event
+/// carrying the new config (without `safe_mode_passphrase_hash`) is emitted, so every
+/// window and the tray can drop stale copies.
+///
+/// `safe_mode_passphrase_hash` is always kept as stored; use
+/// [`set_safe_mode_passphrase`] to change it.
+#[tauri::command]
+fn update_config(
+    app_handle: tauri::AppHandle,
+    mut config: Config,
+    passphrase: Option<String>,
+) -> Result<(), AppError> {
+    let path = get_config_path(&app_handle)?;
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let current_config = store::get_config(&path)?;
+    config.validate().map_err(AppError::Invalid)?;
+    passphrase::authorize_config_change(&current_config, &mut config, passphrase.as_deref())
+        .map_err(AppError::Invalid)?;
+
+    if let Some(commands_path) = config
+        .commands_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
     {
-        let state = app_handle.state::<ProcessManager>();
-        state
-            .processes
-            .lock()
-            .unwrap()
-            .insert(command_id.to_string(), pid);
+        store::validate_store_location(&store::resolve_commands_path(
+            &app_data_dir,
+            commands_path,
+        ))?;
     }
 
-    let wait_result = child.wait_with_output();
-
-    {
-        let state = app_handle.state::<ProcessManager>();
-        state.processes.lock().unwrap().remove(command_id);
+    let current_store = get_store_path(&app_handle)?;
+    let new_store = store::resolve_store_path(&app_data_dir, Some(&config));
+    let store_moved = current_store != new_store;
+    let commands_dir = config
+        .commands_dir
+        .as_deref()
+        .map(|dir| store::resolve_commands_path(&app_data_dir, dir));
+    let migrated = store::apply_config_change(
+        &current_store,
+        &new_store,
+        commands_dir.as_deref(),
+        &current_config,
+        &config,
+    )?;
+    if migrated > 0 {
+        log::info!(
+            "Migrated {} commands from {:?} to {:?}",
+            migrated,
+            current_store,
+            new_store
+        );
+    }
+    save_config(&app_handle, &path, &config)?;
+    if current_config.scripts_external != config.scripts_external {
+        store_saved(&app_handle, &new_store);
+    }
+    // Registration can depend on settings, and a moved store has other shortcuts
+    if store_moved {
+        reload_store_and_notify(&app_handle)?;
+    } else {
+        refresh_shortcuts(&app_handle)?;
     }
 
-    let output = wait_result.map_err(|e| format!("Failed to wait for command: {}", e))?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    Ok(format!("{}{}", stdout, stderr))
+    if let Err(e) = app_handle.emit("config://changed", config.changed_event()) {
+        log::warn!("Failed to emit config change: {}", e);
+    }
+    Ok(())
 }
 
-/// Executes a command by its ID.
+/// Sets, changes or removes the passphrase required to turn safe mode off.
 ///
-/// This Tauri command looks up a command by its unique ID, checks if safe mode is enabled,
-/// and executes the associated shell script if allowed.
+/// The passphrase is hashed with a random salt before it is saved; the plain text is
+/// never stored.
 ///
 /// # Arguments
 ///
 /// * `app_handle` - The Tauri application handle for accessing app data directories
-/// * `command_id` - The unique identifier of the command to execute
+/// * `current_passphrase` - The existing passphrase, required when one is already set
+/// * `new_passphrase` - The passphrase to require from now on; empty or absent removes it
 ///
 /// # Returns
 ///
-/// * `Ok(String)` - Combined stdout and stderr output from the command execution
-/// * `Err(String)` - Error message if:
-///   - Safe mode is enabled (command execution disabled)
-///   - Command ID not found in the stored commands
-///   - Failed to access app data directory
-///   - Failed to read commands from storage
-///   - Script execution failed
+/// * `Ok(())` - The passphrase was updated
+/// * `Err(AppError)` - Error message if the current passphrase is missing or wrong, or the
+///   config couldn't be saved
 ///
 /// # Example
 ///
 /// ```javascript
-/// // From the frontend
 /// import { invoke } from '@tauri-apps/api/core';
 ///
-/// try {
-///   const output = await invoke('execute_command', {
-///     commandId: '123e4567-e89b-12d3-a456-426614174000'
-///   });
-///   console.log('Command output:', output);
-/// } catch (error) {
-///   console.error('Execution failed:', error);
-/// }
+/// await invoke('set_safe_mode_passphrase', { newPassphrase: 'correct horse' });
+/// await invoke('update_config', {
+///   config: { ...config, safe_mode: false },
+///   passphrase: 'correct horse'
+/// });
 /// ```
-///
-/// # Security
-///
-/// This function checks the safe mode configuration before executing any command.
-/// If safe mode is enabled, execution will fail with an appropriate error message.
 #[tauri::command]
-async fn execute_command(app_handle: tauri::AppHandle, command_id: String) -> Result<String, String> {
-    let path = get_store_path(&app_handle)?;
-    let commands = store::get_commands(&path)?;
-
-    let command = commands
-        .iter()
-        .find(|c| c.id == command_id)
-        .ok_or_else(|| String::from("Command not found"))?;
+fn set_safe_mode_passphrase(
+    app_handle: tauri::AppHandle,
+    current_passphrase: Option<String>,
+    new_passphrase: Option<String>,
+) -> Result<(), AppError> {
+    let path = get_config_path(&app_handle)?;
+    let mut config = store::get_config(&path)?;
 
-    let script = command.script.clone();
-    let app_handle_clone = app_handle.clone();
-    let command_id_clone = command_id.clone();
+    if let Some(hash) = config.safe_mode_passphrase_hash.as_deref() {
+        let current = current_passphrase.as_deref().unwrap_or_default();
+        if !passphrase::verify_passphrase(hash, current) {
+            return Err(AppError::Invalid(
+                "Incorrect safe mode passphrase".to_string(),
+            ));
+        }
+    }
 
-    tauri::async_runtime::spawn_blocking(move || {
-        run_command_script(&app_handle_clone, &command_id_clone, &script)
-    })
-    .await
-    .map_err(|e| format!("Failed to execute command task: {}", e))?
+    config.safe_mode_passphrase_hash = match new_passphrase.filter(|p| !p.is_empty()) {
+        Some(new_passphrase) => Some(passphrase::hash_passphrase(&new_passphrase)?),
+        None => None,
+    };
+    save_config(&app_handle, &path, &config)
 }
 
-/// Kills a running command by its ID.
+/// Moves the command store to a new location and points the config at it.
+///
+/// The current commands are written to `new_path` atomically, then `commands_path`
+/// is updated and shortcuts are refreshed. If a store already exists at `new_path`
+/// it is adopted as-is rather than overwritten.
 ///
 /// # Arguments
 ///
-/// * `app_handle` - The Tauri application handle
-/// * `command_id` - The unique identifier of the command to kill
+/// * `app_handle` - The Tauri application handle for accessing app data directories
+/// * `new_path` - The new commands file location (`~` is expanded)
+/// * `keep_old` - Leave the previous file in place (default `true`)
 ///
 /// # Returns
 ///
-/// * `Ok(())` - Command was successfully killed or was not running
-/// * `Err(String)` - Error message if killing failed
+/// * `Ok(RelocationSummary)` - Where the store moved from/to and how many commands were copied
+/// * `Err(AppError)` - Error message if the new location is unwritable or the copy failed
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const summary = await invoke('relocate_store', { newPath: '~/Dropbox/climgr/commands.json' });
+/// console.log(`Migrated ${summary.migrated} commands`);
+/// ```
 #[tauri::command]
-fn kill_command(app_handle: AppHandle, state: State<ProcessManager>, command_id: String) -> Result<(), String> {
-    // 1. Try custom kill script if it exists
-    let path = get_store_path(&app_handle)?;
-    let commands = store::get_commands(&path)?;
-    
-    if let Some(command) = commands.iter().find(|c| c.id == command_id) {
-        if let Some(kill_script) = &command.kill_script {
-            if !kill_script.trim().is_empty() {
-                log::info!("Executing custom kill script for command {}: {}", command_id, kill_script);
-                let output = std::process::Command::new("sh")
-                    .arg("-c")
-                    .arg(kill_script)
-                    .output()
-                    .map_err(|e| format!("Failed to execute kill script: {}", e))?;
-                
-                if !output.status.success() {
-                    log::warn!("Kill script exited with error: {}", String::from_utf8_lossy(&output.stderr));
-                }
-                // We return Ok here because the script was executed. 
-                // The process manager will clean up the PID if/when the main process dies.
-                return Ok(());
-            }
-        }
-    }
+fn relocate_store(
+    app_handle: tauri::AppHandle,
+    new_path: String,
+    keep_old: Option<bool>,
+) -> Result<RelocationSummary, AppError> {
+    ensure_writable(&app_handle)?;
+    let to = store::resolve_commands_path(&get_app_data_dir(&app_handle)?, &new_path);
+    store::validate_store_location(&to)?;
 
-    // 2. Fallback to PID-based kill
-    let pid = {
-        let procs = state.processes.lock().unwrap();
-        procs.get(&command_id).copied()
+    let from = get_store_path(&app_handle)?;
+    let migrated = if from != to && !to.exists() {
+        store::migrate_commands(&from, &to, keep_old.unwrap_or(true))?
+    } else {
+        0
     };
 
-    if let Some(pid) = pid {
-        log::info!("Killing process {} for command {}", pid, command_id);
-
-        #[cfg(unix)]
-        {
-            let output = std::process::Command::new("kill")
-                .arg("-9")
-                .arg(pid.to_string())
-                .output()
-                .map_err(|e| format!("Failed to execute kill command: {}", e))?;
-
-            if !output.status.success() {
-                return Err(format!(
-                    "Kill command failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ));
-            }
-        }
-
-        #[cfg(windows)]
-        {
-            let output = std::process::Command::new("taskkill")
-                .arg("/F")
-                .arg("/PID")
-                .arg(pid.to_string())
-                .output()
-                .map_err(|e| format!("Failed to execute taskkill command: {}", e))?;
-
-            if !output.status.success() {
-                return Err(format!(
-                    "Taskkill command failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ));
-            }
-        }
-
-        // The process removal from the map will happen in the run_command_script thread
-        // when wait_with_output returns.
-    }
-
-    Ok(())
-}
-
-fn get_store_path(app: &AppHandle) -> Result<PathBuf, String> {
-    // Check if a custom path is set in the config
-    if let Ok(config_path) = get_config_path(app) {
-        if let Ok(config) = store::get_config(&config_path) {
-            if let Some(path_str) = config.commands_path {
-                let expanded_path = store::expand_path(&path_str);
-                return Ok(PathBuf::from(expanded_path));
-            }
-        }
-    }
-
-    // Fallback to default location
-    Ok(app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data dir: {}", e))?
-        .join("commands.json"))
-}
+    let config_path = get_config_path(&app_handle)?;
+    let mut config = store::get_config(&config_path)?;
+    config.commands_path = Some(new_path);
+    save_config(&app_handle, &config_path, &config)?;
+    reload_store_and_notify(&app_handle)?;
 
-fn get_config_path(app: &AppHandle) -> Result<PathBuf, String> {
-    Ok(app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data dir: {}", e))?
-        .join("config.json"))
+    Ok(RelocationSummary {
+        from: from.to_string_lossy().to_string(),
+        to: to.to_string_lossy().to_string(),
+        migrated,
+    })
 }
 
-fn refresh_shortcuts(app_handle: &tauri::AppHandle) -> Result<(), String> {
-    app_handle
-        .global_shortcut()
-        .unregister_all()
-        .map_err(|e| e.to_string())?;
-
-    let path = get_store_path(app_handle)?;
-    // Ignore errors reading store, maybe empty
-    if let Ok(commands) = store::get_commands(&path) {
-        for command in commands {
-            if let Some(shortcut) = command.shortcut {
-                if !shortcut.trim().is_empty() {
-                    // Best effort registration
-                    if let Err(e) = app_handle.global_shortcut().register(shortcut.as_str()) {
-                        log::error!("Failed to register shortcut '{}': {}", shortcut, e);
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
+/// Retrieves the recorded execution history, oldest first.
+///
+/// Every completed run (manual or via shortcut) is recorded with its start time,
+/// duration and exit code. Only the most recent 1000 entries are kept.
+///
+/// # Example
+///
+/// ```javascript
+/// import { invoke } from '@tauri-apps/api/core';
+///
+/// const history = await invoke('get_history');
+/// ```
+#[tauri::command]
+fn get_history(app_handle: tauri::AppHandle) -> Result<Vec<HistoryEntry>, AppError> {
+    let path = get_history_path(&app_handle)?;
+    history::get_history(&path)
 }
 
-/// Retrieves all stored commands.
+/// Exports the execution history as newline-delimited JSON.
 ///
-/// This Tauri command fetches all commands from persistent storage.
+/// Each `HistoryEntry` is written as one JSON object per line, which suits `jq` and
+/// log tooling better than a single array. If no history exists yet, an empty file
+/// is written.
 ///
 /// # Arguments
 ///
 /// * `app_handle` - The Tauri application handle for accessing app data directories
-///
-/// # Returns
-///
-/// * `Ok(Vec<Command>)` - A vector containing all stored commands
-/// * `Err(String)` - Error message if:
-///   - Failed to access app data directory
-///   - Failed to read from storage file
-///   - JSON parsing failed
+/// * `path` - Destination file (`~` is expanded)
 ///
 /// # Example
 ///
 /// ```javascript
 /// import { invoke } from '@tauri-apps/api/core';
 ///
-/// const commands = await invoke('get_commands');
-/// console.log('All commands:', commands);
+/// await invoke('export_history_ndjson', { path: '~/climgr-history.ndjson' });
 /// ```
 #[tauri::command]
-fn get_commands(app_handle: tauri::AppHandle) -> Result<Vec<Command>, String> {
-    let path = get_store_path(&app_handle)?;
-    store::get_commands(&path)
+fn export_history_ndjson(app_handle: tauri::AppHandle, path: String) -> Result<(), AppError> {
+    let history_path = get_history_path(&app_handle)?;
+    let entries = history::get_history(&history_path)?;
+    history::export_ndjson(&PathBuf::from(store::expand_path(&path)), &entries)
 }
 
-/// Adds a new command to storage.
+/// Reports where the commands store, the config and the app data directory live.
 ///
-/// This Tauri command creates a new command entry and updates the global shortcuts.
+/// This Tauri command is meant for support and "show config location" menus; each path
+/// comes with whether it currently exists, since nothing is created until first saved.
 ///
 /// # Arguments
 ///
 /// * `app_handle` - The Tauri application handle for accessing app data directories
-/// * `command` - The command object to add (must have a unique ID)
 ///
 /// # Returns
 ///
-/// * `Ok(())` - Command was successfully added and shortcuts refreshed
-/// * `Err(String)` - Error message if:
-///   - Failed to access app data directory
-///   - Failed to read existing commands
-///   - Failed to save updated commands
-///   - Failed to refresh global shortcuts
+/// * `Ok(AppPaths)` - The store, config and app data paths
+/// * `Err(AppError)` - Error message if the app data directory or config can't be resolved
 ///
 /// # Example
 ///
 /// ```javascript
 /// import { invoke } from '@tauri-apps/api/core';
 ///
-/// const newCommand = {
-///   id: crypto.randomUUID(),
-///   name: 'List Files',
-///   script: 'ls -la',
-///   description: 'List all files',
-///   shortcut: 'Ctrl+L'
-/// };
-///
-/// await invoke('add_command', { command: newCommand });
+/// const { store_path, config_path } = await invoke('get_paths');
+/// console.log(config_path.path, config_path.exists);
 /// ```
-///
-/// # Note
-///
-/// This function does not check for duplicate IDs. Ensure the ID is unique before calling.
 #[tauri::command]
-fn add_command(app_handle: tauri::AppHandle, command: Command) -> Result<(), String> {
-    let path = get_store_path(&app_handle)?;
-    let mut commands = store::get_commands(&path)?;
-    commands.push(command);
-    store::save_commands(&path, &commands)?;
-    refresh_shortcuts(&app_handle)
+fn get_paths(app_handle: tauri::AppHandle) -> Result<AppPaths, AppError> {
+    Ok(AppPaths {
+        store_path: PathInfo::new(&get_store_path(&app_handle)?),
+        config_path: PathInfo::new(&get_config_path(&app_handle)?),
+        app_data_dir: PathInfo::new(&get_app_data_dir(&app_handle)?),
+    })
 }
 
-/// Updates an existing command.
+/// Lists the commands waiting in the execution queue, oldest first.
 ///
-/// This Tauri command finds a command by its ID and updates it with new values,
-/// then refreshes global shortcuts.
+/// Only used with `serialize_executions` on; the command currently running isn't
+/// listed, and the list is empty when the queue is off.
 ///
 /// # Arguments
 ///
-/// * `app_handle` - The Tauri application handle for accessing app data directories
-/// * `command` - The updated command object (ID must match an existing command)
+/// * `queue` - The managed execution queue
 ///
 /// # Returns
 ///
-/// * `Ok(())` - Command was successfully updated and shortcuts refreshed
-/// * `Err(String)` - Error message if:
-///   - Command with the given ID not found
-///   - Failed to access app data directory
-///   - Failed to read existing commands
-///   - Failed to save updated commands
-///   - Failed to refresh global shortcuts
+/// * `Vec<String>` - IDs of the queued commands, in the order they will run
 ///
 /// # Example
 ///
 /// ```javascript
 /// import { invoke } from '@tauri-apps/api/core';
 ///
-/// const updatedCommand = {
-///   id: '123e4567-e89b-12d3-a456-426614174000',
-///   name: 'List Files (Updated)',
-///   script: 'ls -lah',
-///   description: 'List all files with human-readable sizes',
-///   shortcut: 'Cmd+L'
-/// };
-///
-/// await invoke('update_command', { command: updatedCommand });
+/// const pending = await invoke('get_queue');
 /// ```
 #[tauri::command]
-fn update_command(app_handle: tauri::AppHandle, command: Command) -> Result<(), String> {
-    let path = get_store_path(&app_handle)?;
-    let mut commands = store::get_commands(&path)?;
-    if let Some(index) = commands.iter().position(|c| c.id == command.id) {
-        commands[index] = command;
-        store::save_commands(&path, &commands)?;
-        refresh_shortcuts(&app_handle)
-    } else {
-        Err("Command not found".to_string())
-    }
+fn get_queue(queue: State<queue::ExecutionQueue>) -> Vec<String> {
+    queue.pending()
 }
 
-/// Deletes a command by its ID.
+/// Checks that commands can run at all, for troubleshooting and bug reports.
 ///
-/// This Tauri command removes a command from storage and updates global shortcuts.
+/// Checks, in order, that the shell starts, the store can be saved, the config can be
+/// read and safe mode is off, then runs `echo climgr-ok` the way any command runs.
+/// Every check is made even if an earlier one fails; the test run isn't added to the
+/// history or remembered as the last command.
 ///
 /// # Arguments
 ///
 /// * `app_handle` - The Tauri application handle for accessing app data directories
-/// * `id` - The unique identifier of the command to delete
 ///
 /// # Returns
 ///
-/// * `Ok(())` - Command was successfully deleted and shortcuts refreshed
-/// * `Err(String)` - Error message if:
-///   - Failed to access app data directory
-///   - Failed to read existing commands
-///   - Failed to save updated commands
-///   - Failed to refresh global shortcuts
+/// * `Ok(Diagnostics)` - Whether each check passed, with what it found
+/// * `Err(AppError)` - Error message if the checks couldn't be run
 ///
 /// # Example
 ///
 /// ```javascript
 /// import { invoke } from '@tauri-apps/api/core';
 ///
-/// await invoke('delete_command', {
-///   id: '123e4567-e89b-12d3-a456-426614174000'
-/// });
+/// const report = await invoke('run_diagnostics');
+/// for (const check of report.checks) {
+///   console.log(check.passed ? 'ok' : 'FAIL', check.name, check.message);
+/// }
 /// ```
-///
-/// # Note
-///
-/// If the command ID doesn't exist, this function still succeeds (idempotent operation).
 #[tauri::command]
-fn delete_command(app_handle: tauri::AppHandle, id: String) -> Result<(), String> {
-    let path = get_store_path(&app_handle)?;
-    let mut commands = store::get_commands(&path)?;
-    commands.retain(|c| c.id != id);
-    store::save_commands(&path, &commands)?;
-    refresh_shortcuts(&app_handle)
+async fn run_diagnostics(app_handle: tauri::AppHandle) -> Result<models::Diagnostics, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let store = get_store_path(&app_handle).map_err(|e| e.to_string());
+        let config_path = get_config_path(&app_handle)?;
+        let config = store::get_config(&config_path);
+
+        let echo = diagnostics::echo_command();
+        let checks = vec![
+            diagnostics::check("shell", diagnostics::shell_available()),
+            diagnostics::check(
+                "store_writable",
+                store.and_then(|path| diagnostics::store_writable(&path)),
+            ),
+            diagnostics::check(
+                "config_readable",
+                diagnostics::config_readable(&config_path, &config),
+            ),
+            diagnostics::check(
+                "safe_mode",
+                config
+                    .map_err(|e| e.to_string())
+                    .and_then(|config| diagnostics::safe_mode(&config)),
+            ),
+            diagnostics::check(
+                "echo",
+                diagnostics::echo_result(run_command_script(
+                    &app_handle,
+                    &echo,
+                    None,
+                    HashMap::new(),
+                    None,
+                )),
+            ),
+        ];
+        Ok(diagnostics::report(checks))
+    })
+    .await
+    .map_err(|e| AppError::Execution(format!("Failed to run diagnostics: {}", e)))?
 }
 
-/// Retrieves the current application configuration.
+/// Shows the commands file in the OS file manager.
 ///
-/// This Tauri command fetches the app configuration, including safe mode status.
-/// If no config file exists, returns default configuration (safe_mode: false).
+/// On macOS and Windows the containing folder opens with the file selected; elsewhere
+/// the folder is opened with `xdg-open`. See [`executor::reveal_invocation`].
 ///
 /// # Arguments
 ///
@@ -432,68 +2969,89 @@ fn delete_command(app_handle: tauri::AppHandle, id: String) -> Result<(), String
 ///
 /// # Returns
 ///
-/// * `Ok(Config)` - The current configuration object
-/// * `Err(String)` - Error message if:
-///   - Failed to access app data directory
-///   - Failed to parse configuration file
+/// * `Ok(String)` - The path of the commands file that was revealed
+/// * `Err(AppError)` - Error message if:
+///   - The commands file doesn't exist yet (nothing has been saved)
+///   - The file manager could not be launched
 ///
 /// # Example
 ///
 /// ```javascript
 /// import { invoke } from '@tauri-apps/api/core';
 ///
-/// const config = await invoke('get_config');
-/// console.log('Safe mode:', config.safe_mode);
+/// const path = await invoke('reveal_store_in_file_manager');
 /// ```
 #[tauri::command]
-fn get_config(app_handle: tauri::AppHandle) -> Result<Config, String> {
-    let path = get_config_path(&app_handle)?;
-    store::get_config(&path)
+fn reveal_store_in_file_manager(app_handle: tauri::AppHandle) -> Result<String, AppError> {
+    let store_path = reveal_store(&app_handle)?;
+    Ok(store_path.to_string_lossy().to_string())
 }
 
-/// Updates the application configuration.
+/// Opens the folder containing the commands file in the OS file manager.
 ///
-/// This Tauri command saves the provided configuration to persistent storage.
-/// Changes take effect immediately for subsequent command executions.
+/// Kept for existing callers; it reveals the file the same way
+/// `reveal_store_in_file_manager` does and returns the folder instead of the file.
 ///
 /// # Arguments
 ///
 /// * `app_handle` - The Tauri application handle for accessing app data directories
-/// * `config` - The new configuration object to save
 ///
 /// # Returns
 ///
-/// * `Ok(())` - Configuration was successfully saved
-/// * `Err(String)` - Error message if:
-///   - Failed to access app data directory
-///   - Failed to write configuration file
-///   - Failed to serialize configuration to JSON
+/// * `Ok(String)` - The directory that was opened
+/// * `Err(AppError)` - Error message if:
+///   - The commands file doesn't exist yet (nothing has been saved)
+///   - The file manager could not be launched
 ///
 /// # Example
 ///
 /// ```javascript
 /// import { invoke } from '@tauri-apps/api/core';
 ///
-/// await invoke('update_config', {
-///   config: { safe_mode: true }
-/// });
+/// const dir = await invoke('reveal_store_in_explorer');
 /// ```
-///
-/// # Note
-///
-/// When safe mode is enabled, all command executions (manual and shortcuts) will be blocked.
 #[tauri::command]
-fn update_config(app_handle: tauri::AppHandle, config: Config) -> Result<(), String> {
-    let path = get_config_path(&app_handle)?;
-    store::save_config(&path, &config)
+fn reveal_store_in_explorer(app_handle: tauri::AppHandle) -> Result<String, AppError> {
+    let store_path = reveal_store(&app_handle)?;
+    let dir = store_path
+        .parent()
+        .ok_or_else(|| AppError::Invalid(format!("Invalid storage path: {:?}", store_path)))?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+/// Shows the commands file in the OS file manager and returns its path.
+fn reveal_store(app_handle: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let store_path = get_store_path(app_handle)?;
+    if !store_path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "Commands file does not exist yet: {:?}",
+            store_path
+        )));
+    }
+
+    let invocation = executor::reveal_invocation(std::env::consts::OS, &store_path);
+    invocation.to_command().spawn().map_err(|e| {
+        AppError::Io(format!(
+            "Failed to open {:?} with {}: {}",
+            store_path, invocation.program, e
+        ))
+    })?;
+
+    Ok(store_path)
 }
 
 /// Ensures the storage directory exists.
 #[tauri::command]
-fn ensure_storage_directory(app_handle: tauri::AppHandle) -> Result<String, String> {
+fn ensure_storage_directory(app_handle: tauri::AppHandle) -> Result<String, AppError> {
     let path = get_store_path(&app_handle)?;
-    log::info!("Attempting to create storage directory for path: {:?}", path);
-    println!("Debug: Attempting to create storage directory for path: {:?}", path);
+    log::info!(
+        "Attempting to create storage directory for path: {:?}",
+        path
+    );
+    println!(
+        "Debug: Attempting to create storage directory for path: {:?}",
+        path
+    );
 
     if let Some(parent) = path.parent() {
         if !parent.exists() {
@@ -510,11 +3068,14 @@ fn ensure_storage_directory(app_handle: tauri::AppHandle) -> Result<String, Stri
                     .arg("-p")
                     .arg(parent)
                     .output()
-                    .map_err(|e| format!("Failed to execute mkdir command: {}", e))?;
-                
+                    .map_err(|e| AppError::Io(format!("Failed to execute mkdir command: {}", e)))?;
+
                 if !output.status.success() {
                     let stderr = String::from_utf8_lossy(&output.stderr);
-                    return Err(format!("Failed to create directory via mkdir: {}", stderr));
+                    return Err(AppError::Io(format!(
+                        "Failed to create directory via mkdir: {}",
+                        stderr
+                    )));
                 }
             }
         } else {
@@ -526,18 +3087,20 @@ fn ensure_storage_directory(app_handle: tauri::AppHandle) -> Result<String, Stri
 }
 
 #[tauri::command]
-fn open_accessibility_settings() -> Result<(), String> {
+fn open_accessibility_settings() -> Result<(), AppError> {
     #[cfg(target_os = "macos")]
     {
         std::process::Command::new("open")
             .arg("x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility")
             .spawn()
-            .map_err(|e| format!("Failed to open accessibility settings: {}", e))?;
+            .map_err(|e| AppError::Io(format!("Failed to open accessibility settings: {}", e)))?;
         Ok(())
     }
     #[cfg(not(target_os = "macos"))]
     {
-        Err("This feature is only available on macOS".to_string())
+        Err(AppError::Other(
+            "This feature is only available on macOS".to_string(),
+        ))
     }
 }
 
@@ -546,9 +3109,29 @@ fn is_macos() -> bool {
     cfg!(target_os = "macos")
 }
 
+/// Returns the label of the primary shortcut modifier on this platform.
+///
+/// Shortcuts written with `Cmd`, `Command` or `Meta` are registered with this modifier,
+/// so the UI can show the key that will actually trigger them.
+///
+/// # Returns
+///
+/// * `"Cmd"` on macOS, `"Ctrl"` everywhere else
+///
+/// # Example
+///
+/// ```javascript
+/// const modifier = await invoke('get_platform_modifier');
+/// ```
+#[tauri::command]
+fn get_platform_modifier() -> String {
+    shortcuts::platform_modifier().to_string()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        .plugin(tauri_plugin_clipboard_manager::init())
         .setup(|app| {
             if cfg!(debug_assertions) {
                 app.handle().plugin(
@@ -565,21 +3148,36 @@ pub fn run() {
                         .with_handler(|app_handle, shortcut, event| {
                             if event.state == ShortcutState::Pressed {
                                 let shortcut_str = shortcut.to_string();
+                                let panic = panic_shortcut(app_handle);
+                                if shortcuts::is_panic_shortcut(&shortcut_str, panic.as_deref()) {
+                                    press_panic_button(app_handle);
+                                    return;
+                                }
                                 if let Ok(path) = get_store_path(app_handle) {
-                                    if let Ok(commands) = store::get_commands(&path) {
-                                        if let Some(command) = commands.iter().find(|c| {
-                                            c.shortcut.as_deref() == Some(shortcut_str.as_str())
-                                        }) {
-                                            if let Err(e) = run_command_script(
-                                                &app_handle,
-                                                &command.id,
-                                                &command.script,
-                                            ) {
+                                    if let Ok(commands) = load_store(app_handle, &path) {
+                                        if let Some(command) =
+                                            shortcuts::find_command(&commands, &shortcut_str)
+                                        {
+                                            let result = run_command_script(
+                                                app_handle,
+                                                command,
+                                                None,
+                                                HashMap::new(),
+                                                None,
+                                            );
+                                            if let Err(e) = &result {
                                                 log::error!(
                                                     "Failed to execute shortcut command: {}",
                                                     e
                                                 );
                                             }
+                                            let notification =
+                                                CommandNotification::for_result(command, &result);
+                                            if let Err(e) = app_handle
+                                                .emit("command-notification", notification)
+                                            {
+                                                log::warn!("Failed to emit notification: {}", e);
+                                            }
                                         }
                                     }
                                 }
@@ -589,26 +3187,111 @@ pub fn run() {
                 )?;
 
                 refresh_shortcuts(app.handle())?;
+
+                // Live-reload on external edits; the app still works without it
+                if let Err(e) = watcher::start(app.handle()) {
+                    log::error!("Failed to start file watcher: {}", e);
+                }
             }
 
+            let last_command_id = get_last_command_path(app.handle())
+                .and_then(|path| history::load_last_command(&path));
+            app.manage(history::LastCommand::new(last_command_id.unwrap_or_else(
+                |e| {
+                    log::warn!("Failed to restore the last command run: {}", e);
+                    None
+                },
+            )));
+
+            schedule::start(app.handle());
+
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(PRUNE_INTERVAL);
+                prune_dead_processes(handle.state::<ProcessManager>());
+            });
+
             Ok(())
         })
         .manage(ProcessManager {
             processes: Mutex::new(HashMap::new()),
         })
+        .manage(RunTimes {
+            last_runs: Mutex::new(HashMap::new()),
+        })
+        .manage(queue::ExecutionQueue::new())
+        .manage(IncludedCommands {
+            included: Mutex::new(store::Included::default()),
+        })
+        .manage(watcher::StoreWatcher::new())
+        .manage(schedule::Scheduler::new())
+        .manage(stats::ProcessMonitor::new())
+        .manage(runner::Runner::default())
         .invoke_handler(tauri::generate_handler![
             get_commands,
+            get_command,
+            get_command_variables,
+            resolve_script,
+            command_to_script,
+            get_grouped_commands,
+            get_favorites,
+            get_commands_missing_field,
+            toggle_favorite,
+            move_command,
+            set_schedule_enabled,
+            get_scheduled_runs,
+            get_process_stats,
+            check_command_status,
+            verify_store,
+            search_commands,
+            fuzzy_search_commands,
             add_command,
+            add_commands,
+            import_commands_yaml,
+            import_aliases,
+            export_commands_yaml,
             update_command,
             delete_command,
+            delete_commands,
+            batch,
+            get_trash,
+            restore_command,
+            empty_trash,
             execute_command,
+            execute_commands,
+            get_last_command,
+            rerun_last_command,
+            rerun_last,
             kill_command,
+            cancel_all,
+            prune_dead_processes,
             get_config,
             update_config,
+            set_safe_mode_passphrase,
+            relocate_store,
+            get_history,
+            export_history_ndjson,
             ensure_storage_directory,
+            get_paths,
+            run_diagnostics,
+            get_queue,
+            reveal_store_in_file_manager,
+            reveal_store_in_explorer,
+            reload_store,
             open_accessibility_settings,
-            is_macos
+            is_macos,
+            get_platform_modifier
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Don't leave orphaned shells behind
+                match cancel_all_processes(app_handle) {
+                    Ok(0) => {}
+                    Ok(stopped) => log::info!("Stopped {} running commands on exit", stopped),
+                    Err(e) => log::error!("Failed to stop running commands on exit: {}", e),
+                }
+            }
+        });
 }
@@ -1,26 +1,797 @@
+use crate::error::AppError;
+use crate::executor::{KillPolicy, Signal, KILL_GRACE_PERIOD};
+use crate::shortcuts::ShortcutError;
+use crate::store::SaveOptions;
+use crate::stream::BatchSettings;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Group name used for commands without a `group`.
+pub const UNGROUPED: &str = "Ungrouped";
+
+/// Script size limit, in bytes, used when `max_script_length` isn't configured.
+pub const DEFAULT_MAX_SCRIPT_LENGTH: usize = 64 * 1024;
+
+/// A stored shell command.
+///
+/// Most fields are client-owned: the frontend sends them and they are saved as given.
+/// `created_at`, `updated_at`, `run_count`, `last_run_at` and `last_exit_code` are
+/// server-owned: the backend maintains them and keeps the stored values when an update
+/// omits them.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct Command {
     pub id: String,
     pub name: String,
     pub script: String,
+    /// Where `script` is kept when `Config.scripts_external` is on, relative to the store
+    pub script_file: Option<String>,
+    /// Scripts run one after another instead of `script`, stopping at the first failure
+    pub steps: Option<Vec<String>>,
+    /// Makes this a macro: the commands with these IDs run in order instead of `script`
+    pub macro_steps: Option<Vec<String>>,
+    /// Keep running a macro's remaining commands after one fails
+    pub continue_on_error: Option<bool>,
+    /// Placeholders the script takes as `{{name}}`, filled in per run
+    pub variables: Option<Vec<CommandVariable>>,
+    /// IDs of commands that must run, and succeed, before this one
+    pub depends_on: Option<Vec<String>>,
     pub kill_script: Option<String>,
+    /// Health check: exit 0 means the command's service is up, anything else that it isn't
+    pub status_script: Option<String>,
+    /// Run after the script exits 0; its output is appended to the result
+    pub on_success_script: Option<String>,
+    /// Run after the script exits non-zero; its output is appended to the result
+    pub on_failure_script: Option<String>,
     pub shortcut: Option<String>,
     pub description: Option<String>,
+    /// Longer free-form notes (usage, caveats, links); never used when running
+    pub notes: Option<String>,
+    /// Folder the command is shown under, e.g. "Docker" or "Git"
+    pub group: Option<String>,
+    /// Locked commands can't be edited or deleted from the app
+    pub locked: Option<bool>,
+    /// Run through `sudo -S`; the password is supplied per execution and never stored
+    pub requires_sudo: Option<bool>,
+    /// User to run the script as, via `sudo -u` (`runas` on Windows); `root` uses plain `sudo`
+    pub run_as: Option<String>,
+    /// Environment variables for the script, on top of (and overriding)
+    /// `Config.profile_env`
+    pub env: Option<BTreeMap<String, String>>,
+    /// Start the shell as a login shell (`sh -l`) so the profile is read; overrides
+    /// `Config.login_shell`
+    pub login_shell: Option<bool>,
+    /// Start the shell as an interactive one (`sh -i`) so its rc file (`$ENV`) is read;
+    /// overrides `Config.interactive_shell`
+    pub interactive_shell: Option<bool>,
+    /// Scheduling priority as a unix niceness, -20 (highest) to 19 (lowest); mapped to a
+    /// priority class on Windows
+    pub nice: Option<i32>,
+    /// Shortest time, in seconds, between the starts of two runs; runs in between are refused
+    pub min_interval_secs: Option<u64>,
+    /// Emoji or icon name rendered by the UI
+    pub icon: Option<String>,
+    /// Accent color as `#RRGGBB`
+    pub color: Option<String>,
+    /// Pinned to the top of the list
+    pub favorite: Option<bool>,
+    /// RFC 3339 timestamp set when the command is added
+    pub created_at: Option<String>,
+    /// RFC 3339 timestamp set when the command is added or updated
+    pub updated_at: Option<String>,
+    /// Number of completed executions
+    pub run_count: Option<u64>,
+    /// RFC 3339 timestamp of when the most recent execution started
+    pub last_run_at: Option<String>,
+    /// Exit code of the most recent execution; `None` if killed by a signal
+    pub last_exit_code: Option<i32>,
+    /// Exit codes that count as success, e.g. `[0, 1]` for `grep`; `[0]` if unset
+    pub success_exit_codes: Option<Vec<i32>>,
+    /// Which output streams are captured and returned; defaults to both
+    pub capture: Option<CaptureMode>,
+    /// Open in a terminal window instead of capturing output, for interactive programs
+    pub run_in_terminal: Option<bool>,
+    /// Start without capturing output or waiting, and keep running after climgr exits
+    pub detached: Option<bool>,
+    /// Return output that isn't valid UTF-8 base64-encoded instead of replacing bad bytes
+    pub base64_fallback: Option<bool>,
+    /// After a successful run, put the script's output on the system clipboard
+    pub copy_output: Option<bool>,
+    /// Stdout is JSON; it is parsed and returned in `ExecutionResult.json` as well
+    pub output_is_json: Option<bool>,
+    /// `json` to parse stdout like `output_is_json`; `text`, the default, leaves it raw
+    pub output_format: Option<String>,
+    /// Regex; only output lines matching it are kept
+    pub output_filter: Option<String>,
+    /// Regex; a run whose output matches it fails, whatever its exit code
+    pub failure_pattern: Option<String>,
+    /// Regex applied to each streamed output line; the number it captures is emitted as
+    /// a `command://progress` percentage
+    pub progress_regex: Option<String>,
+    /// Remove ANSI escape sequences (colors, cursor movement) from the output;
+    /// overrides `Config.strip_ansi`
+    pub strip_ansi: Option<bool>,
+    /// Run automatically: `every 5m` style interval or five-field cron expression
+    pub schedule: Option<String>,
+    /// Set to `false` to pause the schedule without removing it
+    pub schedule_enabled: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// How [`ExecutionResult::output`] is encoded.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputEncoding {
+    /// Plain text; invalid UTF-8 bytes (if any) were replaced with U+FFFD
+    #[default]
+    Utf8,
+    /// The raw bytes, base64-encoded, because they weren't valid UTF-8
+    Base64,
+}
+
+/// How captured output bytes are turned into [`ExecutionResult::output`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputDecoding {
+    /// Text when the bytes are valid UTF-8, base64 otherwise
+    Utf8,
+    /// Text, with invalid UTF-8 bytes replaced by U+FFFD
+    #[default]
+    Lossy,
+    /// Text, with each byte mapped to the character of the same code point
+    Latin1,
+    /// Always base64, whatever the bytes are
+    RawBase64,
+}
+
+/// The outcome of a finished execution.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ExecutionResult {
+    pub output: String,
+    pub encoding: OutputEncoding,
+    /// Wall-clock time from spawn to exit, when the run was waited for
+    pub duration_ms: Option<u64>,
+    /// Peak resident memory of the script's process tree; unix only, best-effort
+    pub peak_memory_bytes: Option<u64>,
+    /// Per-step results of a command with `steps`, up to and including the first failure,
+    /// or of each command a macro ran
+    pub steps: Option<Vec<StepResult>>,
+    /// The script's exit code; `None` when it wasn't waited for or was killed by a signal
+    pub exit_code: Option<i32>,
+    /// Whether `exit_code` is one of the command's `success_exit_codes` and the output
+    /// doesn't match its `failure_pattern`; `None` when the run wasn't waited for
+    pub success: Option<bool>,
+    /// Stdout parsed as JSON, for commands with `output_is_json` or `output_format: json`
+    pub json: Option<serde_json::Value>,
+    /// Why stdout of an `output_is_json` command couldn't be parsed; `output` still has it
+    pub json_error: Option<String>,
+}
+
+/// A placeholder a command declares for its script.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct CommandVariable {
+    /// Referenced in the script as `{{name}}`
+    pub name: String,
+    /// Used when a run doesn't supply a value; without one the variable is required
+    pub default: Option<String>,
+    /// Help text for the UI's form
+    pub description: Option<String>,
+}
+
+/// The outcome of one step of a multi-step command.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct StepResult {
+    /// Zero-based position in `Command.steps` or `Command.macro_steps`
+    pub index: usize,
+    pub exit_code: Option<i32>,
+    /// The step's captured output, with invalid UTF-8 replaced
+    pub output: String,
+}
+
+impl ExecutionResult {
+    /// A result carrying plain text.
+    pub fn text(output: impl Into<String>) -> Self {
+        Self {
+            output: output.into(),
+            encoding: OutputEncoding::Utf8,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the run counts as a success, or was started without being waited for
+    /// (terminal and detached runs).
+    ///
+    /// Without `success` set, only exit code 0 is a success.
+    pub fn succeeded(&self) -> bool {
+        match (self.success, self.exit_code) {
+            (Some(success), _) => success,
+            (None, Some(code)) => code == 0,
+            (None, None) => self.duration_ms.is_none(),
+        }
+    }
+}
+
+/// The outcome of one command run by `execute_commands`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CommandRunResult {
+    pub command_id: String,
+    /// What the run returned; `None` if it couldn't run
+    pub result: Option<ExecutionResult>,
+    /// Why it couldn't run, e.g. safe mode or an unknown ID
+    pub error: Option<AppError>,
+}
+
+impl CommandRunResult {
+    pub fn new(command_id: String, result: Result<ExecutionResult, AppError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            command_id,
+            result,
+            error,
+        }
+    }
+}
+
+/// Output streams captured when a command runs.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    #[default]
+    Both,
+    StdoutOnly,
+    StderrOnly,
+    None,
+}
+
+impl CaptureMode {
+    pub fn stdout(self) -> bool {
+        matches!(self, CaptureMode::Both | CaptureMode::StdoutOnly)
+    }
+
+    pub fn stderr(self) -> bool {
+        matches!(self, CaptureMode::Both | CaptureMode::StderrOnly)
+    }
+}
+
+impl Command {
+    pub fn is_locked(&self) -> bool {
+        self.locked.unwrap_or(false)
+    }
+
+    /// Whether a run ending with `exit_code` succeeded, per `success_exit_codes`.
+    ///
+    /// A process killed by a signal has no exit code and never succeeds.
+    pub fn is_success(&self, exit_code: Option<i32>) -> bool {
+        let Some(code) = exit_code else {
+            return false;
+        };
+        match &self.success_exit_codes {
+            Some(codes) => codes.contains(&code),
+            None => code == 0,
+        }
+    }
+
+    /// Whether stdout is parsed as JSON, per `output_is_json` or `output_format`.
+    pub fn output_is_json(&self) -> bool {
+        self.output_is_json.unwrap_or(false)
+            || self
+                .output_format
+                .as_deref()
+                .is_some_and(|format| format.eq_ignore_ascii_case("json"))
+    }
+
+    /// Whether this is a macro, with a non-empty `macro_steps`.
+    pub fn is_macro(&self) -> bool {
+        self.macro_steps
+            .as_deref()
+            .is_some_and(|steps| !steps.is_empty())
+    }
+
+    /// The `run_as` user, if one is set.
+    pub fn run_as(&self) -> Option<&str> {
+        self.run_as
+            .as_deref()
+            .filter(|user| !user.trim().is_empty())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct Config {
     pub safe_mode: bool,
     pub commands_path: Option<String>,
     pub accessibility_notice_dismissed: Option<bool>,
+    /// Largest script, in bytes, accepted on save
+    pub max_script_length: Option<usize>,
+    /// Stop processes with SIGTERM first, escalating to SIGKILL after a grace period;
+    /// superseded by `kill_signal`
+    #[serde(default)]
+    pub graceful_kill: bool,
+    /// Signal `kill_command` sends first: `HUP`, `INT`, `QUIT`, `TERM` or `KILL`
+    pub kill_signal: Option<String>,
+    /// Seconds a process gets to exit after a `kill_signal` other than `KILL`
+    pub kill_grace_secs: Option<u64>,
+    /// Keep each script in `scripts/<id>.sh` next to the store instead of inline
+    #[serde(default)]
+    pub scripts_external: bool,
+    /// Salted SHA-256 of the passphrase needed to turn safe mode off, if one is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safe_mode_passphrase_hash: Option<String>,
+    /// Lets a single `execute_command` that supplies it run while safe mode is on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safe_mode_bypass_token: Option<String>,
+    /// Most lines sent in one `command-output` event
+    pub output_batch_lines: Option<usize>,
+    /// Longest a line waits before its `command-output` event is sent, in milliseconds
+    pub output_batch_interval_ms: Option<u64>,
+    /// Presentation mode: commands can be listed and run, but not changed
+    pub read_only: Option<bool>,
+    /// Longest output, in characters, `execute_command` returns to the UI; the rest is
+    /// cut off and only logged
+    pub ui_output_max_chars: Option<usize>,
+    /// Fail runs whose script has an unknown `{{...}}` token instead of keeping it
+    pub strict_templates: Option<bool>,
+    /// How output bytes are decoded; lossy UTF-8 unless set
+    pub output_encoding: Option<OutputDecoding>,
+    /// Save the store and config readable by their owner only (mode 0600); on by
+    /// default, and ignored on Windows
+    pub restrict_permissions: Option<bool>,
+    /// Directory of extra `*.json` command files merged into the store; relative paths
+    /// are resolved against the app data directory
+    pub commands_dir: Option<String>,
+    /// Remove ANSI escape sequences from the output of commands that don't set
+    /// `strip_ansi` themselves; off unless set
+    pub strip_ansi: Option<bool>,
+    /// Reserved system shortcuts (like `Cmd+Q`) commands may bind anyway
+    pub reserved_shortcut_overrides: Option<Vec<String>>,
+    /// Run commands one at a time, in the order they were started, instead of at once
+    pub serialize_executions: Option<bool>,
+    /// Run scripts in a login shell unless a command sets `login_shell`; off unless set
+    pub login_shell: Option<bool>,
+    /// Run scripts in an interactive shell unless a command sets `interactive_shell`;
+    /// off unless set
+    pub interactive_shell: Option<bool>,
+    /// Environment variables set for every command, e.g. shared paths and tokens
+    pub profile_env: Option<BTreeMap<String, String>>,
+    /// Global shortcut that stops every running command, even in safe mode
+    pub panic_shortcut: Option<String>,
+}
+
+impl Config {
+    pub fn script_length_limit(&self) -> usize {
+        self.max_script_length.unwrap_or(DEFAULT_MAX_SCRIPT_LENGTH)
+    }
+
+    /// The config as sent to the frontend: by `get_config` and in the `config://changed`
+    /// and `config-changed` events.
+    ///
+    /// The passphrase hash and the bypass token are left out. No frontend needs them,
+    /// every window listening would otherwise get a copy, and anyone at the UI could
+    /// read the token and use it to run commands in safe mode. A config update that
+    /// leaves the token out keeps the stored one.
+    pub fn changed_event(&self) -> Config {
+        Config {
+            safe_mode_passphrase_hash: None,
+            safe_mode_bypass_token: None,
+            ..self.clone()
+        }
+    }
+
+    /// How `command`'s output is decoded: as `utf8` if it has `base64_fallback`,
+    /// otherwise as `output_encoding` says.
+    pub fn output_decoding(&self, command: &Command) -> OutputDecoding {
+        if command.base64_fallback.unwrap_or(false) {
+            return OutputDecoding::Utf8;
+        }
+        self.output_encoding.unwrap_or_default()
+    }
+
+    /// Whether ANSI escape sequences are removed from `command`'s output.
+    pub fn strip_ansi(&self, command: &Command) -> bool {
+        command.strip_ansi.or(self.strip_ansi).unwrap_or(false)
+    }
+
+    /// Whether `command`'s script runs in a login shell.
+    pub fn login_shell(&self, command: &Command) -> bool {
+        command.login_shell.or(self.login_shell).unwrap_or(false)
+    }
+
+    /// Whether `command`'s script runs in an interactive shell.
+    pub fn interactive_shell(&self, command: &Command) -> bool {
+        command
+            .interactive_shell
+            .or(self.interactive_shell)
+            .unwrap_or(false)
+    }
+
+    /// The environment variables `command`'s script runs with: `profile_env`, with the
+    /// command's own `env` taking precedence.
+    pub fn command_env(&self, command: &Command) -> BTreeMap<String, String> {
+        let mut env = self.profile_env.clone().unwrap_or_default();
+        env.extend(command.env.clone().unwrap_or_default());
+        env
+    }
+
+    /// Rejects settings that can't be applied, e.g. an unknown `kill_signal`.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(signal) = self.kill_signal.as_deref() {
+            Signal::parse(signal)?;
+        }
+        if let Some(shortcut) = self.panic_shortcut.as_deref() {
+            if !shortcut.trim().is_empty() {
+                crate::shortcuts::parse_shortcut(shortcut).map_err(|e| e.to_string())?;
+            }
+        }
+        if let Some(name) = self
+            .profile_env
+            .iter()
+            .flat_map(|env| env.keys())
+            .find(|name| !crate::commands::is_variable_name(name))
+        {
+            return Err(format!(
+                "Invalid profile_env name '{}': use letters, digits and underscores",
+                name
+            ));
+        }
+        Ok(())
+    }
+
+    /// How processes are stopped: `kill_signal`, or `TERM` with the legacy
+    /// `graceful_kill` and `KILL` otherwise, followed by a forced kill after
+    /// `kill_grace_secs` when the first signal isn't `KILL`.
+    pub fn kill_policy(&self) -> KillPolicy {
+        let signal = match self.kill_signal.as_deref() {
+            Some(name) => Signal::parse(name).unwrap_or_else(|e| {
+                log::warn!("{}; killing with KILL", e);
+                Signal::Kill
+            }),
+            None if self.graceful_kill => Signal::Term,
+            None => Signal::Kill,
+        };
+        KillPolicy {
+            signal,
+            grace: self
+                .kill_grace_secs
+                .map_or(KILL_GRACE_PERIOD, std::time::Duration::from_secs),
+        }
+    }
+
+    pub fn restrict_permissions(&self) -> bool {
+        self.restrict_permissions.unwrap_or(true)
+    }
+
+    /// How the commands store is written under this config.
+    pub fn save_options(&self) -> SaveOptions {
+        SaveOptions {
+            scripts_external: self.scripts_external,
+            restrict_permissions: self.restrict_permissions(),
+        }
+    }
+
+    /// How streamed output is batched, with defaults for unset fields.
+    pub fn output_batch(&self) -> BatchSettings {
+        let defaults = BatchSettings::default();
+        BatchSettings {
+            max_lines: self.output_batch_lines.unwrap_or(defaults.max_lines),
+            interval: self
+                .output_batch_interval_ms
+                .map_or(defaults.interval, std::time::Duration::from_millis),
+        }
+    }
+}
+
+/// A deleted command kept in the trash until restored or the trash is emptied.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrashedCommand {
+    #[serde(flatten)]
+    pub command: Command,
+    /// RFC 3339 timestamp of the deletion
+    pub deleted_at: String,
+}
+
+/// Buckets commands by `group`, keeping stored order within each group.
+///
+/// Commands without a group (or with a blank one) land in [`UNGROUPED`].
+pub fn group_commands(commands: &[Command]) -> HashMap<String, Vec<Command>> {
+    let mut groups: HashMap<String, Vec<Command>> = HashMap::new();
+    for command in commands {
+        let key = command
+            .group
+            .as_deref()
+            .map(str::trim)
+            .filter(|g| !g.is_empty())
+            .unwrap_or(UNGROUPED);
+        groups
+            .entry(key.to_string())
+            .or_default()
+            .push(command.clone());
+    }
+    groups
+}
+
+/// Result of moving the command store to a new location.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelocationSummary {
+    pub from: String,
+    pub to: String,
+    pub migrated: usize,
+}
+
+/// Kind of problem found by a store health check.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreIssueKind {
+    DuplicateId,
+    EmptyScript,
+    DuplicateShortcut,
+    InvalidShortcut,
+    DanglingReference,
+    ReferenceCycle,
+    Unreadable,
+}
+
+/// A problem with one stored command.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StoreIssue {
+    pub kind: StoreIssueKind,
+    pub command_id: String,
+    /// Human-readable description naming the command
+    pub message: String,
+}
+
+/// A stored entry that couldn't be parsed as a [`Command`] and was skipped on load.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct UnreadableCommand {
+    /// Position in the stored array
+    pub index: usize,
+    /// The entry's `id`, if it has a string one
+    pub id: Option<String>,
+    pub error: String,
+    /// The entry as stored
+    pub entry: serde_json::Value,
+}
+
+impl UnreadableCommand {
+    /// This entry as a `verify_store` issue.
+    pub fn issue(&self) -> StoreIssue {
+        let entry = match &self.id {
+            Some(id) => format!("Entry {} (ID {})", self.index, id),
+            None => format!("Entry {}", self.index),
+        };
+        StoreIssue {
+            kind: StoreIssueKind::Unreadable,
+            command_id: self.id.clone().unwrap_or_default(),
+            message: format!(
+                "{} couldn't be read and is hidden: {}. A copy is kept in the quarantine file",
+                entry, self.error
+            ),
+        }
+    }
+}
+
+/// A command in a `commands_dir` file whose ID an earlier file already uses; it's
+/// skipped on load but kept in its file when that file is saved.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DuplicateCommand {
+    pub command: Command,
+    /// The file the skipped command is in
+    pub file: PathBuf,
+    /// The file holding the command that keeps the ID
+    pub first_file: PathBuf,
+}
+
+impl DuplicateCommand {
+    /// This command as a `verify_store` issue.
+    pub fn issue(&self) -> StoreIssue {
+        StoreIssue {
+            kind: StoreIssueKind::DuplicateId,
+            command_id: self.command.id.clone(),
+            message: format!(
+                "'{}' in {:?} has the same ID as a command in {:?} and is ignored",
+                self.command.name, self.file, self.first_file
+            ),
+        }
+    }
+}
+
+/// A line of an imported file that didn't become a command.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SkippedLine {
+    /// One-based line number
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+/// Result of `import_aliases`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AliasImportSummary {
+    /// Names of the commands created, in file order
+    pub imported: Vec<String>,
+    pub skipped: Vec<SkippedLine>,
+    /// Findings about the created commands, as in [`BulkSaveReport`]
+    #[serde(flatten)]
+    pub report: SaveReport,
+}
+
+/// Whether a command is running and, if it has a `status_script`, healthy.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CommandStatus {
+    /// A process started by the command is being tracked
+    pub running: bool,
+    /// Result of the `status_script`; `None` without one, or when safe mode kept it
+    /// from running
+    pub healthy: Option<bool>,
+}
+
+/// Non-fatal findings from saving a command with `add_command` or `update_command`.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct SaveReport {
+    /// Shortcuts, this command's or another's, that failed to register
+    pub shortcut_errors: Vec<ShortcutError>,
+    /// Advisory warnings about the saved command, e.g. a kill script that matches broadly
+    pub warnings: Vec<String>,
+}
+
+/// Result of `add_commands` and `import_commands_yaml`.
+///
+/// Carries the fields of a [`SaveReport`], with each warning prefixed by the name of
+/// the command it is about.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct BulkSaveReport {
+    /// Number of commands added
+    pub added: usize,
+    #[serde(flatten)]
+    pub report: SaveReport,
+}
+
+/// One operation of a `batch` call, tagged by `op`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOp {
+    Add { command: Command },
+    Update { command: Command },
+    Delete { id: String },
+}
+
+/// The outcome of one [`BatchOp`], at the same index as the op.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct BatchOpResult {
+    pub ok: bool,
+    /// Why the op was skipped; `None` when it applied
+    pub error: Option<AppError>,
+    /// Advisory warnings about the saved command, as in [`SaveReport`]; empty for
+    /// deletes and skipped ops
+    pub warnings: Vec<String>,
+}
+
+/// How a `command-notification` should be presented.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationSeverity {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// Payload of the `command-notification` event, emitted when a shortcut run ends.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CommandNotification {
+    pub command_id: String,
+    pub severity: NotificationSeverity,
+    pub message: String,
+    pub exit_code: Option<i32>,
+}
+
+impl CommandNotification {
+    /// Summarizes the outcome of running `command`.
+    ///
+    /// A successful exit code (see [`ExecutionResult::succeeded`]) is a success and any
+    /// other code an error. A run that wasn't waited for (terminal, detached) is
+    /// informational; one that ended without a code, i.e. was killed by a signal, is a
+    /// warning.
+    pub fn for_result(command: &Command, result: &Result<ExecutionResult, AppError>) -> Self {
+        let (severity, message, exit_code) = match result {
+            Ok(result) => match (result.exit_code, result.duration_ms) {
+                (Some(code), _) if result.succeeded() => (
+                    NotificationSeverity::Success,
+                    format!("{} finished", command.name),
+                    Some(code),
+                ),
+                (Some(code), _) => (
+                    NotificationSeverity::Error,
+                    format!("{} failed with exit code {}", command.name, code),
+                    Some(code),
+                ),
+                (None, Some(_)) => (
+                    NotificationSeverity::Warning,
+                    format!("{} was terminated", command.name),
+                    None,
+                ),
+                (None, None) => (
+                    NotificationSeverity::Info,
+                    format!("{}: {}", command.name, result.output),
+                    None,
+                ),
+            },
+            Err(e) => (
+                NotificationSeverity::Error,
+                format!("{} failed: {}", command.name, e),
+                None,
+            ),
+        };
+        Self {
+            command_id: command.id.clone(),
+            severity,
+            message,
+            exit_code,
+        }
+    }
+}
+
+/// A file or directory the app uses, and whether it exists right now.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PathInfo {
+    pub path: String,
+    pub exists: bool,
+}
+
+impl PathInfo {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            exists: path.exists(),
+        }
+    }
+}
+
+/// One check made by `run_diagnostics`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DiagnosticCheck {
+    /// `shell`, `store_writable`, `config_readable`, `safe_mode` or `echo`
+    pub name: String,
+    pub passed: bool,
+    /// What was found, or why the check failed
+    pub message: String,
+}
+
+/// The report returned by `run_diagnostics`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Diagnostics {
+    /// Whether every check passed
+    pub passed: bool,
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+/// Where the app keeps its files, as returned by `get_paths`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AppPaths {
+    /// The commands store, after `CLIMGR_STORE_PATH` and `commands_path` are applied
+    pub store_path: PathInfo,
+    /// `config.toml` if present, otherwise `config.json`
+    pub config_path: PathInfo,
+    pub app_data_dir: PathInfo,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_path_info_reports_existence() {
+        let dir = std::env::temp_dir();
+        assert_eq!(
+            PathInfo::new(&dir),
+            PathInfo {
+                path: dir.to_string_lossy().to_string(),
+                exists: true,
+            }
+        );
+        assert!(!PathInfo::new(&dir.join("climgr_no_such_file.json")).exists);
+    }
+
     #[test]
     fn test_command_struct_serialization() {
         let command = Command {
@@ -30,6 +801,7 @@ mod tests {
             kill_script: Some("pkill -f hello".to_string()),
             shortcut: Some("Ctrl+T".to_string()),
             description: Some("A test command".to_string()),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&command).expect("Failed to serialize");
@@ -45,18 +817,295 @@ mod tests {
         assert_eq!(command.description, deserialized.description);
     }
 
+    #[test]
+    fn test_command_without_group_deserializes() {
+        let json = r#"{"id":"1","name":"Old","script":"ls","kill_script":null,"shortcut":null,"description":null}"#;
+        let command: Command = serde_json::from_str(json).expect("Old entries should still load");
+        assert!(command.group.is_none());
+    }
+
+    #[test]
+    fn test_command_notes() {
+        let command = Command {
+            id: "1".to_string(),
+            name: "Deploy".to_string(),
+            script: "make deploy".to_string(),
+            notes: Some("Needs VPN.\nSee https://example.com/runbook".to_string()),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&command).expect("Failed to serialize");
+        let deserialized: Command = serde_json::from_str(&json).expect("Failed to deserialize");
+        assert_eq!(deserialized.notes, command.notes);
+
+        let json = r#"{"id":"1","name":"Old","script":"ls"}"#;
+        let command: Command = serde_json::from_str(json).expect("Old entries should still load");
+        assert!(command.notes.is_none());
+    }
+
+    #[test]
+    fn test_group_commands() {
+        let command = |id: &str, group: Option<&str>| Command {
+            id: id.to_string(),
+            name: id.to_string(),
+            script: "true".to_string(),
+            group: group.map(str::to_string),
+            ..Default::default()
+        };
+        let commands = vec![
+            command("build", Some("Docker")),
+            command("notes", None),
+            command("status", Some("Git")),
+            command("run", Some("Docker")),
+            command("blank", Some("  ")),
+        ];
+
+        let groups = group_commands(&commands);
+        assert_eq!(groups.len(), 3);
+
+        let ungrouped: Vec<&str> = groups[UNGROUPED].iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ungrouped, vec!["notes", "blank"]);
+
+        let docker: Vec<&str> = groups["Docker"].iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(docker, vec!["build", "run"]);
+        assert_eq!(groups["Git"].len(), 1);
+    }
+
     #[test]
     fn test_config_default() {
-        let config = Config { 
+        let config = Config {
             safe_mode: false,
             commands_path: None,
+            accessibility_notice_dismissed: None,
+            max_script_length: None,
+            graceful_kill: false,
+            scripts_external: false,
+            safe_mode_passphrase_hash: None,
+            safe_mode_bypass_token: None,
+            output_batch_lines: None,
+            output_batch_interval_ms: None,
+            read_only: None,
+            ui_output_max_chars: None,
+            strict_templates: None,
+            output_encoding: None,
+            restrict_permissions: None,
+            commands_dir: None,
+            strip_ansi: None,
+            reserved_shortcut_overrides: None,
+            serialize_executions: None,
+            login_shell: None,
+            interactive_shell: None,
+            profile_env: None,
+            panic_shortcut: None,
+            kill_signal: None,
+            kill_grace_secs: None,
         };
-        assert_eq!(config.safe_mode, false);
+        assert!(!config.safe_mode);
         assert!(config.commands_path.is_none());
 
         let json = serde_json::to_string(&config).expect("Failed to serialize");
         let deserialized: Config = serde_json::from_str(&json).expect("Failed to deserialize");
         assert_eq!(config.safe_mode, deserialized.safe_mode);
         assert_eq!(config.commands_path, deserialized.commands_path);
+        assert_eq!(
+            deserialized.script_length_limit(),
+            DEFAULT_MAX_SCRIPT_LENGTH
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi_command_overrides_config() {
+        let plain = Command::default();
+        let colored = Command {
+            strip_ansi: Some(false),
+            ..Default::default()
+        };
+        assert!(!Config::default().strip_ansi(&plain));
+
+        let config = Config {
+            strip_ansi: Some(true),
+            ..Default::default()
+        };
+        assert!(config.strip_ansi(&plain));
+        assert!(!config.strip_ansi(&colored));
+    }
+
+    #[test]
+    fn test_kill_policy() {
+        assert_eq!(Config::default().kill_policy(), KillPolicy::FORCE);
+
+        let legacy = Config {
+            graceful_kill: true,
+            ..Default::default()
+        };
+        assert_eq!(legacy.kill_policy().signal, Signal::Term);
+        assert!(legacy.kill_policy().is_graceful());
+
+        let tuned = Config {
+            graceful_kill: true,
+            kill_signal: Some("int".to_string()),
+            kill_grace_secs: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            tuned.kill_policy(),
+            KillPolicy {
+                signal: Signal::Int,
+                grace: std::time::Duration::from_secs(10),
+            }
+        );
+        assert!(tuned.validate().is_ok());
+
+        let unknown = Config {
+            kill_signal: Some("USR1".to_string()),
+            ..Default::default()
+        };
+        assert!(unknown.validate().unwrap_err().contains("Unknown signal"));
+        assert_eq!(unknown.kill_policy().signal, Signal::Kill);
+    }
+
+    #[test]
+    fn test_validate_rejects_an_invalid_panic_shortcut() {
+        let panic = |shortcut: &str| Config {
+            panic_shortcut: Some(shortcut.to_string()),
+            ..Default::default()
+        };
+        assert!(panic("Ctrl+Alt+Escape").validate().is_ok());
+        assert!(panic("").validate().is_ok());
+        assert!(panic("Ctrl+Banana")
+            .validate()
+            .unwrap_err()
+            .contains("Invalid shortcut"));
+    }
+
+    #[test]
+    fn test_validate_rejects_an_invalid_profile_env_name() {
+        let profile = |name: &str| Config {
+            profile_env: Some(BTreeMap::from([(name.to_string(), "x".to_string())])),
+            ..Default::default()
+        };
+        assert!(profile("API_TOKEN").validate().is_ok());
+        assert!(profile("$(touch /tmp/x)")
+            .validate()
+            .unwrap_err()
+            .contains("Invalid profile_env name"));
+    }
+
+    #[test]
+    fn test_execution_succeeded() {
+        let finished = |exit_code| ExecutionResult {
+            exit_code,
+            duration_ms: Some(5),
+            ..Default::default()
+        };
+        assert!(finished(Some(0)).succeeded());
+        assert!(!finished(Some(1)).succeeded());
+        // Killed by a signal
+        assert!(!finished(None).succeeded());
+        // Opened in a terminal, not waited for
+        assert!(ExecutionResult::text("Opened").succeeded());
+    }
+
+    #[test]
+    fn test_success_exit_codes() {
+        let grep = Command {
+            success_exit_codes: Some(vec![0, 1]),
+            ..Default::default()
+        };
+        assert!(grep.is_success(Some(0)));
+        assert!(grep.is_success(Some(1)));
+        assert!(!grep.is_success(Some(2)));
+        assert!(!grep.is_success(None));
+
+        let plain = Command::default();
+        assert!(plain.is_success(Some(0)));
+        assert!(!plain.is_success(Some(1)));
+
+        // The recorded verdict wins over the exit code
+        let no_match = ExecutionResult {
+            exit_code: Some(1),
+            success: Some(true),
+            duration_ms: Some(5),
+            ..Default::default()
+        };
+        assert!(no_match.succeeded());
+        let notification = CommandNotification::for_result(&grep, &Ok(no_match));
+        assert_eq!(notification.severity, NotificationSeverity::Success);
+        assert_eq!(notification.exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_notification_severity_follows_exit_code() {
+        let command = Command {
+            id: "1".to_string(),
+            name: "Deploy".to_string(),
+            ..Default::default()
+        };
+        let finished = |exit_code| {
+            Ok(ExecutionResult {
+                exit_code,
+                duration_ms: Some(5),
+                ..Default::default()
+            })
+        };
+
+        let ok = CommandNotification::for_result(&command, &finished(Some(0)));
+        assert_eq!(ok.severity, NotificationSeverity::Success);
+        assert_eq!(ok.command_id, "1");
+
+        let failed = CommandNotification::for_result(&command, &finished(Some(2)));
+        assert_eq!(failed.severity, NotificationSeverity::Error);
+        assert_eq!(failed.exit_code, Some(2));
+        assert_eq!(failed.message, "Deploy failed with exit code 2");
+
+        let killed = CommandNotification::for_result(&command, &finished(None));
+        assert_eq!(killed.severity, NotificationSeverity::Warning);
+
+        let opened = Ok(ExecutionResult::text("Opened in a new terminal (pid 7)"));
+        let opened = CommandNotification::for_result(&command, &opened);
+        assert_eq!(opened.severity, NotificationSeverity::Info);
+
+        let blocked = CommandNotification::for_result(&command, &Err(AppError::SafeModeBlocked));
+        assert_eq!(blocked.severity, NotificationSeverity::Error);
+        assert!(blocked.message.contains("safe mode"));
+
+        let json = serde_json::to_value(&ok).unwrap();
+        assert_eq!(json["severity"], "success");
+    }
+
+    #[test]
+    fn test_changed_event_leaves_out_the_secrets() {
+        let config = Config {
+            safe_mode: true,
+            graceful_kill: true,
+            safe_mode_passphrase_hash: Some("00ff$abcd".to_string()),
+            safe_mode_bypass_token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let payload = config.changed_event();
+        assert_eq!(
+            payload,
+            Config {
+                safe_mode_passphrase_hash: None,
+                safe_mode_bypass_token: None,
+                ..config.clone()
+            }
+        );
+
+        let json = serde_json::to_string(&payload).unwrap();
+        assert!(!json.contains("safe_mode_passphrase_hash"), "{}", json);
+        assert!(!json.contains("safe_mode_bypass_token"), "{}", json);
+        assert!(
+            !json.contains("s3cret") && !json.contains("00ff$abcd"),
+            "{}",
+            json
+        );
+
+        // The stored config keeps both
+        let stored = serde_json::to_string(&config).unwrap();
+        assert!(
+            stored.contains("\"safe_mode_bypass_token\":\"s3cret\""),
+            "{}",
+            stored
+        );
     }
 }
@@ -0,0 +1,222 @@
+use crate::models::Config;
+use sha2::{Digest, Sha256};
+
+const SALT_LEN: usize = 16;
+
+/// Hashes a passphrase with a fresh random salt.
+///
+/// The result is `<salt hex>$<sha256(salt + passphrase) hex>`, the format stored in
+/// `Config.safe_mode_passphrase_hash`.
+pub fn hash_passphrase(passphrase: &str) -> Result<String, String> {
+    let mut salt = [0u8; SALT_LEN];
+    getrandom::getrandom(&mut salt).map_err(|e| format!("Failed to generate salt: {}", e))?;
+    Ok(format!(
+        "{}${}",
+        to_hex(&salt),
+        to_hex(&digest(&salt, passphrase))
+    ))
+}
+
+/// Whether `passphrase` matches a hash made by [`hash_passphrase`].
+///
+/// A malformed hash never matches.
+pub fn verify_passphrase(hash: &str, passphrase: &str) -> bool {
+    let Some((salt, expected)) = hash.split_once('$') else {
+        return false;
+    };
+    let (Some(salt), Some(expected)) = (from_hex(salt), from_hex(expected)) else {
+        return false;
+    };
+    constant_time_eq(&digest(&salt, passphrase), &expected)
+}
+
+/// Whether `token` lets a single run through while safe mode is on.
+///
+/// It must equal `Config.safe_mode_bypass_token`; a blank token, supplied or
+/// configured, never matches.
+pub fn bypass_allowed(config: &Config, token: Option<&str>) -> bool {
+    match (config.safe_mode_bypass_token.as_deref(), token) {
+        (Some(expected), Some(token)) if !expected.trim().is_empty() => {
+            constant_time_eq(expected.as_bytes(), token.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+/// Checks that `new` may replace `current` and carries the stored passphrase hash over.
+///
+/// The hash can't be set or cleared through a config update (the UI doesn't send it
+/// back), and while one is set, turning safe mode off or changing the bypass token
+/// requires the matching passphrase.
+///
+/// The UI doesn't get the bypass token either, so an update without one keeps the
+/// stored token; a blank one clears it.
+pub fn authorize_config_change(
+    current: &Config,
+    new: &mut Config,
+    passphrase: Option<&str>,
+) -> Result<(), String> {
+    new.safe_mode_passphrase_hash = current.safe_mode_passphrase_hash.clone();
+    new.safe_mode_bypass_token = match new.safe_mode_bypass_token.take() {
+        None => current.safe_mode_bypass_token.clone(),
+        Some(token) if token.trim().is_empty() => None,
+        token => token,
+    };
+
+    let Some(hash) = current.safe_mode_passphrase_hash.as_deref() else {
+        return Ok(());
+    };
+    let disabling = current.safe_mode && !new.safe_mode;
+    if disabling || current.safe_mode_bypass_token != new.safe_mode_bypass_token {
+        match passphrase {
+            Some(passphrase) if verify_passphrase(hash, passphrase) => {}
+            Some(_) => return Err("Incorrect safe mode passphrase".to_string()),
+            None => return Err("A passphrase is required to turn off safe mode".to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Compares every byte, so timing doesn't reveal how much of a secret matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn digest(salt: &[u8], passphrase: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(passphrase.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_passphrase("open sesame").unwrap();
+        assert!(verify_passphrase(&hash, "open sesame"));
+        assert!(!verify_passphrase(&hash, "open sesame "));
+        assert!(!verify_passphrase("not a hash", "open sesame"));
+
+        // Salted: the same passphrase hashes differently each time
+        assert_ne!(hash, hash_passphrase("open sesame").unwrap());
+    }
+
+    #[test]
+    fn test_turning_off_safe_mode_requires_passphrase() {
+        let current = Config {
+            safe_mode: true,
+            safe_mode_passphrase_hash: Some(hash_passphrase("letmein").unwrap()),
+            ..Default::default()
+        };
+        let off = Config {
+            safe_mode: false,
+            ..Default::default()
+        };
+
+        assert!(authorize_config_change(&current, &mut off.clone(), None).is_err());
+        assert_eq!(
+            authorize_config_change(&current, &mut off.clone(), Some("guess")),
+            Err("Incorrect safe mode passphrase".to_string())
+        );
+
+        let mut allowed = off.clone();
+        authorize_config_change(&current, &mut allowed, Some("letmein")).unwrap();
+        // The update can't drop the hash
+        assert_eq!(
+            allowed.safe_mode_passphrase_hash,
+            current.safe_mode_passphrase_hash
+        );
+
+        // Other changes, and turning safe mode on, need no passphrase
+        let mut still_safe = Config {
+            safe_mode: true,
+            graceful_kill: true,
+            ..Default::default()
+        };
+        authorize_config_change(&current, &mut still_safe, None).unwrap();
+        authorize_config_change(&off, &mut current.clone(), None).unwrap();
+    }
+
+    #[test]
+    fn test_bypass_token_must_match() {
+        let config = Config {
+            safe_mode: true,
+            safe_mode_bypass_token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        assert!(bypass_allowed(&config, Some("s3cret")));
+        assert!(!bypass_allowed(&config, Some("s3cret ")));
+        assert!(!bypass_allowed(&config, Some("")));
+        assert!(!bypass_allowed(&config, None));
+
+        // A blank configured token disables the bypass entirely
+        for configured in [None, Some(""), Some("  ")] {
+            let config = Config {
+                safe_mode_bypass_token: configured.map(str::to_string),
+                ..config.clone()
+            };
+            assert!(!bypass_allowed(&config, configured), "{:?}", configured);
+        }
+    }
+
+    #[test]
+    fn test_changing_bypass_token_requires_passphrase() {
+        let current = Config {
+            safe_mode: true,
+            safe_mode_passphrase_hash: Some(hash_passphrase("letmein").unwrap()),
+            ..Default::default()
+        };
+        let with_token = Config {
+            safe_mode: true,
+            safe_mode_bypass_token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+
+        assert!(authorize_config_change(&current, &mut with_token.clone(), None).is_err());
+        authorize_config_change(&current, &mut with_token.clone(), Some("letmein")).unwrap();
+    }
+
+    #[test]
+    fn test_update_without_the_token_keeps_it() {
+        let current = Config {
+            safe_mode: true,
+            safe_mode_bypass_token: Some("s3cret".to_string()),
+            safe_mode_passphrase_hash: Some(hash_passphrase("letmein").unwrap()),
+            ..Default::default()
+        };
+
+        // What the UI sends back after editing an unrelated setting
+        let mut edited = Config {
+            graceful_kill: true,
+            ..current.changed_event()
+        };
+        authorize_config_change(&current, &mut edited, None).unwrap();
+        assert_eq!(edited.safe_mode_bypass_token.as_deref(), Some("s3cret"));
+
+        // Clearing it is a change, which needs the passphrase
+        let mut cleared = Config {
+            safe_mode_bypass_token: Some(String::new()),
+            ..current.changed_event()
+        };
+        assert!(authorize_config_change(&current, &mut cleared.clone(), None).is_err());
+        authorize_config_change(&current, &mut cleared, Some("letmein")).unwrap();
+        assert_eq!(cleared.safe_mode_bypass_token, None);
+    }
+}
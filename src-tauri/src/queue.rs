@@ -0,0 +1,158 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+    static ON_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the calling thread is the queue's worker.
+///
+/// A queued run that starts another one (like a macro running its steps) has to run it
+/// directly, since the worker would otherwise wait on itself.
+pub fn on_worker() -> bool {
+    ON_WORKER.with(Cell::get)
+}
+
+/// Runs jobs one at a time, in the order they were queued, on a single worker thread.
+///
+/// Backs `Config.serialize_executions`. The worker is started on first use.
+#[derive(Default)]
+pub struct ExecutionQueue {
+    /// Command IDs of the jobs waiting to start, oldest first
+    pending: Arc<Mutex<VecDeque<String>>>,
+    sender: Mutex<Option<Sender<Job>>>,
+}
+
+impl ExecutionQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// IDs of the commands waiting to run, oldest first; the running one isn't listed.
+    pub fn pending(&self) -> Vec<String> {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Queues `job` for the command `id` and blocks until the worker has run it.
+    ///
+    /// Fails if the job panicked; the worker carries on with the next one.
+    pub fn run<T: Send + 'static>(
+        &self,
+        id: &str,
+        job: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, String> {
+        let (result_tx, result_rx) = mpsc::channel();
+        let pending = Arc::clone(&self.pending);
+        let wrapped: Job = Box::new(move || {
+            pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_front();
+            let _ = result_tx.send(job());
+        });
+
+        // Queued under the sender lock so `pending` lists jobs in channel order
+        {
+            let mut sender = self.sender.lock().unwrap_or_else(PoisonError::into_inner);
+            let sender = sender.get_or_insert_with(spawn_worker);
+            self.pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push_back(id.to_string());
+            sender
+                .send(wrapped)
+                .map_err(|_| "The execution queue has stopped".to_string())?;
+        }
+        result_rx
+            .recv()
+            .map_err(|_| format!("Queued run of {} panicked", id))
+    }
+}
+
+fn spawn_worker() -> Sender<Job> {
+    let (sender, jobs) = mpsc::channel::<Job>();
+    std::thread::spawn(move || {
+        ON_WORKER.with(|on_worker| on_worker.set(true));
+        for job in jobs {
+            if std::panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                log::error!("A queued execution panicked");
+            }
+        }
+    });
+    sender
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn wait_for(queue: &ExecutionQueue, expected: &[&str]) {
+        for _ in 0..200 {
+            if queue.pending() == expected {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(queue.pending(), expected);
+    }
+
+    #[test]
+    fn test_jobs_run_one_at_a_time_in_order() {
+        let queue = Arc::new(ExecutionQueue::new());
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (started_tx, started_rx) = mpsc::channel::<()>();
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+
+        let spawn = |id: &'static str, gate: Option<(Sender<()>, mpsc::Receiver<()>)>| {
+            let (queue, order) = (Arc::clone(&queue), Arc::clone(&order));
+            std::thread::spawn(move || {
+                queue.run(id, move || {
+                    if let Some((started, gate)) = gate {
+                        started.send(()).unwrap();
+                        gate.recv().unwrap();
+                    }
+                    order.lock().unwrap().push(id);
+                    on_worker()
+                })
+            })
+        };
+
+        // "first" holds the worker until the others are queued behind it
+        let first = spawn("first", Some((started_tx, gate_rx)));
+        started_rx.recv().unwrap();
+        let second = spawn("second", None);
+        wait_for(&queue, &["second"]);
+        let third = spawn("third", None);
+        wait_for(&queue, &["second", "third"]);
+        assert!(order.lock().unwrap().is_empty());
+
+        gate_tx.send(()).unwrap();
+        for handle in [first, second, third] {
+            assert_eq!(handle.join().unwrap(), Ok(true));
+        }
+        assert_eq!(*order.lock().unwrap(), vec!["first", "second", "third"]);
+        assert!(queue.pending().is_empty());
+        assert!(!on_worker());
+    }
+
+    #[test]
+    fn test_a_panicking_job_does_not_stop_the_queue() {
+        let queue = ExecutionQueue::new();
+        let err = queue
+            .run("boom", || -> i32 { panic!("job failed") })
+            .unwrap_err();
+        assert_eq!(err, "Queued run of boom panicked");
+        assert_eq!(queue.run("next", || 7), Ok(7));
+    }
+}
@@ -0,0 +1,880 @@
+use crate::error::AppError;
+use crate::executor::{self, Completed, Invocation, ShellFlags};
+use crate::models::{CaptureMode, Command, ExecutionResult, StepResult};
+use std::process::ExitStatus;
+use std::sync::mpsc::SyncSender;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Everything needed to start one execution of a command's script.
+pub struct RunRequest {
+    pub invocation: Invocation,
+    pub capture: CaptureMode,
+    /// Written to the process's stdin (the `sudo -S` password), zeroed when dropped
+    pub stdin_secret: Option<Zeroizing<String>>,
+    /// Receives each captured output line as it is read
+    pub output_lines: Option<SyncSender<String>>,
+    /// Niceness to start the process at (see [`executor::set_priority`])
+    pub nice: Option<i32>,
+    /// The shell runs with `-i`, so it's kept away from any terminal it could wait on
+    pub interactive: bool,
+    /// Environment variables added to the process
+    pub env: Vec<(String, String)>,
+}
+
+impl RunRequest {
+    /// Assembles the request for a command's script.
+    ///
+    /// `sudo_password` is only kept for commands with `requires_sudo`, and is required
+    /// for them.
+    pub fn for_command(
+        command: &Command,
+        sudo_password: Option<Zeroizing<String>>,
+    ) -> Result<RunRequest, String> {
+        Self::for_script(command, &command.script, sudo_password)
+    }
+
+    /// Like [`RunRequest::for_command`], but runs `script` (one of the command's steps)
+    /// with the command's sudo and capture settings.
+    pub fn for_script(
+        command: &Command,
+        script: &str,
+        sudo_password: Option<Zeroizing<String>>,
+    ) -> Result<RunRequest, String> {
+        let requires_sudo = command.requires_sudo.unwrap_or(false);
+        let stdin_secret = match sudo_password {
+            Some(password) if requires_sudo => Some(password),
+            _ if requires_sudo => return Err("Command requires a sudo password".to_string()),
+            _ => None,
+        };
+
+        let flags = ShellFlags {
+            login: command.login_shell.unwrap_or(false),
+            interactive: command.interactive_shell.unwrap_or(false),
+        };
+        let invocation = executor::user_invocation(script, requires_sudo, command.run_as());
+        Ok(RunRequest {
+            invocation: executor::with_shell_flags(invocation, flags),
+            capture: command.capture.unwrap_or_default(),
+            stdin_secret,
+            output_lines: None,
+            nice: command.nice,
+            interactive: flags.interactive,
+            env: command
+                .env
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect(),
+        })
+    }
+}
+
+/// Starts script processes and waits for them, so execution can be swapped out in tests.
+pub trait CommandRunner: Send + Sync {
+    /// Runs `request` to completion, timing it from `start`.
+    ///
+    /// `on_spawn` is called with the PID once the process is running, before waiting,
+    /// so the caller can track it for `kill_command`.
+    fn run(
+        &self,
+        request: RunRequest,
+        start: Instant,
+        on_spawn: &mut dyn FnMut(u32),
+    ) -> Result<Completed, String>;
+}
+
+/// Runs scripts as real child processes.
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(
+        &self,
+        request: RunRequest,
+        start: Instant,
+        on_spawn: &mut dyn FnMut(u32),
+    ) -> Result<Completed, String> {
+        let mut process = request.invocation.to_command();
+        process.envs(request.env.iter().cloned());
+        executor::apply_capture(&mut process, request.capture);
+        if request.interactive {
+            // An interactive shell takes over the terminal climgr was started from, if
+            // any, and stops when it can't; a new session has no terminal to take. Its
+            // PID still leads its own group, so kill_command reaches the whole tree
+            executor::detach_session(&mut process);
+            process.stdin(std::process::Stdio::null());
+        } else {
+            // Lets kill_command stop anything the script spawns, not just the shell
+            executor::isolate_process_group(&mut process);
+        }
+        if let Some(nice) = request.nice {
+            executor::set_priority(&mut process, nice);
+        }
+        if request.stdin_secret.is_some() {
+            // stderr is always read for sudo so a rejected password can be detected
+            process
+                .stdin(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped());
+        }
+        let mut child = process
+            .spawn()
+            .map_err(|e| executor::spawn_error(&request.invocation, &e))?;
+
+        if let Some(secret) = request.stdin_secret {
+            let fed = executor::feed_secret(&mut child, &secret);
+            drop(secret);
+            if let Err(e) = fed {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        }
+
+        on_spawn(child.id());
+        executor::wait_for_completion(child, start, request.output_lines)
+            .map_err(|e| format!("Failed to wait for command: {}", e))
+    }
+}
+
+/// Starts `request` without capturing output or waiting for it to finish.
+///
+/// The process gets its own session (see [`executor::detach_session`]) and null stdio,
+/// so it keeps running after climgr exits. A sudo password is still written to stdin.
+/// A background thread reaps the process when it exits. Returns its PID.
+pub fn spawn_detached(request: RunRequest) -> Result<u32, String> {
+    let mut process = request.invocation.to_command();
+    process
+        .envs(request.env.iter().cloned())
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    executor::detach_session(&mut process);
+    if let Some(nice) = request.nice {
+        executor::set_priority(&mut process, nice);
+    }
+    if request.stdin_secret.is_some() {
+        process.stdin(std::process::Stdio::piped());
+    }
+    let mut child = process
+        .spawn()
+        .map_err(|e| executor::spawn_error(&request.invocation, &e))?;
+
+    if let Some(secret) = request.stdin_secret {
+        let fed = executor::feed_secret(&mut child, &secret);
+        drop(secret);
+        if let Err(e) = fed {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+    }
+
+    let pid = child.id();
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => {
+            log::warn!("Detached process {} exited with {}", pid, status)
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("Failed to wait for detached process {}: {}", pid, e),
+    });
+    Ok(pid)
+}
+
+/// The combined result of running a command's script, or each of its steps.
+pub struct Execution {
+    /// Captured output of every script that ran, in order
+    pub output: Vec<u8>,
+    /// Exit status of the last script that ran
+    pub status: ExitStatus,
+    /// Raw stdout of the last script that ran, for `output_is_json`
+    pub stdout: Vec<u8>,
+    /// Raw stderr of the last script that ran, for detecting a rejected sudo password
+    pub stderr: Vec<u8>,
+    pub duration: Duration,
+    /// Highest peak memory across the scripts, where available
+    pub peak_memory_bytes: Option<u64>,
+    /// Per-step results; `None` for a plain `script` command
+    pub steps: Option<Vec<StepResult>>,
+}
+
+/// Runs a command to completion through `runner`.
+///
+/// A command with non-empty `steps` runs them in order and stops at the first one
+/// that exits non-zero, calling `on_step` as each finishes; otherwise `script` runs on
+/// its own. `on_spawn` is called with the PID of every process started. With an
+/// `output_filter`, only matching lines are kept in the output and each step's output;
+/// with `strip_ansi`, escape sequences are removed from both first. Captured lines are
+/// also sent to `output_lines` as they are read, unfiltered.
+pub fn execute(
+    runner: &dyn CommandRunner,
+    command: &Command,
+    sudo_password: Option<Zeroizing<String>>,
+    start: Instant,
+    output_lines: Option<SyncSender<String>>,
+    on_spawn: &mut dyn FnMut(u32),
+    on_step: &mut dyn FnMut(&StepResult),
+) -> Result<Execution, String> {
+    let filter = command
+        .output_filter
+        .as_deref()
+        .map(executor::output_filter)
+        .transpose()?;
+    let collect = |completed: &Completed, capture| {
+        let mut output = executor::collect_output(&completed.output, capture);
+        if command.strip_ansi.unwrap_or(false) {
+            output = executor::strip_ansi(&output);
+        }
+        match &filter {
+            Some(filter) => executor::filter_lines(&output, filter),
+            None => output,
+        }
+    };
+
+    let steps = command.steps.as_deref().filter(|steps| !steps.is_empty());
+    let Some(steps) = steps else {
+        let mut request = RunRequest::for_command(command, sudo_password)?;
+        request.output_lines = output_lines;
+        let capture = request.capture;
+        let completed = runner.run(request, start, on_spawn)?;
+        return Ok(Execution {
+            output: collect(&completed, capture),
+            status: completed.output.status,
+            stdout: completed.output.stdout,
+            stderr: completed.output.stderr,
+            duration: completed.duration,
+            peak_memory_bytes: completed.peak_memory_bytes,
+            steps: None,
+        });
+    };
+
+    let mut output = Vec::new();
+    let mut results = Vec::new();
+    let mut last = None;
+    let mut peak_memory_bytes = None;
+    for (index, step) in steps.iter().enumerate() {
+        let mut request = RunRequest::for_script(command, step, sudo_password.clone())?;
+        request.output_lines = output_lines.clone();
+        let capture = request.capture;
+        let completed = runner.run(request, start, on_spawn)?;
+
+        let step_output = collect(&completed, capture);
+        let result = StepResult {
+            index,
+            exit_code: completed.output.status.code(),
+            output: String::from_utf8_lossy(&step_output).to_string(),
+        };
+        on_step(&result);
+        output.extend(step_output);
+        results.push(result);
+        peak_memory_bytes = peak_memory_bytes.max(completed.peak_memory_bytes);
+
+        let failed = !command.is_success(completed.output.status.code());
+        last = Some(completed);
+        if failed {
+            break;
+        }
+    }
+
+    let last = last.expect("steps is non-empty, so at least one step ran");
+    Ok(Execution {
+        output,
+        status: last.output.status,
+        stdout: last.output.stdout,
+        stderr: last.output.stderr,
+        duration: start.elapsed(),
+        peak_memory_bytes,
+        steps: Some(results),
+    })
+}
+
+/// Runs several commands by ID, one after another or all at once.
+///
+/// Sequential runs keep the order of `ids`; with `fail_fast`, the first run `failed`
+/// reports on is the last one, and later IDs are left out of the results. Parallel runs
+/// each get their own thread and are all waited for, so `fail_fast` doesn't apply;
+/// results still come back in the order of `ids`.
+pub fn run_all<T: Send>(
+    ids: &[String],
+    parallel: bool,
+    fail_fast: bool,
+    run: impl Fn(&str) -> T + Sync,
+    failed: impl Fn(&T) -> bool,
+) -> Vec<(String, T)> {
+    if parallel {
+        let run = &run;
+        return std::thread::scope(|scope| {
+            let handles: Vec<_> = ids.iter().map(|id| scope.spawn(move || run(id))).collect();
+            ids.iter()
+                .cloned()
+                .zip(handles)
+                .map(|(id, handle)| match handle.join() {
+                    Ok(result) => (id, result),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                })
+                .collect()
+        });
+    }
+
+    let mut results = Vec::with_capacity(ids.len());
+    for id in ids {
+        let result = run(id);
+        let stop = fail_fast && failed(&result);
+        results.push((id.clone(), result));
+        if stop {
+            break;
+        }
+    }
+    results
+}
+
+/// Runs a macro's commands in order through `run`, combining their results.
+///
+/// Each command's output follows a `==> <name>` header line; a command that couldn't
+/// run contributes its error message instead. The first failure stops the macro unless
+/// `continue_on_error` is set, and gives the combined result its exit code; otherwise
+/// that is the last command's. Base64 output is included as it is.
+pub fn run_macro(
+    steps: &[&Command],
+    continue_on_error: bool,
+    mut run: impl FnMut(&Command) -> Result<ExecutionResult, AppError>,
+) -> ExecutionResult {
+    let mut output = String::new();
+    let mut results = Vec::with_capacity(steps.len());
+    let mut duration_ms = 0;
+    let mut exit_code = None;
+    let mut failed = false;
+    for (index, step) in steps.iter().enumerate() {
+        let (step_output, step_exit_code, succeeded) = match run(step) {
+            Ok(result) => {
+                duration_ms += result.duration_ms.unwrap_or(0);
+                let succeeded = result.succeeded();
+                (result.output, result.exit_code, succeeded)
+            }
+            Err(e) => (format!("{}\n", e), None, false),
+        };
+        output.push_str(&format!("==> {}\n", step.name));
+        output.push_str(&step_output);
+        if !output.ends_with('\n') {
+            output.push('\n');
+        }
+        results.push(StepResult {
+            index,
+            exit_code: step_exit_code,
+            output: step_output,
+        });
+
+        if !failed {
+            exit_code = step_exit_code;
+        }
+        if !succeeded {
+            failed = true;
+            if !continue_on_error {
+                break;
+            }
+        }
+    }
+    ExecutionResult {
+        duration_ms: Some(duration_ms),
+        steps: Some(results),
+        exit_code,
+        success: Some(!failed),
+        ..ExecutionResult::text(output)
+    }
+}
+
+/// Managed state holding the runner `run_command_script` executes through.
+pub struct Runner(pub Box<dyn CommandRunner>);
+
+impl Default for Runner {
+    fn default() -> Self {
+        Runner(Box::new(SystemRunner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Records what it was asked to run and reports a canned exit.
+    #[cfg(unix)]
+    struct MockRunner {
+        runs: std::sync::Mutex<Vec<(Invocation, CaptureMode, Option<String>)>>,
+        /// Exit code of each successive run; runs past the end exit 0
+        exit_codes: Vec<i32>,
+        stdout: Vec<u8>,
+    }
+
+    #[cfg(unix)]
+    impl CommandRunner for MockRunner {
+        fn run(
+            &self,
+            request: RunRequest,
+            start: Instant,
+            on_spawn: &mut dyn FnMut(u32),
+        ) -> Result<Completed, String> {
+            use std::os::unix::process::ExitStatusExt;
+
+            let mut runs = self.runs.lock().unwrap();
+            let exit_code = self.exit_codes.get(runs.len()).copied().unwrap_or(0);
+            runs.push((
+                request.invocation,
+                request.capture,
+                request.stdin_secret.map(|s| s.to_string()),
+            ));
+            on_spawn(4242);
+            Ok(Completed {
+                output: std::process::Output {
+                    // Wait status layout: the exit code sits in the second byte
+                    status: std::process::ExitStatus::from_raw(exit_code << 8),
+                    stdout: self.stdout.clone(),
+                    stderr: Vec::new(),
+                },
+                duration: start.elapsed(),
+                peak_memory_bytes: None,
+            })
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mock_runner_sees_assembled_invocation() {
+        let runner = MockRunner {
+            runs: std::sync::Mutex::new(Vec::new()),
+            exit_codes: vec![2],
+            stdout: b"mocked".to_vec(),
+        };
+        let command = Command {
+            id: "upgrade".to_string(),
+            script: "apt upgrade".to_string(),
+            requires_sudo: Some(true),
+            capture: Some(CaptureMode::StdoutOnly),
+            ..Default::default()
+        };
+
+        let request =
+            RunRequest::for_command(&command, Some(Zeroizing::new("hunter2".to_string()))).unwrap();
+        let mut pids = Vec::new();
+        let completed = runner
+            .run(request, Instant::now(), &mut |pid| pids.push(pid))
+            .unwrap();
+
+        assert_eq!(pids, vec![4242]);
+        assert_eq!(completed.output.status.code(), Some(2));
+        assert_eq!(completed.output.stdout, b"mocked");
+
+        let runs = runner.runs.lock().unwrap();
+        let (invocation, capture, secret) = &runs[0];
+        assert_eq!(invocation, &executor::shell_invocation("apt upgrade", true));
+        assert_eq!(*capture, CaptureMode::StdoutOnly);
+        assert_eq!(secret.as_deref(), Some("hunter2"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_steps_run_in_order_and_stop_at_first_failure() {
+        let runner = MockRunner {
+            runs: std::sync::Mutex::new(Vec::new()),
+            exit_codes: vec![0, 1, 0],
+            stdout: b"ok\n".to_vec(),
+        };
+        let command = Command {
+            steps: Some(vec![
+                "make".to_string(),
+                "make test".to_string(),
+                "make install".to_string(),
+            ]),
+            ..Default::default()
+        };
+
+        let mut seen = Vec::new();
+        let execution = execute(
+            &runner,
+            &command,
+            None,
+            Instant::now(),
+            None,
+            &mut |_| {},
+            &mut |step| seen.push(step.clone()),
+        )
+        .unwrap();
+
+        let runs = runner.runs.lock().unwrap();
+        let scripts: Vec<&str> = runs
+            .iter()
+            .map(|(invocation, _, _)| invocation.args.last().unwrap().as_str())
+            .collect();
+        assert_eq!(scripts, vec!["make", "make test"]);
+        assert_eq!(execution.status.code(), Some(1));
+        assert_eq!(execution.output, b"ok\nok\n");
+        assert_eq!(execution.steps.as_ref(), Some(&seen));
+        assert_eq!(
+            seen.iter()
+                .map(|s| (s.index, s.exit_code))
+                .collect::<Vec<_>>(),
+            vec![(0, Some(0)), (1, Some(1))]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_steps_continue_past_success_exit_codes() {
+        let runner = MockRunner {
+            runs: std::sync::Mutex::new(Vec::new()),
+            exit_codes: vec![1, 2, 0],
+            stdout: Vec::new(),
+        };
+        let command = Command {
+            steps: Some(vec![
+                "grep TODO src".to_string(),
+                "diff a b".to_string(),
+                "make".to_string(),
+            ]),
+            success_exit_codes: Some(vec![0, 1]),
+            ..Default::default()
+        };
+
+        let execution = execute(
+            &runner,
+            &command,
+            None,
+            Instant::now(),
+            None,
+            &mut |_| {},
+            &mut |_| {},
+        )
+        .unwrap();
+
+        // grep's 1 is fine, diff's 2 is not
+        assert_eq!(runner.runs.lock().unwrap().len(), 2);
+        assert_eq!(execution.status.code(), Some(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_script_runs_once_without_steps() {
+        let runner = MockRunner {
+            runs: std::sync::Mutex::new(Vec::new()),
+            exit_codes: vec![],
+            stdout: b"done".to_vec(),
+        };
+        let command = Command {
+            script: "echo done".to_string(),
+            steps: Some(vec![]),
+            ..Default::default()
+        };
+
+        let mut steps_seen = 0;
+        let execution = execute(
+            &runner,
+            &command,
+            None,
+            Instant::now(),
+            None,
+            &mut |_| {},
+            &mut |_| steps_seen += 1,
+        )
+        .unwrap();
+
+        assert_eq!(runner.runs.lock().unwrap().len(), 1);
+        assert_eq!(steps_seen, 0);
+        assert_eq!(execution.steps, None);
+        assert_eq!(execution.output, b"done");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_filter_keeps_matching_lines() {
+        let runner = MockRunner {
+            runs: std::sync::Mutex::new(Vec::new()),
+            exit_codes: vec![],
+            stdout: b"pulling\nerror: timeout\ndone\n".to_vec(),
+        };
+        let command = Command {
+            steps: Some(vec!["fetch".to_string(), "fetch --retry".to_string()]),
+            output_filter: Some("^error".to_string()),
+            ..Default::default()
+        };
+
+        let execution = execute(
+            &runner,
+            &command,
+            None,
+            Instant::now(),
+            None,
+            &mut |_| {},
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(execution.output, b"error: timeout\nerror: timeout\n");
+        let steps = execution.steps.unwrap();
+        assert_eq!(steps[0].output, "error: timeout\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_strip_ansi_runs_before_the_output_filter() {
+        let runner = MockRunner {
+            runs: std::sync::Mutex::new(Vec::new()),
+            exit_codes: vec![],
+            stdout: b"\x1b[31merror\x1b[0m: timeout\n\x1b[32mok\x1b[0m\n".to_vec(),
+        };
+        let command = Command {
+            output_filter: Some("^error".to_string()),
+            strip_ansi: Some(true),
+            ..Default::default()
+        };
+
+        let execution = execute(
+            &runner,
+            &command,
+            None,
+            Instant::now(),
+            None,
+            &mut |_| {},
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(execution.output, b"error: timeout\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_detached_command_returns_immediately() {
+        let command = Command {
+            script: "sleep 100".to_string(),
+            detached: Some(true),
+            ..Default::default()
+        };
+        let request = RunRequest::for_command(&command, None).unwrap();
+
+        let start = Instant::now();
+        let pid = spawn_detached(request).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // It runs in its own session, which it leads
+        let session = unsafe { libc::getsid(pid as libc::pid_t) };
+        assert_eq!(session, pid as libc::pid_t);
+
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+    }
+
+    #[test]
+    fn test_request_requires_sudo_password() {
+        let sudo = Command {
+            script: "apt update".to_string(),
+            requires_sudo: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            RunRequest::for_command(&sudo, None).err(),
+            Some("Command requires a sudo password".to_string())
+        );
+
+        // A password is dropped for commands that don't need it
+        let plain = Command {
+            script: "ls".to_string(),
+            ..Default::default()
+        };
+        let request =
+            RunRequest::for_command(&plain, Some(Zeroizing::new("unused".to_string()))).unwrap();
+        assert!(request.stdin_secret.is_none());
+        assert_eq!(request.invocation, executor::shell_invocation("ls", false));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_system_runner_spawns_the_script() {
+        let command = Command {
+            script: "echo real".to_string(),
+            ..Default::default()
+        };
+        let request = RunRequest::for_command(&command, None).unwrap();
+        let mut spawned = None;
+        let completed = SystemRunner
+            .run(request, Instant::now(), &mut |pid| spawned = Some(pid))
+            .unwrap();
+
+        assert!(spawned.is_some());
+        assert!(completed.output.status.success());
+        assert_eq!(completed.output.stdout, b"real\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_system_runner_sets_the_command_env() {
+        let command = Command {
+            script: "echo \"$API_URL $REGION\"".to_string(),
+            env: Some(BTreeMap::from([
+                ("API_URL".to_string(), "https://api.example.com".to_string()),
+                ("REGION".to_string(), "eu-west-1".to_string()),
+            ])),
+            ..Default::default()
+        };
+        let request = RunRequest::for_command(&command, None).unwrap();
+        let completed = SystemRunner
+            .run(request, Instant::now(), &mut |_| {})
+            .unwrap();
+        assert_eq!(
+            completed.output.stdout,
+            b"https://api.example.com eu-west-1\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_interactive_shell_runs_without_a_terminal() {
+        let command = Command {
+            script: "echo ran".to_string(),
+            login_shell: Some(true),
+            interactive_shell: Some(true),
+            ..Default::default()
+        };
+        let request = RunRequest::for_command(&command, None).unwrap();
+        assert_eq!(request.invocation.args, vec!["-l", "-i", "-c", "echo ran"]);
+        assert!(request.interactive);
+
+        // Finishes instead of stopping to wait for a terminal
+        let completed = SystemRunner
+            .run(request, Instant::now(), &mut |_| {})
+            .unwrap();
+        assert!(completed.output.status.success());
+        assert!(String::from_utf8_lossy(&completed.output.stdout).ends_with("ran\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_system_runner_applies_niceness() {
+        // Lowering priority to the minimum is always permitted
+        let command = Command {
+            script: "nice".to_string(),
+            nice: Some(19),
+            ..Default::default()
+        };
+        let request = RunRequest::for_command(&command, None).unwrap();
+        let completed = SystemRunner
+            .run(request, Instant::now(), &mut |_| {})
+            .unwrap();
+
+        assert_eq!(completed.output.stdout, b"19\n");
+    }
+
+    #[test]
+    fn test_run_all_sequential_stops_at_failure_with_fail_fast() {
+        let ids: Vec<String> = ["a", "bad", "c"].iter().map(|s| s.to_string()).collect();
+        let ran = std::sync::Mutex::new(Vec::new());
+        let run = |id: &str| {
+            ran.lock().unwrap().push(id.to_string());
+            if id == "bad" {
+                Err(format!("{} failed", id))
+            } else {
+                Ok(id.len())
+            }
+        };
+
+        let results = run_all(&ids, false, true, run, Result::is_err);
+        assert_eq!(
+            results,
+            vec![
+                ("a".to_string(), Ok(1)),
+                ("bad".to_string(), Err("bad failed".to_string())),
+            ]
+        );
+        assert_eq!(*ran.lock().unwrap(), vec!["a", "bad"]);
+
+        // Without fail_fast every command runs, in order
+        ran.lock().unwrap().clear();
+        let results = run_all(&ids, false, false, run, Result::is_err);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2], ("c".to_string(), Ok(1)));
+        assert_eq!(*ran.lock().unwrap(), vec!["a", "bad", "c"]);
+    }
+
+    #[test]
+    fn test_run_all_parallel_runs_concurrently_in_id_order() {
+        let ids: Vec<String> = ["slow", "bad", "fast"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let started = Instant::now();
+        let results = run_all(
+            &ids,
+            true,
+            true,
+            |id| {
+                let millis = match id {
+                    "slow" => 300,
+                    _ => 200,
+                };
+                std::thread::sleep(Duration::from_millis(millis));
+                id != "bad"
+            },
+            |ok| !ok,
+        );
+
+        // All three ran, overlapping, despite the failure and fail_fast
+        assert!(started.elapsed() < Duration::from_millis(600));
+        assert_eq!(
+            results,
+            vec![
+                ("slow".to_string(), true),
+                ("bad".to_string(), false),
+                ("fast".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_macro_stops_at_the_first_failure() {
+        let step = |id: &str| Command {
+            id: id.to_string(),
+            name: id.to_uppercase(),
+            ..Default::default()
+        };
+        let (fetch, build, deploy) = (step("fetch"), step("build"), step("deploy"));
+        let steps = [&fetch, &build, &deploy];
+        let run = |command: &Command| match command.id.as_str() {
+            "build" => Ok(ExecutionResult {
+                exit_code: Some(2),
+                duration_ms: Some(5),
+                ..ExecutionResult::text("compile error")
+            }),
+            "deploy" => Err(AppError::ReadOnly),
+            _ => Ok(ExecutionResult {
+                exit_code: Some(0),
+                duration_ms: Some(10),
+                ..ExecutionResult::text("fetched\n")
+            }),
+        };
+
+        let result = run_macro(&steps, false, run);
+        assert_eq!(
+            result.output,
+            "==> FETCH\nfetched\n==> BUILD\ncompile error\n"
+        );
+        assert_eq!(result.exit_code, Some(2));
+        assert_eq!(result.success, Some(false));
+        assert_eq!(result.duration_ms, Some(15));
+        assert_eq!(result.steps.as_ref().unwrap().len(), 2);
+
+        let result = run_macro(&steps, true, run);
+        let ran: Vec<(usize, Option<i32>)> = result
+            .steps
+            .unwrap()
+            .iter()
+            .map(|s| (s.index, s.exit_code))
+            .collect();
+        assert_eq!(ran, vec![(0, Some(0)), (1, Some(2)), (2, None)]);
+        assert!(result
+            .output
+            .ends_with("==> DEPLOY\nApp is in read-only mode\n"));
+        assert_eq!(result.exit_code, Some(2));
+        assert_eq!(result.success, Some(false));
+
+        let passing = run_macro(&[&fetch, &fetch], false, run);
+        assert_eq!(passing.success, Some(true));
+        assert_eq!(passing.exit_code, Some(0));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::models::Command;
+use crate::store;
+use chrono::{DateTime, Local};
+use croner::Cron;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// How often the scheduler checks for due commands; runs fire within one tick of their time.
+pub const SCHEDULER_TICK: Duration = Duration::from_secs(5);
+
+/// A parsed `schedule` expression.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// `every 5m` / `@every 90s`: a fixed interval (units `s`, `m`, `h`, `d`)
+    Interval(chrono::Duration),
+    /// A five-field cron expression (or nickname such as `@hourly`), in local time
+    Cron(Box<Cron>),
+}
+
+impl Schedule {
+    /// Parses an interval (`every 10m`) or a cron expression (`*/15 * * * *`).
+    pub fn parse(expr: &str) -> Result<Schedule, String> {
+        let expr = expr.trim();
+        let interval = expr
+            .strip_prefix("@every")
+            .or_else(|| expr.strip_prefix("every"));
+        if let Some(interval) = interval {
+            return parse_interval(interval.trim()).map(Schedule::Interval);
+        }
+
+        Cron::new(expr)
+            .parse()
+            .map(|cron| Schedule::Cron(Box::new(cron)))
+            .map_err(|e| format!("Invalid schedule '{}': {}", expr, e))
+    }
+
+    /// The first run strictly after `after`, or `None` if the schedule never fires again.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Schedule::Interval(interval) => after.checked_add_signed(*interval),
+            Schedule::Cron(cron) => cron.find_next_occurrence(&after, false).ok(),
+        }
+    }
+}
+
+fn parse_interval(value: &str) -> Result<chrono::Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid interval '{}': expected a number and unit, e.g. 30s, 5m, 2h or 1d",
+            value
+        )
+    };
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    if amount <= 0 {
+        return Err(format!("Invalid interval '{}': must be positive", value));
+    }
+
+    match unit.trim() {
+        "s" => Ok(chrono::Duration::seconds(amount)),
+        "m" => Ok(chrono::Duration::minutes(amount)),
+        "h" => Ok(chrono::Duration::hours(amount)),
+        "d" => Ok(chrono::Duration::days(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Whether a command has a schedule that should currently fire.
+///
+/// A schedule is enabled unless `schedule_enabled` is explicitly `false`.
+pub fn is_scheduled(command: &Command) -> bool {
+    command
+        .schedule
+        .as_deref()
+        .is_some_and(|s| !s.trim().is_empty())
+        && command.schedule_enabled.unwrap_or(true)
+}
+
+/// Upcoming run of one scheduled command.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduledRun {
+    pub command_id: String,
+    /// The expression `next_run_at` was computed from, so edits are picked up
+    pub schedule: String,
+    /// RFC 3339 timestamp of the next run
+    pub next_run_at: DateTime<Local>,
+}
+
+/// Managed state with the next run time of every scheduled command.
+pub struct Scheduler {
+    runs: Mutex<HashMap<String, ScheduledRun>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            runs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Upcoming runs, soonest first.
+    pub fn upcoming(&self) -> Vec<ScheduledRun> {
+        let mut runs: Vec<ScheduledRun> = self
+            .runs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .cloned()
+            .collect();
+        runs.sort_by_key(|r| r.next_run_at);
+        runs
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Brings `runs` in line with `commands` and returns the commands due at `now`.
+///
+/// New or edited schedules are planned from `now`, unscheduled or disabled commands are
+/// dropped, and every due command is rescheduled for its next run. Invalid expressions
+/// are logged and skipped.
+pub fn take_due(
+    runs: &mut HashMap<String, ScheduledRun>,
+    commands: &[Command],
+    now: DateTime<Local>,
+) -> Vec<Command> {
+    runs.retain(|id, _| commands.iter().any(|c| &c.id == id && is_scheduled(c)));
+
+    let mut due = Vec::new();
+    for command in commands.iter().filter(|c| is_scheduled(c)) {
+        let expr = command.schedule.as_deref().unwrap_or_default();
+        let schedule = match Schedule::parse(expr) {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                log::warn!("Not scheduling command {}: {}", command.id, e);
+                runs.remove(&command.id);
+                continue;
+            }
+        };
+
+        let planned = runs
+            .get(&command.id)
+            .filter(|r| r.schedule == expr)
+            .map(|r| r.next_run_at);
+        let next_run_at = match planned {
+            Some(at) if at <= now => {
+                due.push(command.clone());
+                schedule.next_after(now)
+            }
+            Some(at) => Some(at),
+            None => schedule.next_after(now),
+        };
+
+        match next_run_at {
+            Some(next_run_at) => {
+                runs.insert(
+                    command.id.clone(),
+                    ScheduledRun {
+                        command_id: command.id.clone(),
+                        schedule: expr.to_string(),
+                        next_run_at,
+                    },
+                );
+            }
+            None => {
+                runs.remove(&command.id);
+            }
+        }
+    }
+    due
+}
+
+/// Spawns the scheduler thread.
+///
+/// Due commands run on their own thread through `run_command_script`, so a slow script
+/// doesn't delay others. Runs are skipped while safe mode is on or while the previous
+/// run of the same command is still going.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SCHEDULER_TICK);
+        tick(&app);
+    });
+}
+
+fn tick(app: &AppHandle) {
+    let commands = match crate::get_store_path(app).and_then(|path| crate::load_store(app, &path)) {
+        Ok(commands) => commands,
+        Err(e) => {
+            log::warn!("Scheduler could not read the store: {}", e);
+            return;
+        }
+    };
+
+    let due = {
+        let scheduler = app.state::<Scheduler>();
+        let mut runs = scheduler
+            .runs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        take_due(&mut runs, &commands, Local::now())
+    };
+    if due.is_empty() {
+        return;
+    }
+
+    let safe_mode = crate::get_config_path(app)
+        .and_then(|path| store::get_config(&path))
+        .map(|config| config.safe_mode)
+        .unwrap_or(true);
+    if safe_mode {
+        log::info!("Safe mode is on; skipping {} scheduled run(s)", due.len());
+        return;
+    }
+
+    for command in due {
+        let running = app
+            .state::<crate::ProcessManager>()
+            .processes()
+            .contains_key(&command.id);
+        if running {
+            log::info!("Skipping scheduled run of {}: still running", command.id);
+            continue;
+        }
+
+        let app = app.clone();
+        std::thread::spawn(move || {
+            log::info!("Running scheduled command {}", command.id);
+            if let Err(e) = crate::run_command_script(&app, &command, None, HashMap::new(), None) {
+                log::error!("Scheduled run of {} failed: {}", command.id, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32, second: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 1, 15, hour, minute, second)
+            .unwrap()
+    }
+
+    fn scheduled(id: &str, schedule: &str) -> Command {
+        Command {
+            id: id.to_string(),
+            name: format!("Command {}", id),
+            script: "true".to_string(),
+            schedule: Some(schedule.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let every = Schedule::parse("every 5m").unwrap();
+        assert_eq!(every.next_after(at(9, 0, 0)), Some(at(9, 5, 0)));
+
+        let every = Schedule::parse("@every 90s").unwrap();
+        assert_eq!(every.next_after(at(9, 0, 0)), Some(at(9, 1, 30)));
+
+        let cron = Schedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(cron.next_after(at(9, 7, 12)), Some(at(9, 15, 0)));
+        // Strictly after: a run exactly on the boundary schedules the next one
+        assert_eq!(cron.next_after(at(9, 15, 0)), Some(at(9, 30, 0)));
+
+        for bad in ["every", "every 0m", "every 5w", "every m", "not a cron"] {
+            assert!(Schedule::parse(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_take_due() {
+        let mut runs = HashMap::new();
+        let mut disabled = scheduled("off", "every 1m");
+        disabled.schedule_enabled = Some(false);
+        let commands = vec![
+            scheduled("a", "every 1m"),
+            scheduled("b", "every 10m"),
+            disabled,
+        ];
+
+        // The first pass only plans
+        assert!(take_due(&mut runs, &commands, at(9, 0, 0)).is_empty());
+        assert_eq!(runs.len(), 2);
+        assert!(!runs.contains_key("off"));
+
+        let due = take_due(&mut runs, &commands, at(9, 1, 2));
+        let ids: Vec<&str> = due.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a"]);
+        assert_eq!(runs["a"].next_run_at, at(9, 2, 2));
+        assert_eq!(runs["b"].next_run_at, at(9, 10, 0));
+
+        // Editing the schedule re-plans instead of firing on the stale time
+        let edited = vec![scheduled("a", "every 1h"), scheduled("b", "every 10m")];
+        assert!(take_due(&mut runs, &edited, at(9, 5, 0)).is_empty());
+        assert_eq!(runs["a"].next_run_at, at(10, 5, 0));
+
+        // Removing the schedule drops the planned run
+        let removed = vec![scheduled("b", "every 10m")];
+        take_due(&mut runs, &removed, at(9, 6, 0));
+        assert!(!runs.contains_key("a"));
+    }
+}
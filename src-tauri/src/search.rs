@@ -0,0 +1,207 @@
+use crate::models::Command;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::Serialize;
+
+/// Penalty applied to matches that only succeed after correcting one query character.
+const TYPO_PENALTY: i64 = 40;
+
+/// A command returned from a fuzzy search together with its match score.
+#[derive(Debug, Serialize, Clone)]
+pub struct ScoredCommand {
+    pub command: Command,
+    pub score: i64,
+}
+
+/// Case-insensitive substring search over a command's name, description and script.
+///
+/// An empty (or whitespace-only) query matches every command. Results keep the stored order.
+pub fn search_commands(commands: &[Command], query: &str) -> Vec<Command> {
+    let needle = query.trim().to_lowercase();
+    if needle.is_empty() {
+        return commands.to_vec();
+    }
+
+    commands
+        .iter()
+        .filter(|c| {
+            c.name.to_lowercase().contains(&needle)
+                || c.script.to_lowercase().contains(&needle)
+                || c.description
+                    .as_deref()
+                    .is_some_and(|d| d.to_lowercase().contains(&needle))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Fuzzy search over command names, sorted by descending score.
+///
+/// Uses a subsequence scorer, so abbreviations like `grc` match `git rebase --continue`.
+/// Names that don't match get a typo check, scored at a fixed penalty: one wrong
+/// initial (`grd` still finds `git rebase --continue`), or for queries of 4 or more
+/// characters one extra character (`dokcer` finds `docker ps`).
+/// If nothing scores, falls back to [`search_commands`] with a score of 0.
+pub fn fuzzy_search_commands(commands: &[Command], query: &str) -> Vec<ScoredCommand> {
+    let pattern = query.trim();
+    if pattern.is_empty() {
+        return commands
+            .iter()
+            .cloned()
+            .map(|command| ScoredCommand { command, score: 0 })
+            .collect();
+    }
+
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let mut results: Vec<ScoredCommand> = commands
+        .iter()
+        .filter_map(|c| {
+            fuzzy_score(&matcher, &c.name, pattern).map(|score| ScoredCommand {
+                command: c.clone(),
+                score,
+            })
+        })
+        .collect();
+
+    if results.is_empty() {
+        return search_commands(commands, pattern)
+            .into_iter()
+            .map(|command| ScoredCommand { command, score: 0 })
+            .collect();
+    }
+
+    // Stable sort keeps stored order for equal scores
+    results.sort_by_key(|r| std::cmp::Reverse(r.score));
+    results
+}
+
+fn fuzzy_score(matcher: &SkimMatcherV2, choice: &str, pattern: &str) -> Option<i64> {
+    if let Some(score) = matcher.fuzzy_match(choice, pattern) {
+        return Some(score);
+    }
+
+    let chars: Vec<char> = pattern.to_lowercase().chars().collect();
+    if chars.len() < 3 {
+        return None;
+    }
+
+    // Typo candidates: the query with one character corrected against the initials of
+    // the name's words (`grd` -> `grc`), plus, for longer queries, the query with one
+    // character dropped. Dropping one of 3 characters would match nearly any name.
+    let mut candidates: Vec<String> = Vec::new();
+    if chars.len() >= 4 {
+        candidates.extend((0..chars.len()).map(|skip| {
+            chars
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != skip)
+                .map(|(_, c)| c)
+                .collect::<String>()
+        }));
+    }
+
+    let initials: Vec<char> = choice
+        .split(|c: char| c.is_whitespace() || "-_/.:".contains(c))
+        .filter_map(|word| word.chars().next())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if initials.len() >= chars.len() {
+        let mismatches = chars.iter().zip(&initials).filter(|(a, b)| a != b).count();
+        if mismatches == 1 {
+            candidates.push(initials[..chars.len()].iter().collect());
+        }
+    }
+
+    candidates
+        .iter()
+        .filter_map(|candidate| matcher.fuzzy_match(choice, candidate))
+        .max()
+        .map(|score| (score - TYPO_PENALTY).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(id: &str, name: &str, script: &str) -> Command {
+        Command {
+            id: id.to_string(),
+            name: name.to_string(),
+            script: script.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_substring_search() {
+        let commands = vec![
+            command("1", "List Files", "ls -la"),
+            command("2", "Disk Usage", "du -sh ."),
+        ];
+
+        let results = search_commands(&commands, "files");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "1");
+
+        // Script contents are searched too
+        let results = search_commands(&commands, "DU -SH");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "2");
+
+        assert_eq!(search_commands(&commands, "  ").len(), 2);
+    }
+
+    #[test]
+    fn test_fuzzy_search_ranks_abbreviation() {
+        let commands = vec![
+            command("weak", "golang release --clean-cache", "go clean -cache"),
+            command("strong", "git rebase --continue", "git rebase --continue"),
+            command("none", "List Files", "ls -la"),
+        ];
+
+        let results = fuzzy_search_commands(&commands, "grd");
+        let ids: Vec<&str> = results.iter().map(|r| r.command.id.as_str()).collect();
+
+        assert!(
+            ids.contains(&"strong"),
+            "grd should match git rebase --continue"
+        );
+        assert!(!ids.contains(&"none"));
+        assert_eq!(ids[0], "strong");
+        assert!(results[0].score > results[1].score);
+    }
+
+    #[test]
+    fn test_fuzzy_search_short_typo_needs_matching_initials() {
+        let commands = vec![
+            command("rebase", "git rebase --continue", "git rebase --continue"),
+            command(
+                "unrelated",
+                "grep logs for errors",
+                "grep -r error /var/log",
+            ),
+        ];
+
+        // `grd` shares 2 of 3 characters with both names, but only the initials of
+        // `git rebase --continue` are one character off
+        let results = fuzzy_search_commands(&commands, "grd");
+        let ids: Vec<&str> = results.iter().map(|r| r.command.id.as_str()).collect();
+        assert_eq!(ids, vec!["rebase"]);
+
+        // Longer queries also tolerate one extra character
+        let commands = vec![command("1", "docker ps", "docker ps")];
+        let results = fuzzy_search_commands(&commands, "dokcer");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].score > 0);
+    }
+
+    #[test]
+    fn test_fuzzy_search_falls_back_to_substring() {
+        let commands = vec![command("1", "Deploy", "kubectl apply -f prod.yaml")];
+
+        // No fuzzy match on the name, but the script contains the query
+        let results = fuzzy_search_commands(&commands, "kubectl");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].score, 0);
+    }
+}
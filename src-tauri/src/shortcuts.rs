@@ -0,0 +1,502 @@
+use crate::error::AppError;
+use crate::models::Command;
+use serde::Serialize;
+use tauri_plugin_global_shortcut::Shortcut;
+
+/// A command whose shortcut couldn't be parsed or registered.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShortcutError {
+    pub command_id: String,
+    /// The shortcut as stored on the command
+    pub shortcut: String,
+    pub message: String,
+}
+
+/// `command_id` of the [`ShortcutError`] reported when `Config.panic_shortcut` can't be
+/// registered.
+pub const PANIC_SHORTCUT_ID: &str = "panic_shortcut";
+
+/// The primary modifier for this platform, as shown to users: `Cmd` on macOS, `Ctrl` elsewhere.
+pub fn platform_modifier() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "Cmd"
+    } else {
+        "Ctrl"
+    }
+}
+
+/// Rewrites macOS-style modifier aliases so a synced shortcut registers on every OS.
+///
+/// `Cmd`, `Command` and `Meta` become [`platform_modifier`], so `Cmd+L` typed on a Mac
+/// registers as `Ctrl+L` on Linux and Windows. `Super` is left alone, since on those
+/// platforms it is a real key (the Windows key) users bind on purpose. Modifiers that
+/// collapse into duplicates (`Cmd+Ctrl+L` off macOS) are emitted once.
+pub fn normalize_platform_modifiers(shortcut: &str) -> String {
+    let mut tokens: Vec<String> = Vec::new();
+    for raw in shortcut.split('+') {
+        let token = raw.trim();
+        let token = match token.to_ascii_lowercase().as_str() {
+            "cmd" | "command" | "meta" => platform_modifier().to_string(),
+            "control" if !cfg!(target_os = "macos") => "Ctrl".to_string(),
+            _ => token.to_string(),
+        };
+        if !tokens.iter().any(|t| t.eq_ignore_ascii_case(&token)) {
+            tokens.push(token);
+        }
+    }
+    tokens.join("+")
+}
+
+/// Parses a shortcut string, after [`normalize_platform_modifiers`], for registration.
+pub fn parse_shortcut(shortcut: &str) -> Result<Shortcut, AppError> {
+    normalize_platform_modifiers(shortcut)
+        .parse::<Shortcut>()
+        .map_err(|e| AppError::ShortcutInvalid(format!("Invalid shortcut '{}': {}", shortcut, e)))
+}
+
+/// Canonical form of a shortcut, the same string the plugin reports for a pressed one.
+///
+/// Modifier aliases and casing are resolved and modifiers are put in a fixed order, so
+/// `cmd+L`, `Cmd+l` and `Shift+Ctrl+L` / `ctrl+shift+l` each collapse to one form
+/// (`shift+control+KeyL` style, as `Shortcut::to_string` emits). A shortcut that
+/// doesn't parse is only trimmed and lowercased.
+pub fn normalize_shortcut(shortcut: &str) -> String {
+    match parse_shortcut(shortcut) {
+        Ok(parsed) => parsed.to_string(),
+        Err(_) => shortcut.trim().to_ascii_lowercase(),
+    }
+}
+
+/// Rewrites `command`'s shortcut to one spelling before it is stored, so one binding
+/// is always persisted the same way.
+///
+/// Casing and modifier order are fixed as in [`normalize_shortcut`], but `Cmd`,
+/// `Command` and `Meta` are kept as a leading `cmd` instead of being resolved to this
+/// platform's modifier, so a store synced to another OS still means its primary
+/// modifier there (`Cmd+Shift+L` is stored as `cmd+shift+KeyL`). Blank shortcuts and
+/// ones that don't parse are kept as given; the latter are reported when shortcuts are
+/// registered.
+pub fn canonicalize(command: &mut Command) {
+    let Some(shortcut) = command.shortcut.as_mut() else {
+        return;
+    };
+    if let Some(canonical) = canonical_form(shortcut) {
+        *shortcut = canonical;
+    }
+}
+
+/// The stored form of `shortcut` (see [`canonicalize`]), `None` if it doesn't parse.
+fn canonical_form(shortcut: &str) -> Option<String> {
+    let is_alias = |token: &str| {
+        matches!(
+            token.trim().to_ascii_lowercase().as_str(),
+            "cmd" | "command" | "meta"
+        )
+    };
+    let tokens: Vec<&str> = shortcut.split('+').collect();
+    let rest: Vec<&str> = tokens.iter().copied().filter(|t| !is_alias(t)).collect();
+    let parsed = rest.join("+").parse::<Shortcut>().ok()?.to_string();
+    if rest.len() == tokens.len() {
+        return Some(parsed);
+    }
+    // The alias has to resolve to a modifier this platform understands
+    parse_shortcut(shortcut).ok()?;
+    Some(format!("cmd+{}", parsed))
+}
+
+/// The command bound to a pressed shortcut, given as `Shortcut::to_string`.
+pub fn find_command<'a>(commands: &'a [Command], pressed: &str) -> Option<&'a Command> {
+    let pressed = normalize_shortcut(pressed);
+    commands.iter().find(|c| {
+        c.shortcut
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .is_some_and(|s| normalize_shortcut(s) == pressed)
+    })
+}
+
+/// Whether the pressed shortcut, given as `Shortcut::to_string`, is the panic shortcut.
+pub fn is_panic_shortcut(pressed: &str, panic: Option<&str>) -> bool {
+    panic
+        .filter(|s| !s.trim().is_empty())
+        .is_some_and(|s| normalize_shortcut(s) == normalize_shortcut(pressed))
+}
+
+/// Rejects `command`'s shortcut if another command is already bound to it.
+///
+/// Shortcuts are compared by [`normalize_shortcut`], so `Cmd+l` clashes with `cmd+L`.
+pub fn check_available(commands: &[Command], command: &Command) -> Result<(), AppError> {
+    let Some(shortcut) = command.shortcut.as_deref().filter(|s| !s.trim().is_empty()) else {
+        return Ok(());
+    };
+    let key = normalize_shortcut(shortcut);
+    let taken = commands.iter().find(|c| {
+        c.id != command.id
+            && c.shortcut
+                .as_deref()
+                .is_some_and(|s| normalize_shortcut(s) == key)
+    });
+    match taken {
+        Some(other) => Err(AppError::ShortcutInvalid(format!(
+            "Shortcut '{}' is already bound to '{}'",
+            shortcut, other.name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Accelerators the OS keeps for itself on this platform, such as quitting or
+/// switching apps; binding one would break it.
+pub fn reserved_shortcuts() -> &'static [&'static str] {
+    if cfg!(target_os = "macos") {
+        &[
+            "Cmd+Q",
+            "Cmd+W",
+            "Cmd+H",
+            "Cmd+M",
+            "Cmd+Tab",
+            "Cmd+Space",
+            "Ctrl+Space",
+            "Cmd+Alt+Escape",
+            "Ctrl+Cmd+Q",
+            "Cmd+Shift+3",
+            "Cmd+Shift+4",
+            "Cmd+Shift+5",
+        ]
+    } else if cfg!(target_os = "windows") {
+        &[
+            "Alt+F4",
+            "Alt+Tab",
+            "Ctrl+Alt+Delete",
+            "Ctrl+Shift+Escape",
+            "Ctrl+Escape",
+            "Super+L",
+            "Super+D",
+            "Super+Tab",
+        ]
+    } else {
+        &[
+            "Alt+F2",
+            "Alt+F4",
+            "Alt+Tab",
+            "Ctrl+Alt+Delete",
+            "Ctrl+Alt+Backspace",
+            "Super+L",
+        ]
+    }
+}
+
+/// Whether `shortcut` is one of this platform's [`reserved_shortcuts`].
+pub fn is_reserved_shortcut(shortcut: &str) -> bool {
+    let key = normalize_shortcut(shortcut);
+    reserved_shortcuts()
+        .iter()
+        .any(|reserved| normalize_shortcut(reserved) == key)
+}
+
+/// Rejects `command`'s shortcut if it is reserved, unless it is listed in `allowed`
+/// (the config's `reserved_shortcut_overrides`).
+pub fn check_not_reserved(command: &Command, allowed: &[String]) -> Result<(), AppError> {
+    let Some(shortcut) = command.shortcut.as_deref().filter(|s| !s.trim().is_empty()) else {
+        return Ok(());
+    };
+    if !is_reserved_shortcut(shortcut) {
+        return Ok(());
+    }
+    let key = normalize_shortcut(shortcut);
+    if allowed.iter().any(|a| normalize_shortcut(a) == key) {
+        return Ok(());
+    }
+    Err(AppError::ShortcutInvalid(format!(
+        "Shortcut '{}' is reserved by the system; add it to reserved_shortcut_overrides to use it anyway",
+        shortcut
+    )))
+}
+
+/// Registers the `panic` shortcut, then every command's shortcut, through `register`,
+/// continuing past failures.
+///
+/// The panic shortcut goes first so no command can take it; a command bound to it
+/// isn't registered. Blank shortcuts are skipped. Returns one [`ShortcutError`] per
+/// shortcut that didn't parse or that `register` rejected, so a single bad accelerator
+/// doesn't stop the rest from binding; a failing panic shortcut is reported under
+/// [`PANIC_SHORTCUT_ID`].
+pub fn register_all(
+    commands: &[Command],
+    panic: Option<&str>,
+    mut register: impl FnMut(Shortcut) -> Result<(), String>,
+) -> Vec<ShortcutError> {
+    let mut errors = Vec::new();
+    if let Some(panic) = panic.filter(|s| !s.trim().is_empty()) {
+        let registered = parse_shortcut(panic)
+            .and_then(|parsed| register(parsed).map_err(AppError::ShortcutInvalid));
+        if let Err(e) = registered {
+            errors.push(ShortcutError {
+                command_id: PANIC_SHORTCUT_ID.to_string(),
+                shortcut: panic.to_string(),
+                message: e.to_string(),
+            });
+        }
+    }
+    for command in commands {
+        let Some(shortcut) = command.shortcut.as_deref() else {
+            continue;
+        };
+        if shortcut.trim().is_empty() {
+            continue;
+        }
+        let registered = if is_panic_shortcut(shortcut, panic) {
+            Err(AppError::ShortcutInvalid(format!(
+                "Shortcut '{}' is the panic shortcut, which stops all running commands",
+                shortcut
+            )))
+        } else {
+            parse_shortcut(shortcut)
+                .and_then(|parsed| register(parsed).map_err(AppError::ShortcutInvalid))
+        };
+        if let Err(e) = registered {
+            errors.push(ShortcutError {
+                command_id: command.id.clone(),
+                shortcut: shortcut.to_string(),
+                message: e.to_string(),
+            });
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_platform_modifiers() {
+        let m = platform_modifier();
+
+        assert_eq!(normalize_platform_modifiers("Cmd+L"), format!("{}+L", m));
+        assert_eq!(
+            normalize_platform_modifiers("meta + Shift + K"),
+            format!("{}+Shift+K", m)
+        );
+        assert_eq!(normalize_platform_modifiers("Alt+F4"), "Alt+F4");
+        assert_eq!(normalize_platform_modifiers("Super+L"), "Super+L");
+    }
+
+    #[test]
+    fn test_parse_shortcut() {
+        assert!(parse_shortcut("Cmd+Shift+K").is_ok());
+        assert!(parse_shortcut("Alt+F4").is_ok());
+
+        let err = parse_shortcut("Ctrl+Banana").unwrap_err();
+        assert_eq!(err.code(), "shortcut_invalid");
+        assert!(err.to_string().contains("Ctrl+Banana"), "{}", err);
+    }
+
+    #[test]
+    fn test_normalize_shortcut() {
+        let m = platform_modifier();
+        let canonical = normalize_shortcut(&format!("{}+Shift+L", m));
+        for spelling in [
+            "cmd+shift+L",
+            "Cmd+Shift+l",
+            "shift+command+L",
+            " Meta + SHIFT + l ",
+        ] {
+            assert_eq!(normalize_shortcut(spelling), canonical, "{}", spelling);
+        }
+        // Already canonical strings, like the plugin emits, are left as they are
+        assert_eq!(normalize_shortcut(&canonical), canonical);
+        assert_ne!(normalize_shortcut("Alt+Shift+L"), canonical);
+        assert_eq!(normalize_shortcut(" Ctrl+Banana "), "ctrl+banana");
+    }
+
+    #[test]
+    fn test_find_command_matches_any_spelling() {
+        let commands = vec![
+            Command {
+                id: "empty".to_string(),
+                shortcut: Some(String::new()),
+                ..Default::default()
+            },
+            Command {
+                id: "logs".to_string(),
+                shortcut: Some("cmd+l".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let pressed = parse_shortcut("Cmd+L").unwrap().to_string();
+        assert_eq!(find_command(&commands, &pressed).unwrap().id, "logs");
+        assert!(find_command(&commands, "alt+KeyL").is_none());
+    }
+
+    #[test]
+    fn test_check_available_compares_normalized() {
+        let bound = Command {
+            id: "1".to_string(),
+            name: "Logs".to_string(),
+            shortcut: Some("Cmd+L".to_string()),
+            ..Default::default()
+        };
+        let mut other = Command {
+            id: "2".to_string(),
+            shortcut: Some("cmd+l".to_string()),
+            ..Default::default()
+        };
+        let commands = vec![bound.clone()];
+
+        let err = check_available(&commands, &other).unwrap_err();
+        assert_eq!(err.code(), "shortcut_invalid");
+        assert!(err.to_string().contains("'Logs'"), "{}", err);
+
+        // A command doesn't clash with itself, and blank shortcuts never clash
+        assert!(check_available(&commands, &bound).is_ok());
+        other.shortcut = Some("Alt+L".to_string());
+        assert!(check_available(&commands, &other).is_ok());
+        other.shortcut = None;
+        assert!(check_available(&commands, &other).is_ok());
+    }
+
+    #[test]
+    fn test_reserved_shortcut_is_rejected_unless_overridden() {
+        let reserved = reserved_shortcuts()[0];
+        assert!(is_reserved_shortcut(reserved));
+        assert!(is_reserved_shortcut(&reserved.to_ascii_lowercase()));
+        assert!(!is_reserved_shortcut("Ctrl+Alt+Shift+K"));
+
+        let mut command = Command {
+            id: "1".to_string(),
+            shortcut: Some(reserved.to_ascii_lowercase()),
+            ..Default::default()
+        };
+        let err = check_not_reserved(&command, &[]).unwrap_err();
+        assert_eq!(err.code(), "shortcut_invalid");
+        assert!(err.to_string().contains("reserved"), "{}", err);
+
+        assert!(check_not_reserved(&command, &[reserved.to_string()]).is_ok());
+        command.shortcut = Some("Ctrl+Alt+Shift+K".to_string());
+        assert!(check_not_reserved(&command, &[]).is_ok());
+        command.shortcut = None;
+        assert!(check_not_reserved(&command, &[]).is_ok());
+    }
+
+    #[test]
+    fn test_register_all_collects_errors() {
+        let command = |id: &str, shortcut: Option<&str>| Command {
+            id: id.to_string(),
+            shortcut: shortcut.map(str::to_string),
+            ..Default::default()
+        };
+        let commands = vec![
+            command("good", Some("Alt+F4")),
+            command("bad", Some("Ctrl+Banana")),
+            command("none", None),
+            command("blank", Some("  ")),
+            command("taken", Some("Alt+F5")),
+        ];
+
+        let taken: Shortcut = "Alt+F5".parse().unwrap();
+        let mut registered = Vec::new();
+        let errors = register_all(&commands, None, |shortcut| {
+            if shortcut == taken {
+                return Err("HotKey already registered".to_string());
+            }
+            registered.push(shortcut);
+            Ok(())
+        });
+
+        // The bad shortcut doesn't stop the others from registering
+        assert_eq!(registered, vec!["Alt+F4".parse::<Shortcut>().unwrap()]);
+        let failed: Vec<(&str, &str)> = errors
+            .iter()
+            .map(|e| (e.command_id.as_str(), e.shortcut.as_str()))
+            .collect();
+        assert_eq!(failed, vec![("bad", "Ctrl+Banana"), ("taken", "Alt+F5")]);
+        assert!(
+            errors[0].message.contains("Invalid shortcut"),
+            "{:?}",
+            errors[0]
+        );
+        assert_eq!(errors[1].message, "HotKey already registered");
+    }
+
+    #[test]
+    fn test_panic_shortcut_registers_first_and_wins() {
+        let commands = vec![
+            Command {
+                id: "deploy".to_string(),
+                shortcut: Some("Alt+Shift+K".to_string()),
+                ..Default::default()
+            },
+            Command {
+                id: "build".to_string(),
+                shortcut: Some("Alt+F6".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let mut registered = Vec::new();
+        let errors = register_all(&commands, Some("alt+shift+k"), |shortcut| {
+            registered.push(shortcut.to_string());
+            Ok(())
+        });
+        assert_eq!(
+            registered,
+            vec![
+                "alt+shift+k".parse::<Shortcut>().unwrap().to_string(),
+                "Alt+F6".parse::<Shortcut>().unwrap().to_string(),
+            ]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].command_id, "deploy");
+        assert!(
+            errors[0].message.contains("panic shortcut"),
+            "{:?}",
+            errors[0]
+        );
+
+        let pressed = "Alt+Shift+K".parse::<Shortcut>().unwrap().to_string();
+        assert!(is_panic_shortcut(&pressed, Some("Alt+Shift+K")));
+        assert!(!is_panic_shortcut(&pressed, Some("Alt+Shift+L")));
+        assert!(!is_panic_shortcut(&pressed, Some(" ")));
+        assert!(!is_panic_shortcut(&pressed, None));
+
+        let errors = register_all(&[], Some("Ctrl+Banana"), |_| Ok(()));
+        assert_eq!(errors[0].command_id, PANIC_SHORTCUT_ID);
+    }
+
+    #[test]
+    fn test_canonicalize_keeps_the_cmd_alias() {
+        let stored = |shortcut: &str| {
+            let mut command = Command {
+                shortcut: Some(shortcut.to_string()),
+                ..Default::default()
+            };
+            canonicalize(&mut command);
+            command.shortcut.unwrap()
+        };
+
+        assert_eq!(stored("Cmd+L"), "cmd+KeyL");
+        for spelling in ["cmd+shift+l", "Shift+Command+L", " Meta + SHIFT + l "] {
+            assert_eq!(stored(spelling), "cmd+shift+KeyL", "{}", spelling);
+        }
+        assert_eq!(stored("Ctrl+Shift+K"), "shift+control+KeyK");
+        assert_eq!(stored("Super+L"), "super+KeyL");
+        assert_eq!(stored("Ctrl+Banana"), "Ctrl+Banana");
+        assert_eq!(stored(""), "");
+
+        // The stored form is stable and still matches what the user typed
+        assert_eq!(stored("cmd+shift+KeyL"), "cmd+shift+KeyL");
+        assert_eq!(
+            normalize_shortcut("cmd+shift+KeyL"),
+            normalize_shortcut("Cmd+Shift+L")
+        );
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_normalize_collapses_duplicate_modifiers() {
+        assert_eq!(normalize_platform_modifiers("Cmd+Ctrl+L"), "Ctrl+L");
+        assert_eq!(normalize_platform_modifiers("Command+Control+L"), "Ctrl+L");
+    }
+}
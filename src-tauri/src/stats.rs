@@ -0,0 +1,107 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use sysinfo::{Pid, System};
+
+/// Resource usage of a running command.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessStats {
+    /// CPU usage since the previous sample; can exceed 100 on multi-core machines
+    pub cpu_percent: f32,
+    /// Resident memory in bytes
+    pub memory_bytes: u64,
+}
+
+/// Managed state holding the OS process table between samples.
+///
+/// CPU usage is measured between two refreshes, so keeping the same `System` around
+/// lets each poll report usage since the previous one.
+pub struct ProcessMonitor {
+    system: Mutex<System>,
+}
+
+impl ProcessMonitor {
+    pub fn new() -> Self {
+        Self {
+            system: Mutex::new(System::new()),
+        }
+    }
+
+    /// Samples the process `pid` and every process it spawned.
+    ///
+    /// Scripts run under a shell, so the shell's descendants are summed in. Returns
+    /// `None` if `pid` is no longer running. A process seen for the first time is
+    /// sampled twice, `MINIMUM_CPU_UPDATE_INTERVAL` apart, so its CPU usage isn't 0.
+    pub fn sample(&self, pid: u32) -> Option<ProcessStats> {
+        let mut system = self.system.lock().unwrap();
+        let pid = Pid::from_u32(pid);
+        let seen = system.process(pid).is_some();
+        system.refresh_processes();
+        if !seen && system.process(pid).is_some() {
+            std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+            system.refresh_processes();
+        }
+        process_tree_stats(&system, pid)
+    }
+}
+
+impl Default for ProcessMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sums the usage of `root` and all of its descendants.
+fn process_tree_stats(system: &System, root: Pid) -> Option<ProcessStats> {
+    system.process(root)?;
+
+    let mut stats = ProcessStats {
+        cpu_percent: 0.0,
+        memory_bytes: 0,
+    };
+    for (pid, process) in system.processes() {
+        if descends_from(system, *pid, root) {
+            stats.cpu_percent += process.cpu_usage();
+            stats.memory_bytes += process.memory();
+        }
+    }
+    Some(stats)
+}
+
+fn descends_from(system: &System, pid: Pid, root: Pid) -> bool {
+    let mut current = Some(pid);
+    // The depth bound guards against a parent cycle from a recycled PID
+    for _ in 0..64 {
+        match current {
+            Some(p) if p == root => return true,
+            Some(p) => current = system.process(p).and_then(|process| process.parent()),
+            None => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sample_includes_children() {
+        let monitor = ProcessMonitor::new();
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .spawn()
+            .expect("Failed to spawn sleep");
+
+        let own = monitor
+            .sample(std::process::id())
+            .expect("Own process is running");
+        let alone = monitor.sample(child.id()).expect("Child is running");
+        assert!(alone.memory_bytes > 0);
+        assert!(own.memory_bytes >= alone.memory_bytes);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(monitor.sample(child.id()), None);
+    }
+}
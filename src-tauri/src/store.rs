@@ -1,7 +1,27 @@
-use crate::models::{Command, Config};
+use crate::error::AppError;
+use crate::models::{Command, Config, DuplicateCommand, TrashedCommand, UnreadableCommand};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Directory next to the commands file that holds external scripts.
+pub const SCRIPTS_DIR: &str = "scripts";
+
+/// Format version written to the commands file.
+///
+/// The store is a `{ "version": N, "commands": [...] }` object. Files from before the
+/// version stamp hold a bare array of commands and are read as version 0.
+pub const STORE_VERSION: u64 = 1;
+
+/// Environment variable that overrides the commands file location.
+pub const STORE_PATH_ENV: &str = "CLIMGR_STORE_PATH";
+
+/// Environment variable that overrides the app data directory.
+pub const DATA_DIR_ENV: &str = "CLIMGR_DATA_DIR";
 
 /// Expands `~` to the user's home directory.
 ///
@@ -18,11 +38,235 @@ pub fn expand_path(path_str: &str) -> String {
     path_str.to_string()
 }
 
+/// Resolves the app data directory.
+///
+/// The `CLIMGR_DATA_DIR` environment variable, with `~` expanded via [`expand_path`],
+/// takes precedence; otherwise `default` is called. An empty value is ignored.
+///
+/// # Arguments
+///
+/// * `default` - Produces the platform app data directory
+pub fn resolve_data_dir(
+    default: impl FnOnce() -> Result<PathBuf, AppError>,
+) -> Result<PathBuf, AppError> {
+    if let Ok(env_dir) = std::env::var(DATA_DIR_ENV) {
+        if !env_dir.trim().is_empty() {
+            return Ok(PathBuf::from(expand_path(&env_dir)));
+        }
+    }
+    default()
+}
+
+/// The directory holding the running executable, used as the app data directory by
+/// builds with the `portable` feature.
+pub fn portable_data_dir() -> Result<PathBuf, AppError> {
+    let exe = std::env::current_exe()
+        .map_err(|e| AppError::Io(format!("Failed to locate the executable: {}", e)))?;
+    exe.parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| AppError::Io(format!("{:?} has no parent directory", exe)))
+}
+
+/// `config.toml` in the app data directory if it exists, `config.json` otherwise.
+pub fn resolve_config_path(app_data_dir: &Path) -> PathBuf {
+    let toml_path = app_data_dir.join("config.toml");
+    if toml_path.exists() {
+        return toml_path;
+    }
+    app_data_dir.join("config.json")
+}
+
+/// Resolves where the commands JSON file lives.
+///
+/// Precedence, highest first:
+///
+/// 1. The `CLIMGR_STORE_PATH` environment variable
+/// 2. `config.commands_path`
+/// 3. `commands.json` inside the app data directory
+///
+/// Both overrides have `~` expanded via [`expand_path`]. A relative `commands_path` is
+/// resolved against the app data directory (see [`resolve_commands_path`]). Empty values
+/// are ignored.
+///
+/// # Arguments
+///
+/// * `app_data_dir` - The default application data directory
+/// * `config` - The current configuration, if it could be read
+pub fn resolve_store_path(app_data_dir: &Path, config: Option<&Config>) -> PathBuf {
+    if let Ok(env_path) = std::env::var(STORE_PATH_ENV) {
+        if !env_path.trim().is_empty() {
+            return PathBuf::from(expand_path(&env_path));
+        }
+    }
+
+    if let Some(path_str) = config.and_then(|c| c.commands_path.as_deref()) {
+        if !path_str.trim().is_empty() {
+            return resolve_commands_path(app_data_dir, path_str);
+        }
+    }
+
+    app_data_dir.join("commands.json")
+}
+
+/// Resolves a user-supplied `commands_path`.
+///
+/// `~` is expanded first; a path that is still relative is taken relative to the app
+/// data directory rather than the process working directory, which is unpredictable
+/// for a GUI app. Absolute paths are returned as-is.
+pub fn resolve_commands_path(app_data_dir: &Path, path_str: &str) -> PathBuf {
+    let path = PathBuf::from(expand_path(path_str));
+    if path.is_relative() {
+        app_data_dir.join(path)
+    } else {
+        path
+    }
+}
+
+/// How long a write waits for another writer to release a store lock.
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a held lock is retried while waiting.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Age past which a lock is assumed to be left over from a crashed writer.
+///
+/// Writes take milliseconds, so a lock this old is no longer protecting anything.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(30);
+
+/// An advisory lock on a store file, held while it is written.
+///
+/// The lock is a `<file>.lock` sibling created exclusively, so only one writer at a
+/// time, in this or another climgr instance, can hold it. Editors and other tools
+/// don't know about it, so it only guards writes made through climgr. The lock file
+/// is removed when the guard is dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// The lock file guarding `target`.
+    pub fn path_for(target: &Path) -> PathBuf {
+        let mut name = target.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        target.with_file_name(name)
+    }
+
+    /// Takes the lock on `target`, retrying until `timeout` while another writer has it.
+    ///
+    /// A lock older than a few seconds' worth of writes is treated as stale and taken
+    /// over. The parent directory must exist.
+    pub fn acquire(target: &Path, timeout: Duration) -> Result<FileLock, AppError> {
+        let path = Self::path_for(target);
+        let deadline = Instant::now() + timeout;
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    // Who holds it, for anyone inspecting a lock left behind
+                    let _ = std::io::Write::write_all(
+                        &mut file,
+                        std::process::id().to_string().as_bytes(),
+                    );
+                    return Ok(FileLock { path });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if is_stale(&path) {
+                        log::warn!("Removing stale lock {:?}", path);
+                        let _ = std::fs::remove_file(&path);
+                        continue;
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(AppError::Io(format!(
+                            "{:?} is being written by another climgr window or instance; \
+                             try again (or delete {:?} if none is running)",
+                            target, path
+                        )));
+                    }
+                    std::thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                Err(e) => {
+                    return Err(AppError::Io(format!(
+                        "Failed to create lock {:?}: {}",
+                        path, e
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to release lock {:?}: {}", self.path, e);
+        }
+    }
+}
+
+fn is_stale(lock: &Path) -> bool {
+    std::fs::metadata(lock)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > STALE_LOCK_AGE)
+}
+
+/// Checks that a commands file can be written at the given path.
+///
+/// Creates the parent directory if needed and writes (then removes) a probe file next
+/// to the target, so permission problems surface before the location is adopted.
+///
+/// # Arguments
+///
+/// * `path` - Path of the commands JSON file to validate
+///
+/// # Returns
+///
+/// * `Ok(())` - The location is writable
+/// * `Err(AppError)` - Descriptive error naming the directory that failed
+pub fn validate_store_location(path: &Path) -> Result<(), AppError> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => {
+            return Err(AppError::Invalid(format!(
+                "Invalid storage path: {:?}",
+                path
+            )))
+        }
+    };
+
+    std::fs::create_dir_all(parent).map_err(|e| {
+        AppError::Io(format!(
+            "Cannot create storage directory {:?}: {}",
+            parent, e
+        ))
+    })?;
+
+    let probe = parent.join(format!(".climgr-write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"").map_err(|e| {
+        AppError::Io(format!(
+            "Storage directory {:?} is not writable: {}",
+            parent, e
+        ))
+    })?;
+    let _ = std::fs::remove_file(&probe);
+
+    Ok(())
+}
+
 /// Retrieves all commands from persistent storage.
 ///
 /// Returns an empty vector if the file doesn't exist. This allows the app to start
 /// with no commands and add them later.
 ///
+/// Entries that don't parse as a command are skipped, so one corrupt entry doesn't
+/// hide the rest. Each is logged and copied to the [`quarantine_path`] file, where it
+/// survives the store being saved without it.
+///
 /// # Arguments
 ///
 /// * `path` - Path to the commands JSON file
@@ -30,19 +274,217 @@ pub fn expand_path(path_str: &str) -> String {
 /// # Returns
 ///
 /// * `Ok(Vec<Command>)` - Vector of commands (empty if file doesn't exist)
-/// * `Err(String)` - Error if file cannot be read or JSON is invalid
-pub fn get_commands(path: &Path) -> Result<Vec<Command>, String> {
+/// * `Err(AppError)` - Error if file cannot be read or isn't a JSON array
+pub fn get_commands(path: &Path) -> Result<Vec<Command>, AppError> {
+    let (commands, unreadable) = load_commands(path)?;
+    if !unreadable.is_empty() {
+        for entry in &unreadable {
+            log::warn!(
+                "Skipping unreadable command at index {} of {:?}: {}",
+                entry.index,
+                path,
+                entry.error
+            );
+        }
+        if let Err(e) = quarantine(path, &unreadable) {
+            log::error!("Failed to quarantine unreadable commands: {}", e);
+        }
+    }
+    Ok(commands)
+}
+
+/// Like [`get_commands`], but also returns the entries that couldn't be parsed,
+/// without logging or quarantining them.
+pub fn load_commands(path: &Path) -> Result<(Vec<Command>, Vec<UnreadableCommand>), AppError> {
     if !path.exists() {
-        return Ok(vec![]);
+        return Ok((vec![], vec![]));
     }
 
-    let file = File::open(path).map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
-    let commands: Vec<Command> = serde_json::from_reader(reader).map_err(|e| e.to_string())?;
-    Ok(commands)
+    let text = std::fs::read_to_string(path)?;
+    let entries = match serde_json::from_str::<serde_json::Value>(&text)? {
+        serde_json::Value::Object(mut wrapper) if wrapper.contains_key("commands") => {
+            check_version(path, wrapper.get("version"))?;
+            match wrapper.remove("commands") {
+                Some(serde_json::Value::Array(entries)) => entries,
+                _ => return Err(commands_not_an_array(path, &text)),
+            }
+        }
+        serde_json::Value::Object(_) => return Err(not_an_array(path, &text)),
+        legacy => Vec::<serde_json::Value>::deserialize(legacy)?,
+    };
+
+    let mut commands = Vec::with_capacity(entries.len());
+    let mut unreadable = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        match Command::deserialize(&entry) {
+            Ok(command) => commands.push(command),
+            Err(e) => unreadable.push(UnreadableCommand {
+                index,
+                id: entry.get("id").and_then(|id| id.as_str()).map(String::from),
+                error: e.to_string(),
+                entry,
+            }),
+        }
+    }
+
+    // Scripts stored externally are read back in; a missing file isn't fatal
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    for command in &mut commands {
+        if let Some(script_file) = command.script_file.as_deref() {
+            command.script = std::fs::read_to_string(base.join(script_file)).unwrap_or_else(|e| {
+                log::warn!(
+                    "Script file {:?} for command {} is unreadable ({}); using an empty script",
+                    script_file,
+                    command.id,
+                    e
+                );
+                String::new()
+            });
+        }
+    }
+    Ok((commands, unreadable))
+}
+
+/// Fails if the store was written in a newer format than this build understands.
+///
+/// A wrapper without a `version` is taken to be the current one.
+fn check_version(path: &Path, version: Option<&serde_json::Value>) -> Result<(), AppError> {
+    let Some(version) = version else {
+        return Ok(());
+    };
+    match version.as_u64() {
+        Some(version) if version <= STORE_VERSION => Ok(()),
+        Some(version) => Err(AppError::Parse(format!(
+            "{:?} uses store format version {}, but this version of climgr only reads up to {}",
+            path, version, STORE_VERSION
+        ))),
+        None => Err(AppError::Parse(format!(
+            "{:?} has an invalid store format version: {}",
+            path, version
+        ))),
+    }
+}
+
+/// A clearer error for the common hand-editing mistake of a store holding one command
+/// object (`{...}`) instead of the `commands` list.
+fn not_an_array(path: &Path, text: &str) -> AppError {
+    AppError::Parse(format!(
+        "{:?} holds a JSON object without a \"commands\" array, but the commands file must \
+         be {{ \"version\": {}, \"commands\": [...] }} or a bare array of commands{}",
+        path,
+        STORE_VERSION,
+        at_byte(
+            text,
+            serde_json::from_str::<Vec<serde::de::IgnoredAny>>(text).err()
+        )
+    ))
+}
+
+/// Like [`not_an_array`], for a wrapper whose `commands` is something else, such as a
+/// single command object.
+fn commands_not_an_array(path: &Path, text: &str) -> AppError {
+    #[derive(Deserialize)]
+    struct Wrapper {
+        #[allow(dead_code)]
+        commands: Vec<serde::de::IgnoredAny>,
+    }
+    AppError::Parse(format!(
+        "The \"commands\" of {:?} must be an array of commands: [{{...}}, ...]{}",
+        path,
+        at_byte(text, serde_json::from_str::<Wrapper>(text).err())
+    ))
+}
+
+/// `" (at byte N)"` for where serde found the problem in `text`, or nothing if it
+/// didn't say.
+///
+/// serde reports a 1-based line and the number of bytes read on it, so the offset is
+/// the length of the lines before plus that column.
+fn at_byte(text: &str, error: Option<serde_json::Error>) -> String {
+    let Some(error) = error.filter(|e| e.line() > 0) else {
+        return String::new();
+    };
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(error.line() - 1)
+        .map(str::len)
+        .sum();
+    format!(" (at byte {})", line_start + error.column())
+}
+
+/// Where unreadable entries of the store at `path` are kept, e.g.
+/// `commands.quarantine.json` next to `commands.json`.
+pub fn quarantine_path(path: &Path) -> PathBuf {
+    path.with_extension("quarantine.json")
+}
+
+/// Adds the entries to the quarantine file, skipping any it already holds.
+fn quarantine(path: &Path, unreadable: &[UnreadableCommand]) -> Result<(), AppError> {
+    let quarantine_path = quarantine_path(path);
+    // Held from the read on, so entries another load quarantines meanwhile aren't lost
+    let _lock = FileLock::acquire(&quarantine_path, LOCK_TIMEOUT)?;
+    let mut kept: Vec<serde_json::Value> = if quarantine_path.exists() {
+        serde_json::from_reader(BufReader::new(File::open(&quarantine_path)?))?
+    } else {
+        vec![]
+    };
+    let before = kept.len();
+    for entry in unreadable {
+        if !kept.contains(&entry.entry) {
+            kept.push(entry.entry.clone());
+        }
+    }
+    if kept.len() == before {
+        return Ok(());
+    }
+
+    // Always owner-only: it holds scripts the store couldn't read, and no config is at
+    // hand to say otherwise
+    let writer = BufWriter::new(create_file(&quarantine_path, true)?);
+    serde_json::to_writer_pretty(writer, &kept).map_err(AppError::from)
+}
+
+/// How [`save_commands_with`] writes the store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// Write each script to `scripts/<id>.sh` instead of inline
+    pub scripts_external: bool,
+    /// Make the files readable and writable by their owner only (unix)
+    pub restrict_permissions: bool,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            scripts_external: false,
+            restrict_permissions: true,
+        }
+    }
+}
+
+/// Creates (or truncates) `path`, first limiting it to mode 0600 if `restrict` is set.
+///
+/// The mode is set before anything is written, so secrets in the contents are never
+/// readable by others. Windows files keep their inherited ACLs.
+pub fn create_file(path: &Path, restrict: bool) -> Result<File, AppError> {
+    let file = File::create(path)?;
+    #[cfg(unix)]
+    if restrict {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| {
+                AppError::Io(format!(
+                    "Failed to restrict permissions of {:?}: {}",
+                    path, e
+                ))
+            })?;
+    }
+    #[cfg(not(unix))]
+    let _ = restrict;
+    Ok(file)
 }
 
-/// Saves commands to persistent storage.
+/// Saves commands to persistent storage with scripts inline.
 ///
 /// Creates the parent directory if it doesn't exist. Writes commands as
 /// pretty-printed JSON for human readability.
@@ -55,80 +497,474 @@ pub fn get_commands(path: &Path) -> Result<Vec<Command>, String> {
 /// # Returns
 ///
 /// * `Ok(())` - Commands were successfully saved
-/// * `Err(String)` - Error if directory creation or file write fails
-pub fn save_commands(path: &Path, commands: &[Command]) -> Result<(), String> {
+/// * `Err(AppError)` - Error if directory creation or file write fails
+pub fn save_commands(path: &Path, commands: &[Command]) -> Result<(), AppError> {
+    save_commands_with(path, commands, SaveOptions::default())
+}
+
+/// Saves commands, optionally writing each script to its own file.
+///
+/// With `scripts_external`, every script is written to `scripts/<id>.sh` next to the
+/// commands file and the JSON only keeps that relative path in `script_file`. An ID
+/// that isn't a safe file name gets a short hash of itself appended. Without
+/// it, scripts are stored inline and `script_file` is cleared, so flipping the mode and
+/// re-saving migrates the store either way.
+///
+/// With `restrict_permissions`, the commands file and script files are created with
+/// mode 0600 on unix, since scripts may contain tokens.
+pub fn save_commands_with(
+    path: &Path,
+    commands: &[Command],
+    options: SaveOptions,
+) -> Result<(), AppError> {
     // Ensure parent directory exists
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    std::fs::create_dir_all(base)?;
+
+    let _lock = FileLock::acquire(path, LOCK_TIMEOUT)?;
+    write_commands(path, commands, options)
+}
+
+/// Loads the commands at `path`, applies `update` and saves the result, holding the
+/// file's lock the whole time.
+///
+/// Unlike a [`get_commands`] followed by a [`save_commands_with`], nothing another
+/// writer saves in between is lost, so concurrent updates (such as two runs finishing
+/// at once) all land. Nothing is written if `update` fails.
+pub fn update_commands<T>(
+    path: &Path,
+    options: SaveOptions,
+    update: impl FnOnce(&mut Vec<Command>) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    std::fs::create_dir_all(base)?;
+
+    let _lock = FileLock::acquire(path, LOCK_TIMEOUT)?;
+    let mut commands = get_commands(path)?;
+    let result = update(&mut commands)?;
+    write_commands(path, &commands, options)?;
+    Ok(result)
+}
+
+/// Writes the commands file (and script files); the caller holds the lock.
+fn write_commands(path: &Path, commands: &[Command], options: SaveOptions) -> Result<(), AppError> {
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut stored = commands.to_vec();
+    for command in &mut stored {
+        if options.scripts_external {
+            let script_file = format!("{}/{}.sh", SCRIPTS_DIR, script_file_stem(&command.id));
+            let script_path = base.join(&script_file);
+            std::fs::create_dir_all(base.join(SCRIPTS_DIR))?;
+            create_file(&script_path, options.restrict_permissions)?
+                .write_all(command.script.as_bytes())
+                .map_err(|e| {
+                    AppError::Io(format!("Failed to write script {:?}: {}", script_path, e))
+                })?;
+            command.script = String::new();
+            command.script_file = Some(script_file);
+        } else {
+            command.script_file = None;
+        }
     }
 
-    let file = File::create(path).map_err(|e| e.to_string())?;
+    let file = create_file(path, options.restrict_permissions)?;
     let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, commands).map_err(|e| e.to_string())?;
+    let wrapper = StoreFile {
+        version: STORE_VERSION,
+        commands: &stored,
+    };
+    serde_json::to_writer_pretty(writer, &wrapper)?;
     Ok(())
 }
 
+/// The versioned layout of the commands file.
+#[derive(Serialize)]
+struct StoreFile<'a> {
+    version: u64,
+    commands: &'a [Command],
+}
+
+/// A filesystem-safe file name for a command ID.
+///
+/// IDs that are already safe are used as-is. Otherwise the unsafe characters become
+/// `_` and the first 8 hex digits of the ID's SHA-256 follow a `.`, which safe IDs
+/// never contain, so IDs such as `a.b` and `a/b` don't share a script file.
+fn script_file_stem(id: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if id.chars().all(is_safe) {
+        return id.to_string();
+    }
+    let stem: String = id
+        .chars()
+        .map(|c| if is_safe(c) { c } else { '_' })
+        .collect();
+    let hash: String = Sha256::digest(id.as_bytes())[..4]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("{}.{}", stem, hash)
+}
+
+/// Where each command loaded from a `commands_dir` came from, so saving can write it
+/// back to the same file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Included {
+    /// Include file of each command ID not in the primary store
+    pub files: HashMap<String, PathBuf>,
+    /// Commands skipped because an earlier file already used their ID
+    pub duplicates: Vec<DuplicateCommand>,
+}
+
+/// The `*.json` files in `dir`, sorted by name; quarantine files are left out and a
+/// missing directory has none.
+pub fn include_files(dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && is_include_file(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether a file in `commands_dir` is read as an include file, going by its name.
+pub fn is_include_file(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    name.ends_with(".json") && !name.ends_with(".quarantine.json")
+}
+
+/// Loads the primary store followed by every include file in `dir`.
+///
+/// The first command with a given ID wins: the primary store is read first, then the
+/// include files in name order. Later commands with the same ID are left out and
+/// returned in [`Included::duplicates`].
+///
+/// # Arguments
+///
+/// * `primary` - Path to the main commands JSON file
+/// * `dir` - Directory of extra command files
+///
+/// # Returns
+///
+/// * `Ok((Vec<Command>, Included))` - The merged commands and where they came from
+/// * `Err(AppError)` - Error if any of the files cannot be read
+pub fn load_merged(primary: &Path, dir: &Path) -> Result<(Vec<Command>, Included), AppError> {
+    let mut commands = get_commands(primary)?;
+    let mut seen: HashMap<String, PathBuf> = HashMap::new();
+    for command in &commands {
+        seen.entry(command.id.clone())
+            .or_insert_with(|| primary.to_path_buf());
+    }
+
+    let mut included = Included::default();
+    for file in include_files(dir)? {
+        for command in get_commands(&file)? {
+            if let Some(first_file) = seen.get(&command.id) {
+                included.duplicates.push(DuplicateCommand {
+                    command,
+                    file: file.clone(),
+                    first_file: first_file.clone(),
+                });
+                continue;
+            }
+            seen.insert(command.id.clone(), file.clone());
+            included.files.insert(command.id.clone(), file.clone());
+            commands.push(command);
+        }
+    }
+    Ok((commands, included))
+}
+
+/// Saves merged commands, each to the file it was loaded from.
+///
+/// Commands without an entry in `included` (new ones included) go to `primary`. An
+/// include file is only rewritten when its commands changed, and keeps the duplicates
+/// that were skipped when it was loaded.
+pub fn save_merged(
+    primary: &Path,
+    commands: &[Command],
+    included: &Included,
+    options: SaveOptions,
+) -> Result<(), AppError> {
+    let mut by_file: BTreeMap<&Path, Vec<Command>> = included
+        .files
+        .values()
+        .map(|file| (file.as_path(), vec![]))
+        .collect();
+    let mut main = Vec::new();
+    for command in commands {
+        match included.files.get(&command.id) {
+            Some(file) => by_file
+                .entry(file.as_path())
+                .or_default()
+                .push(command.clone()),
+            None => main.push(command.clone()),
+        }
+    }
+    for duplicate in &included.duplicates {
+        if let Some(kept) = by_file.get_mut(duplicate.file.as_path()) {
+            kept.push(duplicate.command.clone());
+        }
+    }
+
+    for (file, kept) in by_file {
+        let current = load_commands(file).ok().map(|(current, _)| current);
+        if current.as_ref() != Some(&kept) {
+            save_commands_with(file, &kept, options)?;
+        }
+    }
+    save_commands_with(primary, &main, options)
+}
+
+/// Copies the commands stored at `from` to `to`.
+///
+/// The new file is written to a temporary sibling and renamed into place, so `to` is
+/// never left half-written. A missing `from` migrates zero commands.
+///
+/// # Arguments
+///
+/// * `from` - Current commands file
+/// * `to` - New commands file location
+/// * `keep_old` - Leave `from` in place instead of deleting it after the copy
+///
+/// # Returns
+///
+/// * `Ok(usize)` - Number of commands migrated
+/// * `Err(AppError)` - Error if reading, writing or renaming fails
+pub fn migrate_commands(from: &Path, to: &Path, keep_old: bool) -> Result<usize, AppError> {
+    let commands = get_commands(from)?;
+
+    let mut tmp_name = to.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = to.with_file_name(tmp_name);
+
+    // Keep external scripts external at the new location
+    let options = SaveOptions {
+        scripts_external: commands.iter().any(|c| c.script_file.is_some()),
+        ..Default::default()
+    };
+    save_commands_with(&tmp_path, &commands, options)?;
+    std::fs::rename(&tmp_path, to).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        AppError::Io(format!("Failed to move commands into {:?}: {}", to, e))
+    })?;
+
+    if !keep_old && from.exists() {
+        std::fs::remove_file(from)?;
+    }
+
+    Ok(commands.len())
+}
+
+/// Brings the store in line with a config update from `current` to `new`.
+///
+/// A store that moved to a location with no file yet is copied there (the old file is
+/// kept), and when `scripts_external` flips the store is re-saved so scripts
+/// move into or out of separate files right away. With a `commands_dir`, its include
+/// files are re-saved the same way, each keeping its own commands (duplicates too).
+/// While `new` is in read-only mode the store isn't touched at all.
+///
+/// # Returns
+///
+/// * `Ok(usize)` - Number of commands migrated (0 if the store didn't move)
+/// * `Err(AppError)` - Error if migrating or re-saving failed
+pub fn apply_config_change(
+    current_store: &Path,
+    new_store: &Path,
+    commands_dir: Option<&Path>,
+    current: &Config,
+    new: &Config,
+) -> Result<usize, AppError> {
+    if new.read_only.unwrap_or(false) {
+        return Ok(0);
+    }
+
+    let migrated = if current_store != new_store && !new_store.exists() {
+        migrate_commands(current_store, new_store, true)?
+    } else {
+        0
+    };
+    if current.scripts_external != new.scripts_external {
+        let includes = match commands_dir {
+            Some(dir) => include_files(dir)?,
+            None => vec![],
+        };
+        let files = std::iter::once(new_store.to_path_buf()).chain(includes);
+        for file in files.filter(|file| file.exists()) {
+            let commands = get_commands(&file)?;
+            save_commands_with(&file, &commands, new.save_options())?;
+        }
+    }
+    Ok(migrated)
+}
+
+/// Returns the trash file that belongs to a commands file.
+///
+/// The trash lives in a `trash/` directory next to the store, so it follows the store
+/// when `commands_path` points somewhere else.
+pub fn trash_path_for(store_path: &Path) -> PathBuf {
+    store_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("trash")
+        .join("trashed_commands.json")
+}
+
+/// Retrieves trashed commands. Returns an empty vector if the trash file doesn't exist.
+pub fn get_trash(path: &Path) -> Result<Vec<TrashedCommand>, AppError> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+    serde_json::from_reader(reader).map_err(AppError::from)
+}
+
+/// Saves trashed commands, creating the `trash/` directory if needed.
+///
+/// Trashed scripts may hold secrets like the store's, so the file is written under its
+/// lock and, with `restrict`, owner-only.
+pub fn save_trash(path: &Path, trash: &[TrashedCommand], restrict: bool) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let _lock = FileLock::acquire(path, LOCK_TIMEOUT)?;
+    let file = create_file(path, restrict)?;
+    let writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(writer, trash).map_err(AppError::from)
+}
+
 /// Retrieves application configuration from storage.
 ///
 /// Returns default configuration (safe_mode: false) if the file doesn't exist.
 /// This allows the app to run with sensible defaults on first launch.
+/// A path ending in `.toml` is read as TOML; anything else as JSON.
 ///
 /// # Arguments
 ///
-/// * `path` - Path to the config JSON file
+/// * `path` - Path to the config JSON or TOML file
 ///
 /// # Returns
 ///
 /// * `Ok(Config)` - Configuration object (default if file doesn't exist)
-/// * `Err(String)` - Error if file cannot be read or JSON is invalid
-pub fn get_config(path: &Path) -> Result<Config, String> {
+/// * `Err(AppError)` - Error if file cannot be read or its contents are invalid
+pub fn get_config(path: &Path) -> Result<Config, AppError> {
     if !path.exists() {
         // Return default config if file doesn't exist
         return Ok(Config {
             safe_mode: false,
             commands_path: None,
             accessibility_notice_dismissed: Some(false),
+            ..Default::default()
         });
     }
 
-    let file = File::open(path).map_err(|e| e.to_string())?;
+    if is_toml(path) {
+        let contents = std::fs::read_to_string(path)?;
+        return toml::from_str(&contents).map_err(AppError::from);
+    }
+
+    let file = File::open(path)?;
     let reader = BufReader::new(file);
-    let config: Config = serde_json::from_reader(reader).map_err(|e| e.to_string())?;
+    let config: Config = serde_json::from_reader(reader)?;
     Ok(config)
 }
 
 /// Saves application configuration to persistent storage.
 ///
 /// Creates the parent directory if it doesn't exist. Writes config as
-/// pretty-printed JSON for human readability.
+/// pretty-printed JSON for human readability, or as TOML if the path ends in `.toml`.
 ///
 /// # Arguments
 ///
-/// * `path` - Path where the config JSON file should be saved
+/// * `path` - Path where the config JSON or TOML file should be saved
 /// * `config` - Configuration object to save
 ///
 /// # Returns
 ///
 /// * `Ok(())` - Configuration was successfully saved
-/// * `Err(String)` - Error if directory creation or file write fails
-pub fn save_config(path: &Path, config: &Config) -> Result<(), String> {
+/// * `Err(AppError)` - Error if directory creation or file write fails
+pub fn save_config(path: &Path, config: &Config) -> Result<(), AppError> {
     // Ensure parent directory exists
     if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(parent)?;
+    }
+    let _lock = FileLock::acquire(path, LOCK_TIMEOUT)?;
+
+    let file = create_file(path, config.restrict_permissions())?;
+    if is_toml(path) {
+        let contents = toml::to_string_pretty(config)?;
+        return BufWriter::new(file)
+            .write_all(contents.as_bytes())
+            .map_err(AppError::from);
     }
 
-    let file = File::create(path).map_err(|e| e.to_string())?;
     let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, config).map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(writer, config)?;
     Ok(())
 }
 
+/// Serializes commands as YAML for hand editing.
+///
+/// Unset optional fields are left out, and multi-line scripts are written as block
+/// scalars (`|`) rather than escaped strings.
+pub fn commands_to_yaml(commands: &[Command]) -> Result<String, AppError> {
+    let mut value = serde_yaml::to_value(commands)?;
+    if let serde_yaml::Value::Sequence(items) = &mut value {
+        for item in items {
+            if let serde_yaml::Value::Mapping(fields) = item {
+                fields.retain(|_, v| !v.is_null());
+            }
+        }
+    }
+    serde_yaml::to_string(&value).map_err(AppError::from)
+}
+
+/// Parses a YAML list of commands, as written by [`commands_to_yaml`].
+///
+/// # Returns
+///
+/// * `Ok(Vec<Command>)` - The parsed commands
+/// * `Err(AppError)` - `"Invalid YAML: ..."` with the location of the problem
+pub fn commands_from_yaml(yaml: &str) -> Result<Vec<Command>, AppError> {
+    serde_yaml::from_str(yaml).map_err(|e| AppError::Parse(format!("Invalid YAML: {}", e)))
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::Command;
+    use crate::models::{Command, OutputDecoding, StoreIssueKind};
     use std::fs;
 
+    fn external() -> SaveOptions {
+        SaveOptions {
+            scripts_external: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_portable_data_dir_is_next_to_the_executable() {
+        let dir = portable_data_dir().expect("Failed to resolve");
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(exe.parent(), Some(dir.as_path()));
+    }
+
     #[test]
     fn test_save_and_load_commands() {
         let temp_dir = std::env::temp_dir();
@@ -149,9 +985,7 @@ mod tests {
                 id: "1".to_string(),
                 name: "Test 1".to_string(),
                 script: "echo 1".to_string(),
-                kill_script: None,
-                shortcut: None,
-                description: None,
+                ..Default::default()
             },
             Command {
                 id: "2".to_string(),
@@ -160,6 +994,7 @@ mod tests {
                 kill_script: Some("pkill 2".to_string()),
                 shortcut: Some("Ctrl+2".to_string()),
                 description: Some("Description".to_string()),
+                ..Default::default()
             },
         ];
 
@@ -183,6 +1018,375 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_config_round_trips_as_json_and_toml() {
+        let temp_dir = std::env::temp_dir().join("climgr_config_formats");
+        let _ = fs::remove_dir_all(&temp_dir);
+
+        let config = Config {
+            safe_mode: true,
+            commands_path: Some("~/dotfiles/climgr/commands.json".to_string()),
+            accessibility_notice_dismissed: None,
+            max_script_length: Some(4096),
+            graceful_kill: true,
+            scripts_external: true,
+            safe_mode_passphrase_hash: Some("00ff$abcd".to_string()),
+            safe_mode_bypass_token: Some("s3cret".to_string()),
+            output_batch_lines: Some(20),
+            output_batch_interval_ms: Some(100),
+            read_only: Some(true),
+            ui_output_max_chars: Some(10_000),
+            strict_templates: Some(true),
+            output_encoding: Some(OutputDecoding::RawBase64),
+            restrict_permissions: Some(false),
+            kill_signal: Some("TERM".to_string()),
+            kill_grace_secs: Some(5),
+            commands_dir: Some("commands.d".to_string()),
+            strip_ansi: Some(true),
+            reserved_shortcut_overrides: Some(vec!["Alt+Tab".to_string()]),
+            serialize_executions: Some(true),
+            login_shell: Some(true),
+            interactive_shell: Some(false),
+            profile_env: Some(BTreeMap::from([(
+                "API_URL".to_string(),
+                "https://api.example.com".to_string(),
+            )])),
+            panic_shortcut: Some("Ctrl+Alt+Escape".to_string()),
+        };
+
+        for name in ["config.json", "config.toml"] {
+            let path = temp_dir.join(name);
+            save_config(&path, &config).expect("Failed to save config");
+            let loaded = get_config(&path).expect("Failed to load config");
+            assert_eq!(loaded, config, "{} should round-trip", name);
+        }
+
+        let toml = fs::read_to_string(temp_dir.join("config.toml")).unwrap();
+        assert!(toml.contains("safe_mode = true"), "{}", toml);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_external_scripts_round_trip() {
+        let temp_dir = std::env::temp_dir().join("climgr_external_scripts");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.join("commands.json");
+
+        let command = Command {
+            id: "build".to_string(),
+            name: "Build".to_string(),
+            script: "cargo build\ncargo test\n".to_string(),
+            ..Default::default()
+        };
+
+        save_commands_with(&path, std::slice::from_ref(&command), external())
+            .expect("Failed to save");
+        let script_path = temp_dir.join("scripts").join("build.sh");
+        assert_eq!(fs::read_to_string(&script_path).unwrap(), command.script);
+        let json = fs::read_to_string(&path).unwrap();
+        assert!(
+            json.contains("\"script_file\": \"scripts/build.sh\""),
+            "{}",
+            json
+        );
+        assert!(
+            !json.contains("cargo build"),
+            "Script should not be inline: {}",
+            json
+        );
+
+        let loaded = get_commands(&path).expect("Failed to load");
+        assert_eq!(loaded[0].script, command.script);
+
+        // Flipping back inlines the script again
+        save_commands(&path, &loaded).expect("Failed to save inline");
+        let inline = get_commands(&path).unwrap();
+        assert_eq!(inline[0].script, command.script);
+        assert_eq!(inline[0].script_file, None);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_external_scripts_of_similar_ids_do_not_collide() {
+        let temp_dir = std::env::temp_dir().join("climgr_colliding_scripts");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.join("commands.json");
+
+        let commands: Vec<Command> = ["a_b", "a.b", "a/b"]
+            .iter()
+            .map(|id| Command {
+                id: id.to_string(),
+                name: id.to_string(),
+                script: format!("echo {}", id),
+                ..Default::default()
+            })
+            .collect();
+        save_commands_with(&path, &commands, external()).expect("Failed to save");
+
+        let loaded = get_commands(&path).expect("Failed to load");
+        assert_eq!(loaded[0].script_file.as_deref(), Some("scripts/a_b.sh"));
+        for (loaded, command) in loaded.iter().zip(&commands) {
+            assert_eq!(loaded.script, command.script);
+        }
+        let files: std::collections::HashSet<_> =
+            loaded.iter().map(|c| c.script_file.clone()).collect();
+        assert_eq!(files.len(), 3);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_missing_external_script_loads_empty() {
+        let temp_dir = std::env::temp_dir().join("climgr_missing_script");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.join("commands.json");
+
+        let command = Command {
+            id: "gone".to_string(),
+            name: "Gone".to_string(),
+            script: "echo hi".to_string(),
+            ..Default::default()
+        };
+        save_commands_with(&path, &[command], external()).expect("Failed to save");
+        fs::remove_file(temp_dir.join("scripts").join("gone.sh")).unwrap();
+
+        let loaded = get_commands(&path).expect("A missing script file should not fail");
+        assert_eq!(loaded[0].script, "");
+        assert_eq!(loaded[0].name, "Gone");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_unreadable_entries_are_skipped_and_quarantined() {
+        let temp_dir = std::env::temp_dir().join("climgr_unreadable");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("commands.json");
+        fs::write(
+            &path,
+            r#"[
+                {"id": "1", "name": "Good", "script": "echo 1"},
+                {"id": "2", "name": 5, "script": "echo 2"},
+                {"id": "3", "name": "Also good", "script": "echo 3"},
+                42
+            ]"#,
+        )
+        .unwrap();
+
+        let (commands, unreadable) = load_commands(&path).expect("Failed to load");
+        let ids: Vec<&str> = commands.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "3"]);
+        let found: Vec<(usize, Option<&str>)> = unreadable
+            .iter()
+            .map(|u| (u.index, u.id.as_deref()))
+            .collect();
+        assert_eq!(found, vec![(1, Some("2")), (3, None)]);
+        assert!(
+            unreadable[0].error.contains("invalid type"),
+            "{}",
+            unreadable[0].error
+        );
+        assert_eq!(unreadable[0].issue().command_id, "2");
+
+        // Loading keeps copies, once, even after saving drops them from the store
+        assert_eq!(get_commands(&path).unwrap().len(), 2);
+        save_commands(&path, &commands).unwrap();
+        assert_eq!(get_commands(&path).unwrap().len(), 2);
+        let kept: Vec<serde_json::Value> =
+            serde_json::from_str(&fs::read_to_string(quarantine_path(&path)).unwrap()).unwrap();
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0]["id"], "2");
+        assert_eq!(kept[1], 42);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_object_instead_of_array_gets_a_clear_error() {
+        let temp_dir = std::env::temp_dir().join("climgr_not_an_array");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("commands.json");
+
+        fs::write(&path, "\n  {\"id\": \"1\", \"name\": \"Build\"}\n").unwrap();
+        let err = get_commands(&path).unwrap_err();
+        assert_eq!(err.code(), "parse");
+        let message = err.to_string();
+        assert!(
+            message.contains("without a \"commands\" array"),
+            "{}",
+            message
+        );
+        assert!(message.contains("(at byte 3)"), "{}", message);
+
+        // The offset is serde's, wherever the object starts
+        fs::write(&path, "{\"id\": \"1\"}").unwrap();
+        let message = get_commands(&path).unwrap_err().to_string();
+        assert!(message.contains("(at byte 0)"), "{}", message);
+
+        // A wrapper whose commands is one command object, after other content
+        let text = "{\n  \"version\": 1,\n  \"commands\": {\"id\": \"1\"}\n}";
+        fs::write(&path, text).unwrap();
+        let err = get_commands(&path).unwrap_err();
+        assert_eq!(err.code(), "parse");
+        let message = err.to_string();
+        assert!(
+            message.contains("must be an array of commands"),
+            "{}",
+            message
+        );
+        let offset = text.find("{\"id\"").unwrap();
+        assert!(
+            message.contains(&format!("(at byte {})", offset)),
+            "{}",
+            message
+        );
+
+        // Other mistakes keep serde's own message
+        fs::write(&path, "[{\"id\": \"1\"},").unwrap();
+        let message = get_commands(&path).unwrap_err().to_string();
+        assert!(!message.contains("\"commands\" array"), "{}", message);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_legacy_bare_array_is_read() {
+        let temp_dir = std::env::temp_dir().join("climgr_legacy_store");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("commands.json");
+
+        fs::write(&path, r#"[{"id": "1", "name": "Build", "script": "make"}]"#).unwrap();
+        let commands = get_commands(&path).expect("Legacy stores should still load");
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].script, "make");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_versioned_wrapper_is_read() {
+        let temp_dir = std::env::temp_dir().join("climgr_versioned_store");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("commands.json");
+
+        fs::write(
+            &path,
+            r#"{"version": 1, "commands": [{"id": "1", "name": "Build", "script": "make"}]}"#,
+        )
+        .unwrap();
+        let commands = get_commands(&path).expect("Failed to load");
+        assert_eq!(commands[0].name, "Build");
+
+        fs::write(&path, r#"{"version": 99, "commands": []}"#).unwrap();
+        let message = get_commands(&path).unwrap_err().to_string();
+        assert!(message.contains("store format version 99"), "{}", message);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_saving_always_writes_the_wrapper() {
+        let temp_dir = std::env::temp_dir().join("climgr_store_wrapper");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("commands.json");
+
+        fs::write(&path, r#"[{"id": "1", "name": "Build", "script": "make"}]"#).unwrap();
+        let commands = get_commands(&path).unwrap();
+        save_commands(&path, &commands).expect("Failed to save");
+
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["version"], STORE_VERSION);
+        assert_eq!(saved["commands"][0]["id"], "1");
+        assert_eq!(get_commands(&path).unwrap(), commands);
+
+        save_commands(&path, &[]).unwrap();
+        let empty: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(empty["commands"], serde_json::json!([]));
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_saved_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = std::env::temp_dir().join("climgr_permissions");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let store = temp_dir.join("commands.json");
+        let command = Command {
+            id: "deploy".to_string(),
+            script: "curl -H 'Authorization: Bearer s3cret' example.com".to_string(),
+            ..Default::default()
+        };
+        save_commands_with(&store, &[command], external()).unwrap();
+        assert_eq!(mode(&store), 0o600);
+        assert_eq!(mode(&temp_dir.join("scripts").join("deploy.sh")), 0o600);
+
+        for name in ["config.json", "config.toml"] {
+            let path = temp_dir.join(name);
+            save_config(&path, &Config::default()).unwrap();
+            assert_eq!(mode(&path), 0o600, "{}", name);
+
+            // Opting out leaves the mode alone
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            let relaxed = Config {
+                restrict_permissions: Some(false),
+                ..Default::default()
+            };
+            save_config(&path, &relaxed).unwrap();
+            assert_eq!(mode(&path), 0o644, "{}", name);
+        }
+
+        let trash = trash_path_for(&store);
+        save_trash(&trash, &[], true).unwrap();
+        assert_eq!(mode(&trash), 0o600);
+
+        let corrupt = temp_dir.join("corrupt.json");
+        fs::write(&corrupt, r#"[{"id": "1", "script": 42}]"#).unwrap();
+        assert!(get_commands(&corrupt).unwrap().is_empty());
+        assert_eq!(mode(&quarantine_path(&corrupt)), 0o600);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_commands_yaml_round_trip() {
+        let command = Command {
+            id: "deploy".to_string(),
+            name: "Deploy".to_string(),
+            script: "set -e\nnpm run build\n./deploy.sh --prod\n".to_string(),
+            group: Some("Web".to_string()),
+            ..Default::default()
+        };
+
+        let yaml = commands_to_yaml(std::slice::from_ref(&command)).expect("Failed to export");
+        assert!(yaml.contains("script: |"), "{}", yaml);
+        assert!(
+            !yaml.contains("null"),
+            "Unset fields should be omitted: {}",
+            yaml
+        );
+
+        let imported = commands_from_yaml(&yaml).expect("Failed to import");
+        assert_eq!(imported, vec![command]);
+
+        let err = commands_from_yaml("- id: [unclosed").unwrap_err();
+        assert_eq!(err.code(), "parse");
+        assert!(err.to_string().starts_with("Invalid YAML"), "{}", err);
+    }
+
     #[test]
     fn test_save_and_load_config() {
         let temp_dir = std::env::temp_dir();
@@ -195,19 +1399,41 @@ mod tests {
 
         // Test loading non-existent file returns default
         let default_config = get_config(&file_path).expect("Should return default config");
-        assert_eq!(default_config.safe_mode, false);
+        assert!(!default_config.safe_mode);
 
         // Save a config with safe mode enabled
         let config = Config {
             safe_mode: true,
             commands_path: None,
             accessibility_notice_dismissed: Some(false),
+            max_script_length: None,
+            graceful_kill: false,
+            scripts_external: false,
+            safe_mode_passphrase_hash: None,
+            safe_mode_bypass_token: None,
+            output_batch_lines: None,
+            output_batch_interval_ms: None,
+            read_only: None,
+            ui_output_max_chars: None,
+            strict_templates: None,
+            output_encoding: None,
+            restrict_permissions: None,
+            commands_dir: None,
+            strip_ansi: None,
+            reserved_shortcut_overrides: None,
+            serialize_executions: None,
+            login_shell: None,
+            interactive_shell: None,
+            profile_env: None,
+            panic_shortcut: None,
+            kill_signal: None,
+            kill_grace_secs: None,
         };
         save_config(&file_path, &config).expect("Failed to save config");
 
         // Load and verify
         let loaded = get_config(&file_path).expect("Failed to load config");
-        assert_eq!(loaded.safe_mode, true);
+        assert!(loaded.safe_mode);
 
         // Cleanup
         if file_path.exists() {
@@ -253,6 +1479,432 @@ mod tests {
         assert_eq!(home_count, 1);
     }
 
+    /// Serializes tests that set the path environment variables.
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_resolve_store_path_precedence() {
+        let _env = ENV_LOCK.lock().unwrap();
+        let app_data_dir = Path::new("/app/data");
+        let config = Config {
+            safe_mode: false,
+            commands_path: Some("/custom/commands.json".to_string()),
+            accessibility_notice_dismissed: None,
+            max_script_length: None,
+            graceful_kill: false,
+            scripts_external: false,
+            safe_mode_passphrase_hash: None,
+            safe_mode_bypass_token: None,
+            output_batch_lines: None,
+            output_batch_interval_ms: None,
+            read_only: None,
+            ui_output_max_chars: None,
+            strict_templates: None,
+            output_encoding: None,
+            restrict_permissions: None,
+            commands_dir: None,
+            strip_ansi: None,
+            reserved_shortcut_overrides: None,
+            serialize_executions: None,
+            login_shell: None,
+            interactive_shell: None,
+            profile_env: None,
+            panic_shortcut: None,
+            kill_signal: None,
+            kill_grace_secs: None,
+        };
+
+        std::env::set_var(STORE_PATH_ENV, "/from/env/commands.json");
+        let from_env = resolve_store_path(app_data_dir, Some(&config));
+        std::env::remove_var(STORE_PATH_ENV);
+        assert_eq!(from_env, PathBuf::from("/from/env/commands.json"));
+
+        let from_config = resolve_store_path(app_data_dir, Some(&config));
+        assert_eq!(from_config, PathBuf::from("/custom/commands.json"));
+
+        let default = resolve_store_path(app_data_dir, None);
+        assert_eq!(default, PathBuf::from("/app/data/commands.json"));
+    }
+
+    #[test]
+    fn test_data_dir_env_overrides_app_data_dir() {
+        let _env = ENV_LOCK.lock().unwrap();
+        let temp_dir = std::env::temp_dir().join("climgr_data_dir_env");
+        let _ = fs::remove_dir_all(&temp_dir);
+
+        std::env::set_var(DATA_DIR_ENV, &temp_dir);
+        let data_dir = resolve_data_dir(|| panic!("default shouldn't be used"));
+        std::env::remove_var(DATA_DIR_ENV);
+        let data_dir = data_dir.unwrap();
+        assert_eq!(data_dir, temp_dir);
+
+        assert_eq!(
+            resolve_store_path(&data_dir, None),
+            temp_dir.join("commands.json")
+        );
+        assert_eq!(resolve_config_path(&data_dir), temp_dir.join("config.json"));
+
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("config.toml"), "safe_mode = false\n").unwrap();
+        assert_eq!(resolve_config_path(&data_dir), temp_dir.join("config.toml"));
+
+        // Unset, the default is used
+        let fallback = resolve_data_dir(|| Ok(PathBuf::from("/app/data"))).unwrap();
+        assert_eq!(fallback, PathBuf::from("/app/data"));
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_resolve_commands_path() {
+        let app_data_dir = Path::new("/app/data");
+
+        let relative = resolve_commands_path(app_data_dir, "mystore/commands.json");
+        assert_eq!(relative, PathBuf::from("/app/data/mystore/commands.json"));
+
+        let absolute = resolve_commands_path(app_data_dir, "/custom/commands.json");
+        assert_eq!(absolute, PathBuf::from("/custom/commands.json"));
+
+        let home = std::env::var("HOME").expect("HOME not set");
+        let from_home = resolve_commands_path(app_data_dir, "~/climgr/commands.json");
+        assert_eq!(from_home, PathBuf::from(home).join("climgr/commands.json"));
+    }
+
+    #[test]
+    fn test_validate_store_location() {
+        let temp_dir = std::env::temp_dir().join("climgr_validate_location");
+        let _ = fs::remove_dir_all(&temp_dir);
+
+        // A missing directory is created and accepted
+        let valid = temp_dir.join("nested").join("commands.json");
+        validate_store_location(&valid).expect("Writable location should validate");
+        assert!(valid.parent().unwrap().exists());
+        assert!(
+            !valid.exists(),
+            "Validation should not create the store file"
+        );
+
+        // A regular file in the way of the directory is rejected
+        let blocker = temp_dir.join("blocker");
+        fs::write(&blocker, b"not a directory").unwrap();
+        let invalid = blocker.join("sub").join("commands.json");
+        let err = validate_store_location(&invalid).unwrap_err();
+        assert_eq!(err.code(), "io");
+        assert!(err.to_string().contains("Cannot create storage directory"));
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_migrate_commands() {
+        let temp_dir = std::env::temp_dir().join("climgr_migrate_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let from = temp_dir.join("old").join("commands.json");
+        let to = temp_dir.join("new").join("commands.json");
+
+        let commands = vec![
+            Command {
+                id: "1".to_string(),
+                name: "One".to_string(),
+                script: "echo 1".to_string(),
+                ..Default::default()
+            },
+            Command {
+                id: "2".to_string(),
+                name: "Two".to_string(),
+                script: "echo 2".to_string(),
+                shortcut: Some("Ctrl+2".to_string()),
+                ..Default::default()
+            },
+        ];
+        save_commands(&from, &commands).unwrap();
+
+        let migrated = migrate_commands(&from, &to, true).expect("Migration should succeed");
+        assert_eq!(migrated, 2);
+        assert!(from.exists(), "Old file should be kept");
+        let loaded = get_commands(&to).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[1].shortcut.as_deref(), Some("Ctrl+2"));
+
+        // Moving without keeping the old file removes it
+        let moved = temp_dir.join("moved").join("commands.json");
+        assert_eq!(migrate_commands(&to, &moved, false).unwrap(), 2);
+        assert!(!to.exists());
+        assert!(moved.exists());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_config_change_migrates_and_resaves() {
+        let temp_dir = std::env::temp_dir().join("climgr_config_change");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let from = temp_dir.join("commands.json");
+        let to = temp_dir.join("moved").join("commands.json");
+        save_commands(&from, &[named("1", "Build")]).unwrap();
+
+        let current = Config::default();
+        let external = Config {
+            scripts_external: true,
+            ..Default::default()
+        };
+        let migrated = apply_config_change(&from, &to, None, &current, &external).unwrap();
+        assert_eq!(migrated, 1);
+        assert!(from.exists());
+        assert!(to.with_file_name("scripts").join("1.sh").exists());
+        assert_eq!(get_commands(&to).unwrap()[0].script, "echo Build");
+
+        // Include files are re-saved in place along with the primary store
+        let dir = temp_dir.join("commands.d");
+        let team = dir.join("team.json");
+        save_commands(&team, &[named("2", "Deploy")]).unwrap();
+        apply_config_change(&to, &to, Some(&dir), &current, &external).unwrap();
+        assert!(dir.join("scripts").join("2.sh").exists());
+        assert!(get_commands(&team).unwrap()[0].script_file.is_some());
+        let (merged, _) = load_merged(&to, &dir).unwrap();
+        let ids: Vec<&str> = merged.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+        assert_eq!(merged[1].script, "echo Deploy");
+
+        apply_config_change(&to, &to, Some(&dir), &external, &current).unwrap();
+        assert_eq!(get_commands(&to).unwrap()[0].script_file, None);
+        assert_eq!(get_commands(&team).unwrap()[0].script_file, None);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_read_only_config_change_leaves_the_store_alone() {
+        let temp_dir = std::env::temp_dir().join("climgr_config_change_read_only");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let from = temp_dir.join("commands.json");
+        let to = temp_dir.join("moved").join("commands.json");
+        save_commands(&from, &[named("1", "Build")]).unwrap();
+        let before = fs::read_to_string(&from).unwrap();
+
+        let read_only = Config {
+            read_only: Some(true),
+            scripts_external: true,
+            ..Default::default()
+        };
+        // Neither a migration nor a re-save for the scripts_external flip
+        let migrated =
+            apply_config_change(&from, &to, None, &Config::default(), &read_only).unwrap();
+        assert_eq!(migrated, 0);
+        assert!(!to.exists());
+        apply_config_change(&from, &from, None, &Config::default(), &read_only).unwrap();
+        assert_eq!(fs::read_to_string(&from).unwrap(), before);
+        assert!(!temp_dir.join("scripts").exists());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_trash_file_round_trip() {
+        let temp_dir = std::env::temp_dir().join("climgr_trash_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let trash_path = trash_path_for(&temp_dir.join("commands.json"));
+        assert_eq!(
+            trash_path,
+            temp_dir.join("trash").join("trashed_commands.json")
+        );
+
+        assert!(get_trash(&trash_path).unwrap().is_empty());
+
+        let trash = vec![TrashedCommand {
+            command: Command {
+                id: "1".to_string(),
+                name: "Old".to_string(),
+                script: "echo old".to_string(),
+                ..Default::default()
+            },
+            deleted_at: "2026-01-01T00:00:00+00:00".to_string(),
+        }];
+        save_trash(&trash_path, &trash, true).expect("Failed to save trash");
+        assert_eq!(get_trash(&trash_path).unwrap(), trash);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_file_lock_excludes_other_writers() {
+        let temp_dir = std::env::temp_dir().join("climgr_file_lock");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let store = temp_dir.join("commands.json");
+        let lock_path = FileLock::path_for(&store);
+        assert_eq!(lock_path, temp_dir.join("commands.json.lock"));
+
+        let held = FileLock::acquire(&store, LOCK_TIMEOUT).unwrap();
+        assert!(lock_path.exists());
+
+        // A second writer gives up after the timeout, and so does a save
+        let err = FileLock::acquire(&store, Duration::from_millis(100)).unwrap_err();
+        assert!(err.to_string().contains("another climgr"), "{}", err);
+
+        // Released on drop, after which a waiting writer gets through
+        let waiter = {
+            let store = store.clone();
+            std::thread::spawn(move || save_commands(&store, &[]))
+        };
+        std::thread::sleep(Duration::from_millis(100));
+        drop(held);
+        waiter
+            .join()
+            .unwrap()
+            .expect("Save should wait for the lock");
+        assert!(!lock_path.exists());
+
+        // A lock left behind by a crashed writer doesn't block forever
+        fs::write(&lock_path, "12345").unwrap();
+        let old = SystemTime::now() - STALE_LOCK_AGE - Duration::from_secs(1);
+        fs::File::options()
+            .write(true)
+            .open(&lock_path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        save_config(&temp_dir.join("config.json"), &Config::default()).unwrap();
+        save_commands(&store, &[]).expect("Stale lock should be taken over");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let temp_dir = std::env::temp_dir().join("climgr_update_commands");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let store = temp_dir.join("commands.json");
+        save_commands(&store, &[named("a", "A")]).unwrap();
+
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    update_commands(&store, SaveOptions::default(), |commands| {
+                        let command = &mut commands[0];
+                        command.run_count = Some(command.run_count.unwrap_or(0) + 1);
+                        Ok(())
+                    })
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer
+                .join()
+                .unwrap()
+                .expect("Update should wait for the lock");
+        }
+        assert_eq!(get_commands(&store).unwrap()[0].run_count, Some(8));
+
+        // A failed update writes nothing
+        let before = fs::read_to_string(&store).unwrap();
+        let err = update_commands(&store, SaveOptions::default(), |commands| {
+            commands.clear();
+            Err::<(), _>(AppError::NotFound("Command not found".to_string()))
+        });
+        assert!(err.is_err());
+        assert_eq!(fs::read_to_string(&store).unwrap(), before);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    fn named(id: &str, name: &str) -> Command {
+        Command {
+            id: id.to_string(),
+            name: name.to_string(),
+            script: format!("echo {}", name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_load_merged_and_save_back_to_origin() {
+        let temp_dir = std::env::temp_dir().join("climgr_test_merged");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let primary = temp_dir.join("commands.json");
+        let dir = temp_dir.join("commands.d");
+        save_commands(&primary, &[named("1", "Main")]).unwrap();
+        save_commands(&dir.join("b.json"), &[named("3", "Deploy")]).unwrap();
+        save_commands(&dir.join("a.json"), &[named("2", "Build")]).unwrap();
+        fs::write(dir.join("notes.txt"), "not commands").unwrap();
+
+        let (mut commands, included) = load_merged(&primary, &dir).unwrap();
+        let ids: Vec<&str> = commands.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        assert_eq!(included.files["2"], dir.join("a.json"));
+        assert!(!included.files.contains_key("1"));
+        assert!(included.duplicates.is_empty());
+
+        commands[2].name = "Deploy prod".to_string();
+        commands.push(named("4", "New"));
+        save_merged(&primary, &commands, &included, SaveOptions::default()).unwrap();
+
+        let names = |path: &Path| -> Vec<String> {
+            get_commands(path)
+                .unwrap()
+                .into_iter()
+                .map(|c| c.name)
+                .collect()
+        };
+        assert_eq!(names(&primary), vec!["Main", "New"]);
+        assert_eq!(names(&dir.join("a.json")), vec!["Build"]);
+        assert_eq!(names(&dir.join("b.json")), vec!["Deploy prod"]);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_load_merged_reports_duplicate_ids() {
+        let temp_dir = std::env::temp_dir().join("climgr_test_merged_duplicates");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let primary = temp_dir.join("commands.json");
+        let dir = temp_dir.join("commands.d");
+        save_commands(&primary, &[named("1", "Main")]).unwrap();
+        let (a, b) = (dir.join("a.json"), dir.join("b.json"));
+        save_commands(&a, &[named("1", "Clash"), named("2", "Build")]).unwrap();
+        save_commands(&b, &[named("2", "Other build")]).unwrap();
+
+        let (commands, included) = load_merged(&primary, &dir).unwrap();
+        let names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Main", "Build"]);
+
+        let duplicates: Vec<(&str, &Path, &Path)> = included
+            .duplicates
+            .iter()
+            .map(|d| {
+                (
+                    d.command.name.as_str(),
+                    d.file.as_path(),
+                    d.first_file.as_path(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            duplicates,
+            vec![
+                ("Clash", a.as_path(), primary.as_path()),
+                ("Other build", b.as_path(), a.as_path()),
+            ]
+        );
+        assert_eq!(
+            included.duplicates[0].issue().kind,
+            StoreIssueKind::DuplicateId
+        );
+
+        // Saving keeps the skipped duplicates in their files
+        save_merged(&primary, &commands, &included, SaveOptions::default()).unwrap();
+        let ids: Vec<String> = get_commands(&a)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec!["2", "1"]);
+        assert_eq!(get_commands(&b).unwrap().len(), 1);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
     #[test]
     fn test_ensure_directory_creation() {
         let temp_dir = std::env::temp_dir();
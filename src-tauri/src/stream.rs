@@ -0,0 +1,266 @@
+use regex::Regex;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Lines a batch holds before it is flushed, unless `Config.output_batch_lines` is set.
+pub const DEFAULT_BATCH_LINES: usize = 100;
+
+/// How long a line may wait before its batch is flushed, unless
+/// `Config.output_batch_interval_ms` is set.
+pub const DEFAULT_BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Lines buffered between the reader threads and the emitter.
+///
+/// Once it is full, readers block until the emitter catches up, which in turn stops
+/// draining the pipe and slows a flooding process down instead of piling up events.
+pub const CHANNEL_CAPACITY: usize = 1024;
+
+/// When a batch of output lines is flushed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchSettings {
+    /// Flush as soon as this many lines are buffered
+    pub max_lines: usize,
+    /// Flush once the oldest buffered line has waited this long
+    pub interval: Duration,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self {
+            max_lines: DEFAULT_BATCH_LINES,
+            interval: DEFAULT_BATCH_INTERVAL,
+        }
+    }
+}
+
+/// Coalesces output lines into batches by count and age.
+pub struct Batcher {
+    settings: BatchSettings,
+    lines: Vec<String>,
+    /// When the oldest buffered line arrived
+    since: Option<Instant>,
+}
+
+impl Batcher {
+    pub fn new(settings: BatchSettings) -> Self {
+        Self {
+            settings: BatchSettings {
+                max_lines: settings.max_lines.max(1),
+                ..settings
+            },
+            lines: Vec::new(),
+            since: None,
+        }
+    }
+
+    /// Buffers a line, returning the batch if it is now full.
+    pub fn push(&mut self, line: String, now: Instant) -> Option<Vec<String>> {
+        self.since.get_or_insert(now);
+        self.lines.push(line);
+        if self.lines.len() >= self.settings.max_lines {
+            return self.flush();
+        }
+        None
+    }
+
+    /// Returns the batch if its oldest line has waited out the interval.
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<String>> {
+        match self.deadline() {
+            Some(deadline) if now >= deadline => self.flush(),
+            _ => None,
+        }
+    }
+
+    /// When the buffered lines are due, or `None` if nothing is buffered.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.since.map(|since| since + self.settings.interval)
+    }
+
+    /// Returns whatever is buffered, if anything.
+    pub fn flush(&mut self) -> Option<Vec<String>> {
+        self.since = None;
+        if self.lines.is_empty() {
+            return None;
+        }
+        Some(std::mem::take(&mut self.lines))
+    }
+}
+
+/// Receives lines until every sender is dropped, passing them to `emit` in batches.
+///
+/// The last partial batch is flushed once the channel closes, so no output is lost.
+pub fn forward(
+    lines: Receiver<String>,
+    settings: BatchSettings,
+    mut emit: impl FnMut(Vec<String>),
+) {
+    let mut batcher = Batcher::new(settings);
+    loop {
+        let received = match batcher.deadline() {
+            Some(deadline) => {
+                lines.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let batch = match received {
+            Ok(line) => batcher.push(line, Instant::now()),
+            Err(RecvTimeoutError::Timeout) => batcher.poll(Instant::now()),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if let Some(batch) = batch {
+            emit(batch);
+        }
+    }
+    if let Some(batch) = batcher.flush() {
+        emit(batch);
+    }
+}
+
+/// Compiles a command's `progress_regex`.
+pub fn progress_pattern(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid progress regex '{}': {}", pattern, e))
+}
+
+/// The percentage `line` reports, read from the first capture group of the last match
+/// of `pattern` (the whole match if it has no groups) and clamped to 0-100.
+///
+/// The last match counts because tools like `rsync` redraw their progress with `\r`,
+/// so one line can hold many updates. `None` if nothing matches or the capture isn't a
+/// number.
+pub fn progress_percent(pattern: &Regex, line: &str) -> Option<f64> {
+    let captures = pattern.captures_iter(line).last()?;
+    let text = captures.get(1).or_else(|| captures.get(0))?.as_str();
+    let percent: f64 = text.trim().trim_end_matches('%').parse().ok()?;
+    percent.is_finite().then(|| percent.clamp(0.0, 100.0))
+}
+
+/// The most recent percentage reported in a batch of lines, see [`progress_percent`].
+pub fn latest_progress(pattern: &Regex, lines: &[String]) -> Option<f64> {
+    lines
+        .iter()
+        .rev()
+        .find_map(|line| progress_percent(pattern, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|i| format!("line {}", i)).collect()
+    }
+
+    #[test]
+    fn test_batcher_flushes_by_count_and_age() {
+        let mut batcher = Batcher::new(BatchSettings {
+            max_lines: 3,
+            interval: Duration::from_millis(50),
+        });
+        let start = Instant::now();
+
+        assert_eq!(batcher.push("line 0".into(), start), None);
+        assert_eq!(batcher.push("line 1".into(), start), None);
+        assert_eq!(batcher.push("line 2".into(), start), Some(lines(0..3)));
+        assert_eq!(batcher.deadline(), None);
+
+        // A lone line waits for the interval, measured from when it arrived
+        let later = start + Duration::from_millis(10);
+        assert_eq!(batcher.push("line 3".into(), later), None);
+        assert_eq!(batcher.poll(later + Duration::from_millis(49)), None);
+        assert_eq!(
+            batcher.poll(later + Duration::from_millis(50)),
+            Some(lines(3..4))
+        );
+        assert_eq!(batcher.flush(), None);
+    }
+
+    #[test]
+    fn test_forward_coalesces_a_flood() {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(CHANNEL_CAPACITY);
+        let feeder = std::thread::spawn(move || {
+            for line in lines(0..250) {
+                sender.send(line).unwrap();
+            }
+        });
+
+        let mut batches = Vec::new();
+        forward(
+            receiver,
+            BatchSettings {
+                max_lines: 100,
+                interval: Duration::from_secs(10),
+            },
+            |batch| batches.push(batch),
+        );
+        feeder.join().unwrap();
+
+        // Two full batches, then the remainder once the sender hangs up
+        let sizes: Vec<usize> = batches.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![100, 100, 50]);
+        assert_eq!(batches.concat(), lines(0..250));
+    }
+
+    #[test]
+    fn test_forward_flushes_a_trickle_on_time() {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(CHANNEL_CAPACITY);
+        let feeder = std::thread::spawn(move || {
+            sender.send("first".to_string()).unwrap();
+            std::thread::sleep(Duration::from_millis(300));
+            sender.send("second".to_string()).unwrap();
+        });
+
+        let start = Instant::now();
+        let mut batches = Vec::new();
+        forward(
+            receiver,
+            BatchSettings {
+                max_lines: 100,
+                interval: Duration::from_millis(20),
+            },
+            |batch| batches.push((start.elapsed(), batch)),
+        );
+        feeder.join().unwrap();
+
+        assert_eq!(batches.len(), 2);
+        // The first line went out on its timer, well before the second arrived
+        assert!(
+            batches[0].0 < Duration::from_millis(250),
+            "{:?}",
+            batches[0].0
+        );
+        assert_eq!(batches[0].1, vec!["first"]);
+        assert_eq!(batches[1].1, vec!["second"]);
+    }
+
+    #[test]
+    fn test_progress_percent_from_an_rsync_line() {
+        let pattern = progress_pattern(r"(\d+(?:\.\d+)?)%").unwrap();
+        let line = "     32,768  12%   31.25MB/s    0:00:01\r  1,048,576  45%  1.00MB/s  0:00:12";
+        assert_eq!(progress_percent(&pattern, line), Some(45.0));
+        assert_eq!(
+            progress_percent(&pattern, "sending incremental file list"),
+            None
+        );
+
+        // Without a group the whole match is used, and values are kept in range
+        let whole = progress_pattern(r"\d+%").unwrap();
+        assert_eq!(progress_percent(&whole, "done 150%"), Some(100.0));
+
+        let lines = vec![
+            "file1  10%".to_string(),
+            "file2  20%".to_string(),
+            "speedup is 1.00".to_string(),
+        ];
+        assert_eq!(latest_progress(&pattern, &lines), Some(20.0));
+    }
+
+    #[test]
+    fn test_invalid_progress_regex() {
+        let err = progress_pattern("(\\d+%").unwrap_err();
+        assert!(
+            err.starts_with("Invalid progress regex '(\\d+%'"),
+            "{}",
+            err
+        );
+    }
+}
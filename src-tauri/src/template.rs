@@ -0,0 +1,466 @@
+use crate::models::{Command, CommandVariable};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+
+/// Values substituted into a script by [`render_script`].
+///
+/// The built-in variables are `{{command.id}}`, `{{command.name}}`, `{{store_dir}}`,
+/// `{{home}}` and `{{now}}`; any other `{{name}}` is looked up in `user`. Built-ins
+/// take precedence over a user value of the same name.
+#[derive(Debug, Clone, Default)]
+pub struct Params {
+    /// Directory holding the commands store
+    pub store_dir: String,
+    /// The user's home directory
+    pub home: String,
+    /// ISO 8601 timestamp of the run
+    pub now: String,
+    /// Values supplied for this run
+    pub user: HashMap<String, String>,
+    /// Reject unknown `{{...}}` tokens instead of leaving them intact
+    pub strict: bool,
+}
+
+/// Substitutes template variables in `command.script`.
+///
+/// Whitespace inside the braces is ignored, so `{{ home }}` works too. Unknown tokens
+/// are left as they are, so scripts using braces for their own purposes (like
+/// `docker ps --format '{{.Names}}'`) run unchanged, unless `params.strict` is set.
+pub fn render_script(command: &Command, params: &Params) -> Result<String, String> {
+    render(&command.script, command, params)
+}
+
+/// `command` with [`render_script`] applied to its script and each of its steps.
+pub fn render_command(command: &Command, params: &Params) -> Result<Command, String> {
+    let steps = match &command.steps {
+        Some(steps) => Some(
+            steps
+                .iter()
+                .map(|step| render(step, command, params))
+                .collect::<Result<_, _>>()?,
+        ),
+        None => None,
+    };
+    Ok(Command {
+        script: render_script(command, params)?,
+        steps,
+        ..command.clone()
+    })
+}
+
+/// Fills in the declared `variables` missing from `supplied` with their defaults.
+///
+/// Undeclared values are passed through as they are. Fails naming the first required
+/// variable (one without a default) that has no value.
+pub fn resolve_variables(
+    variables: &[CommandVariable],
+    mut supplied: HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    for variable in variables {
+        if supplied.contains_key(&variable.name) {
+            continue;
+        }
+        match &variable.default {
+            Some(default) => {
+                supplied.insert(variable.name.clone(), default.clone());
+            }
+            None => return Err(format!("Missing value for variable '{}'", variable.name)),
+        }
+    }
+    Ok(supplied)
+}
+
+fn render(text: &str, command: &Command, params: &Params) -> Result<String, String> {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find("{{") {
+        let Some(close) = rest[open + 2..].find("}}") else {
+            break;
+        };
+        let token = &rest[open..open + 2 + close + 2];
+        let name = token[2..token.len() - 2].trim();
+        rendered.push_str(&rest[..open]);
+        match lookup(name, command, params) {
+            Some(value) => rendered.push_str(value),
+            None if params.strict => {
+                return Err(format!("Unknown template variable '{}'", token));
+            }
+            None => rendered.push_str(token),
+        }
+        rest = &rest[open + token.len()..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+fn lookup<'a>(name: &str, command: &'a Command, params: &'a Params) -> Option<&'a str> {
+    match name {
+        "command.id" => Some(&command.id),
+        "command.name" => Some(&command.name),
+        "store_dir" => Some(&params.store_dir),
+        "home" => Some(&params.home),
+        "now" => Some(&params.now),
+        _ => params.user.get(name).map(String::as_str),
+    }
+}
+
+/// `command`'s script (or its steps, one per line) preceded by a comment listing the
+/// environment variables it runs with, for auditing what gets injected.
+///
+/// Each variable is shown with its value and where it comes from: `profile_env`, or the
+/// command's `env`, noting when that overrides a profile value. Variables the script
+/// never references as `$NAME` or `${NAME}` are marked as such. Without any variables
+/// the script is returned as is.
+pub fn annotate_env(command: &Command, profile_env: &BTreeMap<String, String>) -> String {
+    let script = match command.steps.as_deref().filter(|steps| !steps.is_empty()) {
+        Some(steps) => steps.join("\n"),
+        None => command.script.clone(),
+    };
+    let command_env = command.env.clone().unwrap_or_default();
+    let mut names: Vec<&String> = profile_env.keys().chain(command_env.keys()).collect();
+    names.sort();
+    names.dedup();
+    if names.is_empty() {
+        return script;
+    }
+
+    let mut annotated = "# Environment:\n".to_string();
+    for name in names {
+        let (value, mut source) = match command_env.get(name) {
+            Some(value) if profile_env.contains_key(name) => {
+                (value, "env, overrides profile_env".to_string())
+            }
+            Some(value) => (value, "env".to_string()),
+            None => (&profile_env[name], "profile_env".to_string()),
+        };
+        if !references_var(&script, name) {
+            source.push_str(", not referenced");
+        }
+        annotated.push_str(&format!("#   {}={} ({})\n", name, value, source));
+    }
+    annotated.push('\n');
+    annotated.push_str(&script);
+    annotated
+}
+
+/// `command` as a standalone shell script, for saving as a `.sh` file and running
+/// outside climgr.
+///
+/// The script starts with a `#!/bin/sh` shebang, with `-l`/`-i` when the command runs
+/// in a login or interactive shell, and a comment naming the command. Its own `env` is
+/// exported. Values from `profile_env` may be secrets and aren't copied: each one the
+/// script references gets a line that stops the script unless it is set. Steps each run
+/// in a subshell and the script exits at the first one that fails, as in climgr.
+/// Variables whose names aren't shell identifiers are left out.
+pub fn shell_script(command: &Command, profile_env: &BTreeMap<String, String>) -> String {
+    let steps = command.steps.as_deref().filter(|steps| !steps.is_empty());
+    let body = match steps {
+        Some(steps) => steps.join("\n"),
+        None => command.script.clone(),
+    };
+
+    let flags = match (
+        command.login_shell.unwrap_or(false),
+        command.interactive_shell.unwrap_or(false),
+    ) {
+        (true, true) => " -li",
+        (true, false) => " -l",
+        (false, true) => " -i",
+        (false, false) => "",
+    };
+    let mut script = format!("#!/bin/sh{}\n# {}\n", flags, command.name);
+    if let Some(description) = command.description.as_deref().map(str::trim) {
+        if !description.is_empty() {
+            script.push_str(&format!("# {}\n", description));
+        }
+    }
+    if let Some(user) = command.run_as() {
+        script.push_str(&format!("# Runs as {} in climgr (via sudo)\n", user));
+    } else if command.requires_sudo.unwrap_or(false) {
+        script.push_str("# Runs with sudo in climgr\n");
+    }
+
+    let command_env = command.env.clone().unwrap_or_default();
+    let mut preamble = String::new();
+    // Names that aren't shell identifiers are rejected on save; skip any that slipped
+    // into the store rather than writing them into the script
+    let valid = |name: &&String| crate::commands::is_variable_name(name);
+    for (name, value) in command_env.iter().filter(|(name, _)| valid(name)) {
+        preamble.push_str(&format!("export {}={}\n", name, shell_quote(value)));
+    }
+    for name in profile_env.keys().filter(valid) {
+        if !command_env.contains_key(name) && references_var(&body, name) {
+            preamble.push_str(&format!(
+                ": \"${{{}:?is not set (climgr takes it from profile_env)}}\"\n",
+                name
+            ));
+        }
+    }
+    if !preamble.is_empty() {
+        script.push('\n');
+        script.push_str(&preamble);
+    }
+
+    script.push('\n');
+    match steps {
+        Some(steps) => {
+            for (index, step) in steps.iter().enumerate() {
+                script.push_str(&format!(
+                    "# Step {}\n(\n{}\n) || exit $?\n",
+                    index + 1,
+                    step.trim_end()
+                ));
+            }
+        }
+        None => {
+            script.push_str(command.script.trim_end());
+            script.push('\n');
+        }
+    }
+    script
+}
+
+/// Quotes `value` as a single shell word.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Whether `script` expands the variable `name` as `$name` or `${name...}`.
+fn references_var(script: &str, name: &str) -> bool {
+    let name = regex::escape(name);
+    Regex::new(&format!(r"\$(\{{{}\b|{}\b)", name, name))
+        .map(|pattern| pattern.is_match(script))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(script: &str) -> Command {
+        Command {
+            id: "abc".to_string(),
+            name: "Backup".to_string(),
+            script: script.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn params() -> Params {
+        Params {
+            store_dir: "/data/climgr".to_string(),
+            home: "/home/me".to_string(),
+            now: "2024-05-01T12:00:00+00:00".to_string(),
+            user: HashMap::from([("target".to_string(), "prod".to_string())]),
+            strict: false,
+        }
+    }
+
+    #[test]
+    fn test_render_builtins_and_user_params() {
+        let rendered = render_script(
+            &command(
+                "echo {{command.id}} {{ command.name }} {{store_dir}} {{home}} {{now}} {{target}}",
+            ),
+            &params(),
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "echo abc Backup /data/climgr /home/me 2024-05-01T12:00:00+00:00 prod"
+        );
+    }
+
+    #[test]
+    fn test_builtins_win_over_user_params() {
+        let mut params = params();
+        params
+            .user
+            .insert("home".to_string(), "/elsewhere".to_string());
+        assert_eq!(
+            render_script(&command("cd {{home}}"), &params).unwrap(),
+            "cd /home/me"
+        );
+    }
+
+    #[test]
+    fn test_unknown_tokens_are_kept_unless_strict() {
+        let script = "docker ps --format '{{.Names}}' && echo {{missing}} {{";
+        assert_eq!(render_script(&command(script), &params()).unwrap(), script);
+
+        let strict = Params {
+            strict: true,
+            ..params()
+        };
+        assert_eq!(
+            render_script(&command(script), &strict),
+            Err("Unknown template variable '{{.Names}}'".to_string())
+        );
+        // An unclosed brace pair isn't a token, even in strict mode
+        assert_eq!(
+            render_script(&command("echo {{home"), &strict).unwrap(),
+            "echo {{home"
+        );
+    }
+
+    #[test]
+    fn test_substituted_values_are_not_rendered_again() {
+        let mut params = params();
+        params
+            .user
+            .insert("target".to_string(), "{{home}}".to_string());
+        assert_eq!(
+            render_script(&command("echo {{target}}"), &params).unwrap(),
+            "echo {{home}}"
+        );
+    }
+
+    #[test]
+    fn test_resolve_variables_applies_defaults() {
+        let variables = vec![
+            CommandVariable {
+                name: "env".to_string(),
+                default: Some("staging".to_string()),
+                description: None,
+            },
+            CommandVariable {
+                name: "tag".to_string(),
+                default: None,
+                description: Some("Image tag to deploy".to_string()),
+            },
+        ];
+        let supplied = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let resolved = resolve_variables(&variables, supplied(&[("tag", "v2"), ("extra", "1")]));
+        assert_eq!(
+            resolved,
+            Ok(supplied(&[
+                ("env", "staging"),
+                ("tag", "v2"),
+                ("extra", "1")
+            ]))
+        );
+
+        // A supplied value wins over the default, even when empty
+        let resolved = resolve_variables(&variables, supplied(&[("env", ""), ("tag", "v2")]));
+        assert_eq!(resolved.unwrap()["env"], "");
+
+        assert_eq!(
+            resolve_variables(&variables, supplied(&[("env", "prod")])),
+            Err("Missing value for variable 'tag'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_command_covers_steps() {
+        let mut cmd = command("echo {{command.name}}");
+        cmd.steps = Some(vec!["cd {{store_dir}}".to_string(), "ls".to_string()]);
+
+        let rendered = render_command(&cmd, &params()).unwrap();
+        assert_eq!(rendered.script, "echo Backup");
+        assert_eq!(
+            rendered.steps,
+            Some(vec!["cd /data/climgr".to_string(), "ls".to_string()])
+        );
+        assert_eq!(rendered.id, cmd.id);
+    }
+
+    #[test]
+    fn test_annotate_env() {
+        let profile = BTreeMap::from([
+            ("API_URL".to_string(), "https://api.example.com".to_string()),
+            ("REGION".to_string(), "us-east-1".to_string()),
+            ("TOKEN".to_string(), "abc123".to_string()),
+        ]);
+        let mut cmd = command("curl \"$API_URL/health?region=${REGION:-eu}\"");
+        cmd.env = Some(BTreeMap::from([(
+            "REGION".to_string(),
+            "eu-west-1".to_string(),
+        )]));
+
+        assert_eq!(
+            annotate_env(&cmd, &profile),
+            "# Environment:\n\
+             #   API_URL=https://api.example.com (profile_env)\n\
+             #   REGION=eu-west-1 (env, overrides profile_env)\n\
+             #   TOKEN=abc123 (profile_env, not referenced)\n\
+             \n\
+             curl \"$API_URL/health?region=${REGION:-eu}\""
+        );
+    }
+
+    #[test]
+    fn test_annotate_env_without_variables_returns_the_script() {
+        let cmd = command("echo $HOME");
+        assert_eq!(annotate_env(&cmd, &BTreeMap::new()), "echo $HOME");
+
+        // A longer name sharing the prefix isn't a reference
+        let profile = BTreeMap::from([("API".to_string(), "x".to_string())]);
+        let cmd = command("echo $API_URL");
+        assert!(annotate_env(&cmd, &profile).contains("API=x (profile_env, not referenced)"));
+    }
+
+    #[test]
+    fn test_shell_script() {
+        let mut cmd = command("curl -H \"Authorization: $TOKEN\" \"$API_URL/health\"\n");
+        cmd.description = Some("Checks the API".to_string());
+        cmd.login_shell = Some(true);
+        cmd.env = Some(BTreeMap::from([(
+            "API_URL".to_string(),
+            "https://api.example.com/it's".to_string(),
+        )]));
+        let profile = BTreeMap::from([
+            ("TOKEN".to_string(), "secret".to_string()),
+            ("UNUSED".to_string(), "x".to_string()),
+        ]);
+
+        assert_eq!(
+            shell_script(&cmd, &profile),
+            "#!/bin/sh -l\n\
+             # Backup\n\
+             # Checks the API\n\
+             \n\
+             export API_URL='https://api.example.com/it'\\''s'\n\
+             : \"${TOKEN:?is not set (climgr takes it from profile_env)}\"\n\
+             \n\
+             curl -H \"Authorization: $TOKEN\" \"$API_URL/health\"\n"
+        );
+    }
+
+    #[test]
+    fn test_shell_script_skips_invalid_env_names() {
+        let mut cmd = command("echo \"${BAD NAME}\"");
+        cmd.env = Some(BTreeMap::from([
+            ("X;rm -rf ~;Y".to_string(), "1".to_string()),
+            ("OK".to_string(), "1".to_string()),
+        ]));
+        let profile = BTreeMap::from([("BAD NAME".to_string(), "x".to_string())]);
+
+        let script = shell_script(&cmd, &profile);
+        assert!(script.contains("export OK='1'\n"));
+        assert!(!script.contains("rm -rf"), "{}", script);
+        assert!(!script.contains("is not set"), "{}", script);
+    }
+
+    #[test]
+    fn test_shell_script_with_steps() {
+        let mut cmd = command("");
+        cmd.steps = Some(vec!["cargo build".to_string(), "cargo test".to_string()]);
+        cmd.requires_sudo = Some(true);
+
+        assert_eq!(
+            shell_script(&cmd, &BTreeMap::new()),
+            "#!/bin/sh\n\
+             # Backup\n\
+             # Runs with sudo in climgr\n\
+             \n\
+             # Step 1\n(\ncargo build\n) || exit $?\n\
+             # Step 2\n(\ncargo test\n) || exit $?\n"
+        );
+    }
+}
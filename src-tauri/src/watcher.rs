@@ -0,0 +1,448 @@
+use crate::error::AppError;
+use crate::store;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Mutex, PoisonError};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Managed state holding the filesystem watcher for the store and config files.
+///
+/// The parent directories are watched rather than the files themselves, so editors that
+/// save by replacing the file (and files that don't exist yet) are still picked up.
+/// The `commands_dir` include files and the `scripts/` directories of external scripts
+/// are watched too, as part of the store.
+pub struct StoreWatcher {
+    state: Mutex<Option<WatchState>>,
+    own_writes: Mutex<OwnWrites>,
+}
+
+struct WatchState {
+    watcher: RecommendedWatcher,
+    store_path: PathBuf,
+    config_path: PathBuf,
+    parts: StoreParts,
+    dirs: Vec<PathBuf>,
+}
+
+/// The files besides the commands file that make up the store.
+#[derive(Debug, Default, Clone)]
+pub struct StoreParts {
+    /// Its include files are part of the store
+    pub commands_dir: Option<PathBuf>,
+    /// Every file in these holds an external script
+    pub script_dirs: Vec<PathBuf>,
+}
+
+impl StoreParts {
+    /// Whether `path` is an include file of `commands_dir` or a script file.
+    pub fn contains(&self, path: &Path) -> bool {
+        let Some(parent) = path.parent() else {
+            return false;
+        };
+        self.commands_dir
+            .as_deref()
+            .is_some_and(|dir| same_dir(parent, dir) && store::is_include_file(path))
+            || self.script_dirs.iter().any(|dir| same_dir(parent, dir))
+    }
+
+    /// Every part currently on disk.
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files = self
+            .commands_dir
+            .as_deref()
+            .and_then(|dir| store::include_files(dir).ok())
+            .unwrap_or_default();
+        for dir in &self.script_dirs {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            files.extend(entries.flatten().map(|entry| entry.path()));
+        }
+        files
+    }
+}
+
+impl StoreWatcher {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(None),
+            own_writes: Mutex::new(OwnWrites::default()),
+        }
+    }
+}
+
+impl Default for StoreWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the app itself last wrote to each file, so the watcher can tell its own saves
+/// from edits made elsewhere.
+///
+/// Contents are compared rather than events counted, since backends coalesce and
+/// reorder events: a file still holding what the app wrote needs no reload, whichever
+/// event reports it.
+#[derive(Debug, Default)]
+pub struct OwnWrites {
+    written: HashMap<PathBuf, u64>,
+}
+
+impl OwnWrites {
+    /// Remembers the current contents of `path` as written by the app.
+    pub fn note(&mut self, path: &Path) {
+        match content_hash(path) {
+            Some(hash) => self.written.insert(path.to_path_buf(), hash),
+            None => self.written.remove(path),
+        };
+    }
+
+    /// Whether `path` still holds exactly what the app last wrote to it.
+    pub fn is_own(&self, path: &Path) -> bool {
+        self.written
+            .get(path)
+            .is_some_and(|hash| content_hash(path) == Some(*hash))
+    }
+}
+
+fn content_hash(path: &Path) -> Option<u64> {
+    let contents = std::fs::read(path).ok()?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    contents.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Records that the app has just written `path`, so the watcher doesn't react to it.
+///
+/// Whoever writes is responsible for anything a reload would have done, such as
+/// emitting `store-changed` or re-registering shortcuts.
+pub fn note_own_write(app: &AppHandle, path: &Path) {
+    let state = app.state::<StoreWatcher>();
+    state
+        .own_writes
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .note(path);
+}
+
+/// [`note_own_write`] for the commands file, its include files and its script files.
+pub fn note_store_write(app: &AppHandle) {
+    let state = app.state::<StoreWatcher>();
+    let (store_path, parts) = match state
+        .state
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        Some(ws) => (ws.store_path.clone(), ws.parts.clone()),
+        None => return,
+    };
+    let mut own_writes = state
+        .own_writes
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    own_writes.note(&store_path);
+    for file in parts.files() {
+        own_writes.note(&file);
+    }
+}
+
+/// Which of the watched files an event touched.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Change {
+    pub store: bool,
+    pub config: bool,
+}
+
+/// Starts watching the resolved store and config paths.
+///
+/// Events are forwarded to a dedicated thread, since re-pointing the watcher from inside
+/// its own callback would deadlock some backends.
+pub fn start(app: &AppHandle) -> Result<(), AppError> {
+    let (tx, rx) = mpsc::channel::<Event>();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            let _ = tx.send(event);
+        }
+        Err(e) => log::warn!("File watcher error: {}", e),
+    })
+    .map_err(|e| AppError::Io(format!("Failed to create file watcher: {}", e)))?;
+
+    {
+        let state = app.state::<StoreWatcher>();
+        *state.state.lock().unwrap_or_else(PoisonError::into_inner) = Some(WatchState {
+            watcher,
+            store_path: PathBuf::new(),
+            config_path: PathBuf::new(),
+            parts: StoreParts::default(),
+            dirs: Vec::new(),
+        });
+    }
+    rewatch(app)?;
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        for event in rx {
+            handle_event(&app, &event);
+        }
+    });
+    Ok(())
+}
+
+/// Re-resolves the store and config paths and points the watcher at them.
+///
+/// Called whenever the config changes, since `commands_path`, `commands_dir` or
+/// `scripts_external` may have changed what there is to watch.
+pub fn rewatch(app: &AppHandle) -> Result<(), AppError> {
+    let store_path = crate::get_store_path(app)?;
+    let config_path = crate::get_config_path(app)?;
+    let commands_dir = crate::get_commands_dir(app);
+    let parts = StoreParts {
+        script_dirs: [store_path.parent(), commands_dir.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(|dir| dir.join(store::SCRIPTS_DIR))
+            .collect(),
+        commands_dir,
+    };
+
+    let state = app.state::<StoreWatcher>();
+    let mut guard = state.state.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(ws) = guard.as_mut() else {
+        return Ok(());
+    };
+
+    for dir in ws.dirs.drain(..) {
+        let _ = ws.watcher.unwatch(&dir);
+    }
+
+    for path in [&store_path, &config_path] {
+        let Some(dir) = path.parent() else {
+            continue;
+        };
+        if ws.dirs.iter().any(|d| d == dir) {
+            continue;
+        }
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            log::warn!("Not watching {:?}: {}", dir, e);
+            continue;
+        }
+        ws.watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| AppError::Io(format!("Failed to watch {:?}: {}", dir, e)))?;
+        ws.dirs.push(dir.to_path_buf());
+    }
+
+    // Only watched once they exist; creating them here would leave empty directories
+    let part_dirs = parts.commands_dir.iter().chain(&parts.script_dirs);
+    for dir in part_dirs {
+        if !dir.is_dir() || ws.dirs.iter().any(|d| d == dir) {
+            continue;
+        }
+        ws.watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| AppError::Io(format!("Failed to watch {:?}: {}", dir, e)))?;
+        ws.dirs.push(dir.clone());
+    }
+
+    log::info!(
+        "Watching store {:?} and config {:?}",
+        store_path,
+        config_path
+    );
+    ws.store_path = store_path;
+    ws.config_path = config_path;
+    ws.parts = parts;
+    Ok(())
+}
+
+/// Classifies the paths of a filesystem event against the store and config files.
+pub fn classify(paths: &[PathBuf], store_path: &Path, config_path: &Path) -> Change {
+    Change {
+        store: paths.iter().any(|p| same_file(p, store_path)),
+        config: paths.iter().any(|p| same_file(p, config_path)),
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+    if a.file_name() != b.file_name() {
+        return false;
+    }
+    match (a.parent(), b.parent()) {
+        (Some(pa), Some(pb)) => same_dir(pa, pb),
+        _ => false,
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(ca), Ok(cb)) => ca == cb,
+        _ => false,
+    }
+}
+
+fn handle_event(app: &AppHandle, event: &Event) {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Metadata(_)) => return,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {}
+        _ => return,
+    }
+
+    let (store_path, config_path, parts) = {
+        let state = app.state::<StoreWatcher>();
+        let guard = state.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match guard.as_ref() {
+            Some(ws) => (
+                ws.store_path.clone(),
+                ws.config_path.clone(),
+                ws.parts.clone(),
+            ),
+            None => return,
+        }
+    };
+
+    let mut change = classify(&event.paths, &store_path, &config_path);
+    {
+        let state = app.state::<StoreWatcher>();
+        let own_writes = state
+            .own_writes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        change.store &= !own_writes.is_own(&store_path);
+        change.config &= !own_writes.is_own(&config_path);
+        change.store |= event
+            .paths
+            .iter()
+            .any(|path| parts.contains(path) && !own_writes.is_own(path));
+    }
+    let mut store_changed = change.store;
+
+    if change.config {
+        if let Err(e) = rewatch(app) {
+            log::error!("Failed to re-point file watcher: {}", e);
+        }
+        // A new commands_path means the UI is now looking at a different store
+        if let Ok(new_store_path) = crate::get_store_path(app) {
+            store_changed |= new_store_path != store_path;
+        }
+        match store::get_config(&config_path) {
+            Ok(config) => {
+                if let Err(e) = app.emit("config-changed", config.changed_event()) {
+                    log::error!("Failed to emit config-changed: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Config changed on disk but could not be read: {}", e),
+        }
+    }
+
+    if store_changed {
+        if let Err(e) = crate::reload_store_and_notify(app) {
+            log::error!("Failed to reload store after change: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_event_paths() {
+        let store_path = PathBuf::from("/data/commands.json");
+        let config_path = PathBuf::from("/data/config.json");
+
+        let change = classify(std::slice::from_ref(&store_path), &store_path, &config_path);
+        assert_eq!(
+            change,
+            Change {
+                store: true,
+                config: false
+            }
+        );
+
+        let change = classify(
+            &[PathBuf::from("/data/other.json"), config_path.clone()],
+            &store_path,
+            &config_path,
+        );
+        assert!(!change.store);
+        assert!(change.config);
+
+        let change = classify(
+            &[PathBuf::from("/data/other.json")],
+            &store_path,
+            &config_path,
+        );
+        assert_eq!(change, Change::default());
+    }
+
+    #[test]
+    fn test_classify_matches_equivalent_parent() {
+        let temp_dir = std::env::temp_dir().join("climgr_watcher_test");
+        let _ = std::fs::create_dir_all(&temp_dir);
+
+        // The same directory reached through a `.` component still matches
+        let store_path = temp_dir.join("commands.json");
+        let event_path = temp_dir.join(".").join("commands.json");
+        let change = classify(&[event_path], &store_path, &temp_dir.join("config.json"));
+        assert!(change.store);
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_store_parts_cover_includes_and_scripts() {
+        let parts = StoreParts {
+            commands_dir: Some(PathBuf::from("/data/commands.d")),
+            script_dirs: vec![
+                PathBuf::from("/data/scripts"),
+                PathBuf::from("/data/commands.d/scripts"),
+            ],
+        };
+        assert!(parts.contains(Path::new("/data/commands.d/team.json")));
+        assert!(parts.contains(Path::new("/data/scripts/build.sh")));
+        assert!(parts.contains(Path::new("/data/commands.d/scripts/deploy.sh")));
+
+        // Not include files, or outside every part
+        assert!(!parts.contains(Path::new("/data/commands.d/team.quarantine.json")));
+        assert!(!parts.contains(Path::new("/data/commands.d/team.json.lock")));
+        assert!(!parts.contains(Path::new("/data/commands.d/notes.txt")));
+        assert!(!parts.contains(Path::new("/data/other/team.json")));
+        assert!(!StoreParts::default().contains(Path::new("/data/commands.d/team.json")));
+    }
+
+    #[test]
+    fn test_own_writes_are_told_apart_from_outside_edits() {
+        let temp_dir = std::env::temp_dir().join("climgr_watcher_own_writes");
+        let _ = std::fs::create_dir_all(&temp_dir);
+        let store_path = temp_dir.join("commands.json");
+        std::fs::write(&store_path, "[]").unwrap();
+
+        let mut own_writes = OwnWrites::default();
+        assert!(!own_writes.is_own(&store_path));
+
+        own_writes.note(&store_path);
+        assert!(own_writes.is_own(&store_path));
+
+        // Edited elsewhere afterwards
+        std::fs::write(&store_path, "[{}]").unwrap();
+        assert!(!own_writes.is_own(&store_path));
+
+        // Deleted after the app wrote it
+        own_writes.note(&store_path);
+        std::fs::remove_file(&store_path).unwrap();
+        assert!(!own_writes.is_own(&store_path));
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -2,7 +2,7 @@
 
 import { useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { Command } from '../types';
+import { Command, SaveReport, errorMessage } from '../types';
 
 interface CommandFormProps {
   commandToEdit?: Command | null;
@@ -34,6 +34,7 @@ export default function CommandForm({ commandToEdit, onSuccess, onCancel }: Comm
 
     try {
       setIsSubmitting(true);
+      let saved: Command;
       if (commandToEdit) {
         const updatedCommand: Command = {
           ...commandToEdit,
@@ -43,7 +44,7 @@ export default function CommandForm({ commandToEdit, onSuccess, onCancel }: Comm
           description: description || undefined,
           shortcut: shortcut || undefined,
         };
-        await invoke('update_command', { command: updatedCommand });
+        saved = updatedCommand;
       } else {
         const newCommand: Command = {
             id: crypto.randomUUID(),
@@ -53,12 +54,24 @@ export default function CommandForm({ commandToEdit, onSuccess, onCancel }: Comm
             description: description || undefined,
             shortcut: shortcut || undefined,
         };
-        await invoke('add_command', { command: newCommand });
+        saved = newCommand;
+      }
+      const report = await invoke<SaveReport>(
+        commandToEdit ? 'update_command' : 'add_command',
+        { command: saved },
+      );
+      const ownError = report.shortcut_errors.find((e) => e.command_id === saved.id);
+      const notices = [
+        ...(ownError ? [`The shortcut couldn't be registered: ${ownError.message}`] : []),
+        ...report.warnings,
+      ];
+      if (notices.length > 0) {
+        alert(`Saved, but:\n${notices.join('\n')}`);
       }
       onSuccess();
     } catch (err) {
       console.error("Failed to save command:", err);
-      setError("Failed to save command. " + String(err));
+      setError("Failed to save command. " + errorMessage(err));
     } finally {
       setIsSubmitting(false);
     }
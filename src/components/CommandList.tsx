@@ -2,7 +2,7 @@
 
 import { useEffect, useState, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { Command } from "../types";
+import { Command, ExecutionResult, errorMessage } from "../types";
 import CommandForm from "./CommandForm";
 import SafeModeToggle from "./SafeModeToggle";
 import StorageSettings from "./StorageSettings";
@@ -81,10 +81,10 @@ export default function CommandList() {
     }));
 
     try {
-      const result = await invoke<string>("execute_command", { commandId: cmd.id });
+      const result = await invoke<ExecutionResult>("execute_command", { commandId: cmd.id });
       setExecutionStates(prev => ({
         ...prev,
-        [cmd.id]: { loading: false, output: result, error: null }
+        [cmd.id]: { loading: false, output: result.output, error: null }
       }));
     } catch (err) {
       console.error("Failed to execute command:", err);
@@ -92,7 +92,7 @@ export default function CommandList() {
       // If it was cancelled/killed, it might show as an error
       setExecutionStates(prev => ({
         ...prev,
-        [cmd.id]: { loading: false, output: prev[cmd.id]?.output || null, error: String(err) }
+        [cmd.id]: { loading: false, output: prev[cmd.id]?.output || null, error: errorMessage(err) }
       }));
     }
   };
@@ -103,7 +103,7 @@ export default function CommandList() {
       // We don't set loading to false here, because execute_command will return/throw when killed
     } catch (err) {
       console.error("Failed to kill command:", err);
-      alert("Failed to kill command: " + err);
+      alert("Failed to kill command: " + errorMessage(err));
     }
   };
 
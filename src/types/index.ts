@@ -2,13 +2,248 @@ export interface Command {
   id: string;
   name: string;
   script: string;
+  script_file?: string;
+  steps?: string[];
+  macro_steps?: string[];
+  continue_on_error?: boolean;
+  variables?: CommandVariable[];
+  depends_on?: string[];
+  success_exit_codes?: number[];
   kill_script?: string;
+  status_script?: string;
+  on_success_script?: string;
+  on_failure_script?: string;
   shortcut?: string;
   description?: string;
+  notes?: string;
+  group?: string;
+  locked?: boolean;
+  requires_sudo?: boolean;
+  run_as?: string;
+  env?: Record<string, string>;
+  login_shell?: boolean;
+  interactive_shell?: boolean;
+  nice?: number;
+  min_interval_secs?: number;
+  icon?: string;
+  color?: string;
+  favorite?: boolean;
+  created_at?: string;
+  updated_at?: string;
+  run_count?: number;
+  last_run_at?: string;
+  last_exit_code?: number;
+  capture?: CaptureMode;
+  run_in_terminal?: boolean;
+  detached?: boolean;
+  base64_fallback?: boolean;
+  copy_output?: boolean;
+  output_is_json?: boolean;
+  output_format?: 'json' | 'text';
+  output_filter?: string;
+  failure_pattern?: string;
+  progress_regex?: string;
+  strip_ansi?: boolean;
+  schedule?: string;
+  schedule_enabled?: boolean;
+}
+
+/** A `{{name}}` placeholder a command declares; required when it has no default. */
+export interface CommandVariable {
+  name: string;
+  default?: string;
+  description?: string;
+}
+
+export type CaptureMode = 'both' | 'stdout_only' | 'stderr_only' | 'none';
+
+export interface CommandStatus {
+  running: boolean;
+  healthy?: boolean;
+}
+
+export interface ExecutionResult {
+  output: string;
+  encoding: 'utf8' | 'base64';
+  duration_ms?: number;
+  peak_memory_bytes?: number;
+  steps?: StepResult[];
+  exit_code?: number;
+  success?: boolean;
+  json?: unknown;
+  json_error?: string;
+}
+
+/** One entry of the `execute_commands` result. */
+export interface CommandRunResult {
+  command_id: string;
+  result?: ExecutionResult;
+  error?: AppError;
 }
 
 export interface Config {
   safe_mode: boolean;
   commands_path?: string;
   accessibility_notice_dismissed?: boolean;
+  max_script_length?: number;
+  graceful_kill?: boolean;
+  kill_signal?: 'HUP' | 'INT' | 'QUIT' | 'TERM' | 'KILL';
+  kill_grace_secs?: number;
+  scripts_external?: boolean;
+  safe_mode_passphrase_hash?: string;
+  safe_mode_bypass_token?: string;
+  output_batch_lines?: number;
+  output_batch_interval_ms?: number;
+  read_only?: boolean;
+  ui_output_max_chars?: number;
+  strict_templates?: boolean;
+  output_encoding?: 'utf8' | 'lossy' | 'latin1' | 'raw-base64';
+  restrict_permissions?: boolean;
+  commands_dir?: string;
+  strip_ansi?: boolean;
+  reserved_shortcut_overrides?: string[];
+  serialize_executions?: boolean;
+  login_shell?: boolean;
+  interactive_shell?: boolean;
+  profile_env?: Record<string, string>;
+  panic_shortcut?: string;
+}
+
+/** Payload of the `command-notification` event, emitted when a shortcut run ends. */
+export interface CommandNotification {
+  command_id: string;
+  severity: 'info' | 'success' | 'warning' | 'error';
+  message: string;
+  exit_code?: number;
+}
+
+/** Payload of the `command://progress` event. */
+export interface ProgressUpdate {
+  command_id: string;
+  percent: number;
+}
+
+/** Payload of the `command-output` event. */
+export interface OutputBatch {
+  command_id: string;
+  lines: string[];
+}
+
+export interface ScheduledRun {
+  command_id: string;
+  schedule: string;
+  next_run_at: string;
+}
+
+export interface ProcessStats {
+  cpu_percent: number;
+  memory_bytes: number;
+}
+
+export interface StepResult {
+  index: number;
+  exit_code?: number;
+  output: string;
+}
+
+export type AppErrorCode =
+  | 'not_found'
+  | 'locked'
+  | 'safe_mode_blocked'
+  | 'read_only'
+  | 'rate_limited'
+  | 'invalid'
+  | 'shortcut_invalid'
+  | 'io'
+  | 'parse'
+  | 'execution'
+  | 'other';
+
+/** Error rejected by every backend command. */
+export interface AppError {
+  code: AppErrorCode;
+  message: string;
+}
+
+/** The user-facing text of a rejected `invoke`, whether it carried an AppError or not. */
+export function errorMessage(err: unknown): string {
+  if (err && typeof err === 'object' && 'message' in err) {
+    return String((err as { message: unknown }).message);
+  }
+  return String(err);
+}
+
+export interface StoreIssue {
+  kind:
+    | 'duplicate_id'
+    | 'empty_script'
+    | 'duplicate_shortcut'
+    | 'invalid_shortcut'
+    | 'dangling_reference'
+    | 'reference_cycle'
+    | 'unreadable';
+  command_id: string;
+  message: string;
+}
+
+export interface ShortcutError {
+  command_id: string;
+  shortcut: string;
+  message: string;
+}
+
+export type BatchOp =
+  | { op: 'add'; command: Command }
+  | { op: 'update'; command: Command }
+  | { op: 'delete'; id: string };
+
+export interface BatchOpResult {
+  ok: boolean;
+  error?: AppError;
+  warnings: string[];
+}
+
+export interface SaveReport {
+  shortcut_errors: ShortcutError[];
+  warnings: string[];
+}
+
+/** Returned by `add_commands` and `import_commands_yaml`. */
+export interface BulkSaveReport extends SaveReport {
+  added: number;
+}
+
+export interface PathInfo {
+  path: string;
+  exists: boolean;
+}
+
+/** One check made by `run_diagnostics`. */
+export interface DiagnosticCheck {
+  name: 'shell' | 'store_writable' | 'config_readable' | 'safe_mode' | 'echo';
+  passed: boolean;
+  message: string;
+}
+
+/** Returned by `run_diagnostics`. */
+export interface Diagnostics {
+  passed: boolean;
+  checks: DiagnosticCheck[];
+}
+
+export interface AppPaths {
+  store_path: PathInfo;
+  config_path: PathInfo;
+  app_data_dir: PathInfo;
+}
+
+export interface SkippedLine {
+  line: number;
+  text: string;
+  reason: string;
+}
+
+export interface AliasImportSummary extends SaveReport {
+  imported: string[];
+  skipped: SkippedLine[];
 }